@@ -226,7 +226,11 @@ function refreshLiveCameras() {
         const video = document.getElementById(`live-video-${cam.id}`);
         if (!video) return;
 
-        const src = `/api/hls/${cam.id}/live.m3u8`;
+        // The grid shows many cameras at once: use the low-resolution
+        // substream where one is configured.
+        const src = cam.substream_url
+            ? `/api/hls/${cam.id}/live.m3u8?stream=sub`
+            : `/api/hls/${cam.id}/index.m3u8`;
 
         if (Hls.isSupported()) {
             const hls = new Hls({ liveSyncDurationCount: 1, liveMaxLatencyDurationCount: 3 });
@@ -315,7 +319,7 @@ function displaySegmentsList(segments) {
         const sizeBytes = seg.size_bytes;
 
         if (!currentBlock) {
-            currentBlock = { start: sTime, end: eTime, size: sizeBytes, segCount: 1, pools: new Set([seg.pool_idx]) };
+            currentBlock = { start: sTime, end: eTime, size: sizeBytes, segCount: 1, pools: new Set([seg.pool_idx]), ids: [seg.segment_id] };
             blocks.push(currentBlock);
         } else {
             const gap = sTime - currentBlock.end;
@@ -325,9 +329,10 @@ function displaySegmentsList(segments) {
                 currentBlock.size += sizeBytes;
                 currentBlock.segCount++;
                 currentBlock.pools.add(seg.pool_idx);
+                currentBlock.ids.push(seg.segment_id);
             } else {
                 // New block
-                currentBlock = { start: sTime, end: eTime, size: sizeBytes, segCount: 1, pools: new Set([seg.pool_idx]) };
+                currentBlock = { start: sTime, end: eTime, size: sizeBytes, segCount: 1, pools: new Set([seg.pool_idx]), ids: [seg.segment_id] };
                 blocks.push(currentBlock);
             }
         }
@@ -345,17 +350,29 @@ function displaySegmentsList(segments) {
         const dStrEnd = block.end.toLocaleTimeString(undefined, { timeStyle: 'medium' });
 
         // Format Pool List
-        const poolArr = Array.from(block.pools).sort((a, b) => a - b);
+        // Archived segments have no pool (pool_idx is null).
+        const poolArr = Array.from(block.pools).filter(p => p !== null).sort((a, b) => a - b);
         let poolText = poolArr.length > 3
             ? `Pools: ${poolArr[0]}..${poolArr[poolArr.length - 1]}`
             : `Pool(s): ${poolArr.join(', ')}`;
+        if (block.pools.has(null)) {
+            poolText = poolArr.length > 0 ? `${poolText} + archive` : 'Archive';
+        }
 
         const durationMin = Math.round((block.end - block.start) / 60000);
 
+        // Up to 4 thumbnails spread evenly over the block
+        const camId = encodeURIComponent(els.recCamSelect.value);
+        const thumbCount = Math.min(4, block.ids.length);
+        const thumbs = Array.from({ length: thumbCount }, (_, i) =>
+            block.ids[Math.floor(i * block.ids.length / thumbCount)]
+        ).map(id => `<img loading="lazy" src="/api/hls/${camId}/thumbnail/${id}" alt="">`).join('');
+
         const el = document.createElement('div');
         el.className = 'segment-item';
         el.innerHTML = `
             <div class="segment-time">${dStrStart} — ${dStrEnd}</div>
+            <div class="segment-thumbs">${thumbs}</div>
             <div class="segment-meta" style="margin-top:4px;">
                 <span>${durationMin} min (${block.segCount} segs)</span>
                 <span>${(block.size / (1024 * 1024)).toFixed(1)} MB</span>
@@ -402,7 +419,7 @@ els.btnPlayVod.addEventListener('click', () => {
     const fromFmt = dFrom.toISOString().slice(0, 19);
     const toFmt = dTo.toISOString().slice(0, 19);
 
-    const src = `/api/hls/${encodeURIComponent(camId)}/vod.m3u8?from=${encodeURIComponent(fromFmt)}&to=${encodeURIComponent(toFmt)}`;
+    const src = `/api/hls/${encodeURIComponent(camId)}/vod/index.m3u8?from=${encodeURIComponent(fromFmt)}&to=${encodeURIComponent(toFmt)}`;
 
     els.vodOverlay.classList.add('hidden');
 
@@ -9,14 +9,24 @@
 //!   GET    /api/status                                → system status (JSON)
 //!   GET    /api/list?camera=cam1                      → segment list (JSON)
 //!   GET    /api/export?camera=cam1&from=...&to=...    → download .mp4
-//!   GET    /api/hls/{camera}/live.m3u8                → LL-HLS live playlist
+//!   GET    /api/hls/{camera}/index.m3u8               → live multivariant playlist (streams, codecs)
+//!   GET    /api/hls/{camera}/live.m3u8[?stream=sub]   → LL-HLS live playlist
+//!   GET    /api/hls/{camera}/vod/index.m3u8?from=...&to=... → VOD multivariant playlist
 //!   GET    /api/hls/{camera}/vod.m3u8?from=...&to=... → VOD playlist
 //!   GET    /api/dash/{camera}/manifest.mpd            → DASH live manifest
 //!   GET    /api/dash/{camera}/manifest.mpd?from=...&to=... → DASH VOD manifest
 //!   GET    /api/cameras                               → list active cameras
 //!   POST   /api/cameras                               → add camera (hot)
 //!   DELETE /api/cameras/{id}                          → remove camera (hot)
-
+//!   GET    /api/cameras/{id}/coverage?from=...&to=...&resolution=hour
+//!                                                     → recorded spans, gaps, timeline
+//!   GET    /api/cameras/{id}/thumbnail?at=...         → JPEG of the footage at a time
+//!   GET    /api/cameras/{id}/snapshot.jpg[?at=...]    → full-size still image
+//!   GET    /api/holds                                 → list evidence holds
+//!   POST   /api/holds                                 → place a range on hold
+//!   DELETE /api/holds/{id}                            → release a hold
+
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use axum::extract::{Path, Query, State};
@@ -28,16 +38,21 @@ use chrono::NaiveDateTime;
 use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 use tower_http::cors::CorsLayer;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::config::{CameraConfig, Config};
+use crate::codec;
 use crate::dash;
 use crate::error::NvrError;
 use crate::hls;
 use crate::manager::RecordingManager;
 use crate::playback;
-use crate::storage::chunk_pool::{ChunkPool, PoolReadCounters};
-use crate::storage::index::SegmentIndex;
+use crate::storage::archive::{self, SharedArchive};
+use crate::storage::chunk_pool::{ChunkPool, PoolReadCounters, SlotHealth, Stream};
+use crate::storage::crypto::RecordCipher;
+use crate::storage::index::{Coverage, SegmentIndex, SegmentMeta, TimeSpan};
+use crate::storage::vault::{Hold, HoldRequest, Vault};
+use crate::thumbnail::{ThumbnailCache, THUMBNAIL_WIDTH};
 
 /// Shared state passed to all handlers.
 pub struct AppState {
@@ -46,6 +61,45 @@ pub struct AppState {
     pub config_path: std::path::PathBuf,
     pub read_counters: Arc<PoolReadCounters>,
     pub manager: Arc<Mutex<RecordingManager>>,
+    pub snapshots: SnapshotCache,
+    pub codecs: CodecCache,
+}
+
+/// The codec string last read for each camera stream and the segment it was
+/// read from, so polled manifests don't read a segment every time.
+#[derive(Default)]
+pub struct CodecCache(Mutex<HashMap<(String, Stream), CachedCodecs>>);
+
+struct CachedCodecs {
+    segment_id: u64,
+    codecs: Option<String>,
+}
+
+/// Each camera's latest live snapshot, so integrations polling it don't
+/// decode the same segment again on every request.
+#[derive(Default)]
+pub struct SnapshotCache(Mutex<HashMap<String, CachedSnapshot>>);
+
+struct CachedSnapshot {
+    segment_id: u64,
+    width: Option<u32>,
+    jpeg: Vec<u8>,
+}
+
+impl SnapshotCache {
+    fn get(&self, camera_id: &str, segment_id: u64, width: Option<u32>) -> Option<Vec<u8>> {
+        self.0
+            .lock()
+            .get(camera_id)
+            .filter(|s| s.segment_id == segment_id && s.width == width)
+            .map(|s| s.jpeg.clone())
+    }
+
+    fn put(&self, camera_id: &str, segment_id: u64, width: Option<u32>, jpeg: Vec<u8>) {
+        self.0
+            .lock()
+            .insert(camera_id.to_string(), CachedSnapshot { segment_id, width, jpeg });
+    }
 }
 
 // ──────────────── request / response types ────────────────────────────────
@@ -62,6 +116,14 @@ pub struct ExportParams {
     to: String,
 }
 
+/// `_HLS_msn` is read from the raw query.
+#[derive(Deserialize)]
+pub struct LiveParams {
+    /// Defaults to the main stream.
+    #[serde(default)]
+    stream: Stream,
+}
+
 #[derive(Deserialize)]
 pub struct VodParams {
     from: String,
@@ -76,6 +138,46 @@ pub struct DashParams {
     to: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct CoverageParams {
+    /// Defaults to 24 hours before `to`.
+    #[serde(default)]
+    from: Option<String>,
+    /// Defaults to now.
+    #[serde(default)]
+    to: Option<String>,
+    /// Summary bucket size: "hour" (default) or "day".
+    #[serde(default)]
+    resolution: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SnapshotParams {
+    /// A time to take the frame from; omitted = the newest recording.
+    #[serde(default)]
+    at: Option<String>,
+    /// Scale to this width; omitted = recorded size.
+    #[serde(default)]
+    width: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct ThumbnailParams {
+    at: String,
+}
+
+#[derive(Deserialize)]
+pub struct HoldParams {
+    camera: String,
+    from: String,
+    to: String,
+    /// Release automatically after this time; omitted = kept until released.
+    #[serde(default)]
+    expires_at: Option<String>,
+    #[serde(default)]
+    reason: String,
+}
+
 #[derive(Deserialize)]
 pub struct LoginParams {
     username: String,
@@ -90,6 +192,56 @@ struct StatusResponse {
     active_pool_pct: f64,
     total_segments: usize,
     cameras: Vec<CameraStatus>,
+    writes: WriteStatus,
+    /// "healthy", "degraded" (offline or failing slots) or "failed" (no
+    /// writable slot left).
+    storage_health: &'static str,
+    /// Pool slots that are offline or not healthy.
+    unhealthy_pools: Vec<PoolHealthStatus>,
+    /// Only present with a replica configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replica: Option<ReplicaStatus>,
+    /// Only present with an archive configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive: Option<ArchiveStatus>,
+}
+
+#[derive(Serialize)]
+struct ArchiveStatus {
+    segments: usize,
+    size_bytes: u64,
+}
+
+/// Replica ring state and counters since the recorder started.
+#[derive(Serialize)]
+struct ReplicaStatus {
+    segments: usize,
+    mirrored: u64,
+    /// Not mirrored because the replica fell too far behind.
+    skipped: u64,
+    failed: u64,
+    storage_health: &'static str,
+}
+
+#[derive(Serialize)]
+struct PoolHealthStatus {
+    pool_idx: usize,
+    /// "offline", "degraded" or "failed".
+    state: &'static str,
+    write_errors: u64,
+}
+
+/// Pool write counters since the recorder started.
+#[derive(Serialize)]
+struct WriteStatus {
+    records: u64,
+    bytes: u64,
+    writes: u64,
+    /// Above 1 when queued segments were coalesced into one write.
+    records_per_write: f64,
+    direct_writes: u64,
+    syncs: u64,
+    avg_sync_ms: f64,
 }
 
 #[derive(Serialize)]
@@ -97,6 +249,12 @@ struct CameraStatus {
     id: String,
     name: String,
     segments: usize,
+    /// Segments held in memory while the writer queue is full.
+    spilled_segments: usize,
+    spilled_bytes: u64,
+    /// Segments dropped under the overload policy since startup.
+    dropped_segments: u64,
+    dropped_bytes: u64,
 }
 
 #[derive(Serialize)]
@@ -105,7 +263,9 @@ struct SegmentInfo {
     camera_id: String,
     start: String,
     end: String,
-    pool_idx: usize,
+    /// `None` for archived segments.
+    pool_idx: Option<usize>,
+    archived: bool,
     size_bytes: u64,
 }
 
@@ -116,6 +276,75 @@ struct ListResponse {
     total: usize,
 }
 
+#[derive(Serialize)]
+struct SpanInfo {
+    start: String,
+    end: String,
+    duration_secs: f64,
+}
+
+impl From<&TimeSpan> for SpanInfo {
+    fn from(s: &TimeSpan) -> Self {
+        SpanInfo {
+            start: s.start.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            end: s.end.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            duration_secs: s.duration().num_milliseconds() as f64 / 1000.0,
+        }
+    }
+}
+
+/// Recorded share of one hour or day, for the dashboard timeline.
+#[derive(Serialize)]
+struct CoverageBucketInfo {
+    start: String,
+    recorded_secs: f64,
+    /// 0.0 (nothing recorded) to 1.0 (fully recorded).
+    coverage: f64,
+}
+
+#[derive(Serialize)]
+struct CoverageResponse {
+    camera: String,
+    from: String,
+    to: String,
+    resolution: &'static str,
+    recorded_secs: f64,
+    coverage: f64,
+    spans: Vec<SpanInfo>,
+    gaps: Vec<SpanInfo>,
+    buckets: Vec<CoverageBucketInfo>,
+}
+
+#[derive(Serialize)]
+struct HoldInfo {
+    id: u64,
+    camera_id: String,
+    from: String,
+    to: String,
+    created_at: String,
+    expires_at: Option<String>,
+    reason: String,
+    segments: usize,
+    size_bytes: u64,
+}
+
+impl From<&Hold> for HoldInfo {
+    fn from(h: &Hold) -> Self {
+        let fmt = |t: chrono::DateTime<chrono::Utc>| t.format("%Y-%m-%dT%H:%M:%S").to_string();
+        HoldInfo {
+            id: h.id,
+            camera_id: h.camera_id.clone(),
+            from: fmt(h.from),
+            to: fmt(h.to),
+            created_at: fmt(h.created_at),
+            expires_at: h.expires_at.map(fmt),
+            reason: h.reason.clone(),
+            segments: h.segments.len(),
+            size_bytes: h.total_bytes(),
+        }
+    }
+}
+
 // ──────────────── router ──────────────────────────────────────────────────
 
 use tower_http::services::ServeDir;
@@ -127,16 +356,26 @@ pub fn build_router(state: Arc<AppState>) -> Router {
         .route("/api/list", get(handle_list))
         .route("/api/export", get(handle_export))
         // HLS endpoints
+        .route("/api/hls/{camera_id}/index.m3u8", get(handle_hls_master))
+        .route("/api/hls/{camera_id}/vod/index.m3u8", get(handle_hls_vod_master))
         .route("/api/hls/{camera_id}/live.m3u8", get(handle_hls_live))
         .route("/api/hls/{camera_id}/vod.m3u8", get(handle_hls_vod))
         .route("/api/hls/{camera_id}/segment/mp4/{segment_id}", get(handle_hls_segment))
         .route("/api/hls/{camera_id}/player", get(handle_hls_player))
         .route("/api/hls/{camera_id}/vod/player", get(handle_vod_player))
+        .route("/api/hls/{camera_id}/thumbnails.vtt", get(handle_hls_thumbnail_track))
+        .route("/api/hls/{camera_id}/thumbnail/{segment_id}", get(handle_hls_thumbnail))
         // DASH endpoint (same segments as HLS, different manifest)
         .route("/api/dash/{camera_id}/manifest.mpd", get(handle_dash_manifest))
         // Camera management
         .route("/api/cameras", get(handle_list_cameras).post(handle_add_camera))
         .route("/api/cameras/{camera_id}", delete(handle_remove_camera))
+        .route("/api/cameras/{camera_id}/coverage", get(handle_coverage))
+        .route("/api/cameras/{camera_id}/thumbnail", get(handle_camera_thumbnail))
+        .route("/api/cameras/{camera_id}/snapshot.jpg", get(handle_snapshot))
+        // Evidence holds
+        .route("/api/holds", get(handle_list_holds).post(handle_create_hold))
+        .route("/api/holds/{hold_id}", delete(handle_release_hold))
         // Authentication
         .route("/api/login", axum::routing::post(handle_login))
         // Serve static frontend files
@@ -147,6 +386,7 @@ pub fn build_router(state: Arc<AppState>) -> Router {
 
 /// Start the HTTP server.
 pub async fn start_server(state: Arc<AppState>, port: u16) {
+    tokio::spawn(prune_thumbnails_periodically(state.clone()));
     let app = build_router(state);
     let addr = format!("0.0.0.0:{}", port);
     info!(port, "HTTP API listening on http://{}", addr);
@@ -187,14 +427,27 @@ async fn handle_login(
 async fn handle_status(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
-    let pool_guard = {
+    let (pool_guard, overload, replica, archive) = {
         let mgr = state.manager.lock();
-        mgr.pool.clone()
+        (mgr.pool.clone(), mgr.overload.clone(), mgr.replica.clone(), mgr.archive.clone())
     };
-    
-    let (idx, used, cap) = {
+
+    let (idx, used, cap, stats, health, unhealthy_pools) = {
         let p = pool_guard.read();
-        p.status()
+        let (idx, used, cap) = p.status();
+        let unhealthy: Vec<PoolHealthStatus> = (0..p.pool_count())
+            .filter_map(|i| {
+                let state = if !p.is_online(i) {
+                    "offline"
+                } else if p.slot_health(i) != SlotHealth::Healthy {
+                    p.slot_health(i).as_str()
+                } else {
+                    return None;
+                };
+                Some(PoolHealthStatus { pool_idx: i, state, write_errors: p.write_errors(i) })
+            })
+            .collect();
+        (idx, used, cap, p.write_stats(), p.health(), unhealthy)
     };
     let index = state.index.read();
 
@@ -202,10 +455,17 @@ async fn handle_status(
         let cfg = state.config.read().unwrap();
         cfg.cameras
             .iter()
-            .map(|c| CameraStatus {
-                id: c.id.clone(),
-                name: c.name.clone(),
-                segments: index.segments_for_camera(&c.id).len(),
+            .map(|c| {
+                let load = overload.camera(&c.id).unwrap_or_default();
+                CameraStatus {
+                    id: c.id.clone(),
+                    name: c.name.clone(),
+                    segments: index.segments_for_camera(&c.id).len(),
+                    spilled_segments: load.spilled_segments,
+                    spilled_bytes: load.spilled_bytes,
+                    dropped_segments: load.dropped_segments,
+                    dropped_bytes: load.dropped_bytes,
+                }
             })
             .collect()
     };
@@ -222,6 +482,39 @@ async fn handle_status(
         },
         total_segments: index.len(),
         cameras,
+        writes: WriteStatus {
+            records: stats.records,
+            bytes: stats.bytes,
+            writes: stats.writes,
+            records_per_write: if stats.writes > 0 {
+                stats.records as f64 / stats.writes as f64
+            } else {
+                0.0
+            },
+            direct_writes: stats.direct_writes,
+            syncs: stats.syncs,
+            avg_sync_ms: if stats.syncs > 0 {
+                stats.sync_time.as_secs_f64() * 1000.0 / stats.syncs as f64
+            } else {
+                0.0
+            },
+        },
+        storage_health: health.as_str(),
+        unhealthy_pools,
+        replica: replica.map(|r| {
+            let stats = r.stats();
+            ReplicaStatus {
+                segments: stats.segments,
+                mirrored: stats.mirrored,
+                skipped: stats.skipped,
+                failed: stats.failed,
+                storage_health: stats.health.as_str(),
+            }
+        }),
+        archive: archive.map(|a| {
+            let index = a.index();
+            ArchiveStatus { segments: index.len(), size_bytes: index.total_bytes() }
+        }),
     };
 
     (StatusCode::OK, axum::Json(serde_json::to_value(resp).unwrap()))
@@ -231,18 +524,24 @@ async fn handle_list(
     State(state): State<Arc<AppState>>,
     Query(params): Query<ListParams>,
 ) -> impl IntoResponse {
+    let archive = archive_of(&state);
+    let archive_index = archive.as_ref().map(|a| a.index());
     let index = state.index.read();
-    let segments = index.segments_for_camera(&params.camera);
+    let segments = archive::segments_for_camera(&index, archive_index.as_deref(), &params.camera);
 
     let seg_infos: Vec<SegmentInfo> = segments
         .iter()
         .map(|s| SegmentInfo {
-            segment_id: s.segment_id,
-            camera_id: s.camera_id.clone(),
-            start: s.start_ts.format("%Y-%m-%dT%H:%M:%S").to_string(),
-            end: s.end_ts.format("%Y-%m-%dT%H:%M:%S").to_string(),
-            pool_idx: s.location.pool_idx,
-            size_bytes: s.location.record_size - 40,
+            segment_id: s.segment_id(),
+            camera_id: s.camera_id().to_string(),
+            start: s.start_ts().format("%Y-%m-%dT%H:%M:%S").to_string(),
+            end: s.end_ts().format("%Y-%m-%dT%H:%M:%S").to_string(),
+            pool_idx: match s {
+                archive::TieredSegment::Hot(m) => Some(m.location.pool_idx),
+                archive::TieredSegment::Cold(_) => None,
+            },
+            archived: s.is_archived(),
+            size_bytes: s.data_len(),
         })
         .collect();
 
@@ -283,40 +582,13 @@ async fn handle_export(
     let from_utc = from_naive.and_utc();
     let to_utc = to_naive.and_utc();
 
-    // Open pool for reading.
-    let pool_bytes = {
-        let cfg = state.config.read().unwrap();
-        cfg.storage.chunk_size_mb * 1024 * 1024
-    };
-    let base_path = state.config.read().unwrap().storage.base_path.clone();
-    let max_pools = state.config.read().unwrap().storage.max_pools;
-
-    let pool = match ChunkPool::open(
-        &base_path,
-        pool_bytes,
-        max_pools,
-    ) {
-        Ok(p) => p,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                axum::Json(serde_json::json!({"error": e.to_string()})),
-            ).into_response();
-        }
-    };
+    let pool = read_pool_view(&state);
 
     // Acquire read guards on every pool touched by this range up front, so
     // the writer can't rotate any of them out from under the remux below.
-    let _guards: Vec<_> = {
+    let _guards = {
         let index = state.index.read();
-        index
-            .segments_in_range(&params.camera, from_utc, to_utc)
-            .iter()
-            .map(|s| s.location.pool_idx)
-            .collect::<std::collections::HashSet<_>>()
-            .into_iter()
-            .map(|idx| state.read_counters.acquire(idx))
-            .collect()
+        acquire_pools(&state, &index.segments_in_range(&params.camera, from_utc, to_utc))
     };
 
     // Segments are independent, self-initializing fMP4 files, so exporting a
@@ -329,13 +601,29 @@ async fn handle_export(
     ));
 
     let export_result = {
+        let archive = archive_of(&state);
         let index = state.index.read();
-        playback::export_range(&pool, &index, &params.camera, from_utc, to_utc, &tmp_output)
+        playback::export_range(
+            &pool,
+            &index,
+            archive.as_deref(),
+            &params.camera,
+            from_utc,
+            to_utc,
+            &tmp_output,
+        )
     };
     drop(_guards);
 
     let segment_count = match export_result {
         Ok(count) => count,
+        Err(e @ NvrError::SegmentEvicted { .. }) => {
+            let _ = std::fs::remove_file(&tmp_output);
+            return (
+                StatusCode::GONE,
+                axum::Json(serde_json::json!({"error": e.to_string()})),
+            ).into_response();
+        }
         Err(NvrError::Storage(msg)) => {
             let _ = std::fs::remove_file(&tmp_output);
             return (
@@ -389,14 +677,44 @@ async fn handle_export(
     ).into_response()
 }
 
+/// A read-only view of the recorder's pools, so long reads don't hold its
+/// pool lock. Copied from the live pool: nothing is opened or scanned.
+fn read_pool_view(state: &AppState) -> ChunkPool {
+    let pool = state.manager.lock().pool.clone();
+    let view = pool.read().read_view();
+    view
+}
+
+/// The cold archive, if one is configured.
+fn archive_of(state: &AppState) -> Option<SharedArchive> {
+    state.manager.lock().archive.clone()
+}
+
+/// Read guards on every pool holding one of `segments`.
+fn acquire_pools(
+    state: &AppState,
+    segments: &[&SegmentMeta],
+) -> Vec<crate::storage::chunk_pool::PoolReadGuard> {
+    segments
+        .iter()
+        .map(|s| s.location.pool_idx)
+        .collect::<std::collections::HashSet<_>>()
+        .into_iter()
+        .map(|idx| state.read_counters.acquire(idx))
+        .collect()
+}
+
 // ──────────────── HLS handlers ────────────────────────────────────────────
 
-/// LL-HLS live playlist. Supports `?_HLS_msn=N` for blocking reload.
+/// LL-HLS live playlist of the main stream, or of the substream with
+/// `?stream=sub`. Supports `?_HLS_msn=N` for blocking reload.
 async fn handle_hls_live(
     State(state): State<Arc<AppState>>,
     axum::extract::Path(camera_id): axum::extract::Path<String>,
+    Query(params): Query<LiveParams>,
     raw_query: axum::extract::RawQuery,
 ) -> axum::response::Response {
+    let stream = params.stream;
     let seg_dur = state.config.read().unwrap().storage.segment_duration_secs;
 
     // Parse _HLS_msn from raw query string.
@@ -415,21 +733,21 @@ async fn handle_hls_live(
             // Scope the lock guard so it's dropped before .await
             let result = {
                 let idx = state.index.read();
-                hls::generate_live_playlist(&idx, &camera_id, seg_dur, Some(msn))
+                hls::generate_live_playlist(&idx, &camera_id, stream, seg_dur, Some(msn))
             };
             if let Some(pl) = result {
                 break pl;
             }
             if tokio::time::Instant::now() >= deadline {
                 let idx = state.index.read();
-                break hls::generate_live_playlist(&idx, &camera_id, seg_dur, None)
+                break hls::generate_live_playlist(&idx, &camera_id, stream, seg_dur, None)
                     .unwrap_or_default();
             }
             tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
         }
     } else {
         let idx = state.index.read();
-        hls::generate_live_playlist(&idx, &camera_id, seg_dur, None).unwrap_or_default()
+        hls::generate_live_playlist(&idx, &camera_id, stream, seg_dur, None).unwrap_or_default()
     };
 
     (
@@ -467,9 +785,12 @@ async fn handle_hls_vod(
     };
 
     let seg_dur = state.config.read().unwrap().storage.segment_duration_secs;
+    let archive = archive_of(&state);
+    let archive_index = archive.as_ref().map(|a| a.index());
     let idx = state.index.read();
     match hls::generate_vod_playlist(
         &idx,
+        archive_index.as_deref(),
         &camera_id,
         from_naive.and_utc(),
         to_naive.and_utc(),
@@ -488,6 +809,125 @@ async fn handle_hls_vod(
     }
 }
 
+/// Multivariant playlist for live viewing: `live.m3u8` and, if the camera
+/// has recorded a substream, `live.m3u8?stream=sub`, each with its codec.
+async fn handle_hls_master(
+    State(state): State<Arc<AppState>>,
+    Path(camera_id): Path<String>,
+) -> impl IntoResponse {
+    let mut variants = Vec::new();
+    for (stream, uri) in [(Stream::Main, "live.m3u8"), (Stream::Sub, "live.m3u8?stream=sub")] {
+        let newest = state
+            .index
+            .read()
+            .latest_for_stream(&camera_id, stream, 1)
+            .first()
+            .map(|s| (s.segment_id, bandwidth(s.location.record_size, s.start_ts, s.end_ts)));
+        // Without recordings yet, the main stream is listed anyway.
+        if newest.is_none() && stream == Stream::Sub {
+            continue;
+        }
+        variants.push(hls::Variant {
+            uri: uri.to_string(),
+            codecs: newest.and_then(|(id, _)| segment_codecs(&state, &camera_id, stream, id)),
+            bandwidth: newest.map_or(DEFAULT_BANDWIDTH, |(_, b)| b),
+        });
+    }
+    (
+        StatusCode::OK,
+        [("content-type", "application/vnd.apple.mpegurl")],
+        hls::generate_master_playlist(&variants),
+    )
+}
+
+/// Multivariant playlist for a VOD range: `vod.m3u8` with the same query,
+/// with its codec.
+async fn handle_hls_vod_master(
+    State(state): State<Arc<AppState>>,
+    Path(camera_id): Path<String>,
+    Query(params): Query<VodParams>,
+    raw_query: axum::extract::RawQuery,
+) -> impl IntoResponse {
+    let parse = |value: &str| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").map(|dt| dt.and_utc());
+    let (from, to) = match (parse(&params.from), parse(&params.to)) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(e), _) | (_, Err(e)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                [("content-type", "text/plain")],
+                format!("Invalid 'from' or 'to': {e}"),
+            ).into_response();
+        }
+    };
+
+    let first = {
+        let archive = archive_of(&state);
+        let archive_index = archive.as_ref().map(|a| a.index());
+        let index = state.index.read();
+        archive::segments_in_range(&index, archive_index.as_deref(), &camera_id, from, to)
+            .first()
+            .map(|s| (s.segment_id(), bandwidth(s.data_len(), s.start_ts(), s.end_ts())))
+    };
+    let Some((segment_id, bandwidth)) = first else {
+        return (
+            StatusCode::NOT_FOUND,
+            [("content-type", "text/plain")],
+            format!("No segments found for camera '{}' in range", camera_id),
+        ).into_response();
+    };
+    let variant = hls::Variant {
+        uri: format!("../vod.m3u8?{}", raw_query.0.unwrap_or_default()),
+        codecs: segment_codecs(&state, &camera_id, Stream::Main, segment_id),
+        bandwidth,
+    };
+    (
+        StatusCode::OK,
+        [("content-type", "application/vnd.apple.mpegurl")],
+        hls::generate_master_playlist(&[variant]),
+    ).into_response()
+}
+
+/// Advertised bit rate when there's no segment to measure.
+const DEFAULT_BANDWIDTH: u64 = 2_000_000;
+
+/// Bit rate of a segment of `bytes` recorded from `start` to `end`.
+fn bandwidth(bytes: u64, start: chrono::DateTime<chrono::Utc>, end: chrono::DateTime<chrono::Utc>) -> u64 {
+    let ms = (end - start).num_milliseconds();
+    if ms <= 0 {
+        return DEFAULT_BANDWIDTH;
+    }
+    bytes * 8 * 1000 / ms as u64
+}
+
+/// Codec string (RFC 6381) of a camera stream's footage, read from segment
+/// `segment_id`. `None` if it can't be read or isn't recognized.
+fn segment_codecs(state: &AppState, camera_id: &str, stream: Stream, segment_id: u64) -> Option<String> {
+    let key = (camera_id.to_string(), stream);
+    if let Some(cached) = state.codecs.0.lock().get(&key) {
+        if cached.segment_id == segment_id {
+            return cached.codecs.clone();
+        }
+    }
+    let data = match read_segment_by_id(state, camera_id, segment_id) {
+        Ok(Some(data)) => data,
+        Ok(None) => return None,
+        Err(e) => {
+            warn!(camera = camera_id, segment_id, error = %e, "Cannot read segment for its codec");
+            return None;
+        }
+    };
+    let codecs = codec::codec_string(&data);
+    if codecs.is_none() {
+        warn!(camera = camera_id, segment_id, "Unrecognized codec in recorded segment");
+    }
+    state
+        .codecs
+        .0
+        .lock()
+        .insert(key, CachedCodecs { segment_id, codecs: codecs.clone() });
+    codecs
+}
+
 /// Inline HLS.js web player — works in all browsers.
 async fn handle_hls_player(
     Path(camera_id): Path<String>,
@@ -515,7 +955,7 @@ async fn handle_hls_player(
 <video id="v" controls autoplay muted playsinline></video>
 <div id="status">Connecting…</div>
 <script>
-const src = "live.m3u8";
+const src = "index.m3u8";
 const video = document.getElementById("v");
 const status = document.getElementById("status");
 
@@ -583,17 +1023,39 @@ async fn handle_vod_player(
   video {{ width:90vw; max-width:1280px; border-radius:8px;
            background:#000; }}
   #status {{ font-size:.85rem; margin-top:8px; opacity:.5; }}
+  #thumbs {{ display:flex; gap:4px; overflow-x:auto; width:90vw;
+            max-width:1280px; margin-top:12px; }}
+  #thumbs img {{ height:68px; border-radius:4px; cursor:pointer;
+                opacity:.7; background:#222; }}
+  #thumbs img:hover {{ opacity:1; }}
 </style>
 </head>
 <body>
 <h1>🎬 {camera_id} — VOD</h1>
 <video id="v" controls autoplay muted playsinline></video>
 <div id="status">Loading…</div>
+<div id="thumbs"></div>
 <script>
-const src = "../vod.m3u8?{qs}";
+const src = "index.m3u8?{qs}";
 const video = document.getElementById("v");
 const status = document.getElementById("status");
 
+// Thumbnail track: one cue per segment; click to jump there.
+const track = new URL("../thumbnails.vtt?{qs}", location.href);
+fetch(track).then(r => r.ok ? r.text() : "").then(vtt => {{
+  const secs = t => t.split(":").reduce((acc, part) => acc * 60 + parseFloat(part), 0);
+  for (const cue of vtt.split("\n\n").slice(1)) {{
+    const [timing, url] = cue.trim().split("\n");
+    if (!timing || !url) continue;
+    const img = document.createElement("img");
+    img.loading = "lazy";
+    img.src = new URL(url, track).href;
+    img.title = timing.split(" --> ")[0];
+    img.onclick = () => {{ video.currentTime = secs(img.title); }};
+    document.getElementById("thumbs").appendChild(img);
+  }}
+}});
+
 if (Hls.isSupported()) {{
   const hls = new Hls({{ 
     enableWorker: true,
@@ -634,48 +1096,84 @@ async fn handle_hls_segment(
     State(state): State<Arc<AppState>>,
     Path((camera_id, segment_id)): Path<(String, u64)>,
 ) -> impl IntoResponse {
+    match read_segment_by_id(&state, &camera_id, segment_id) {
+        Ok(Some(data)) => (
+            StatusCode::OK,
+            [("content-type", "video/mp4")],
+            data,
+        ).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            [("content-type", "text/plain")],
+            Vec::from("Segment not found".as_bytes()),
+        ).into_response(),
+        // The pool was rotated out from under the index entry.
+        Err(e @ NvrError::SegmentEvicted { .. }) => (
+            StatusCode::GONE,
+            [("content-type", "text/plain")],
+            Vec::from(e.to_string().as_bytes()),
+        ).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [("content-type", "text/plain")],
+            Vec::from(format!("Read error: {e}").as_bytes()),
+        ).into_response(),
+    }
+}
+
+/// A segment's data by ID: from the ring (or the replica, if the ring's
+/// copy is unreadable), else from a hold in the vault, else from the cold
+/// archive. `Ok(None)` if none of them has it.
+fn read_segment_by_id(
+    state: &AppState,
+    camera_id: &str,
+    segment_id: u64,
+) -> crate::error::Result<Option<Vec<u8>>> {
     // Find the segment in the index.
     let seg = {
         let idx = state.index.read();
-        idx.segments_for_camera(&camera_id)
-            .into_iter()
-            .find(|s| s.segment_id == segment_id)
-            .cloned()
+        idx.segment(camera_id, segment_id).cloned()
     };
 
-    let seg = match seg {
-        Some(s) => s,
-        None => {
-            return (
-                StatusCode::NOT_FOUND,
-                [("content-type", "text/plain")],
-                Vec::from("Segment not found".as_bytes()),
-            ).into_response();
+    let Some(seg) = seg else {
+        // Overwritten in the ring, but it may have been put on hold.
+        if let Ok(Some(data)) = vault(state).and_then(|v| v.read_segment(camera_id, segment_id)) {
+            return Ok(Some(data));
+        }
+        // Or copied into the cold archive.
+        if let Some(archive) = archive_of(state) {
+            match archive.read_by_id(camera_id, segment_id) {
+                Ok(Some(data)) => return Ok(Some(data)),
+                Ok(None) => {}
+                Err(e) => warn!(camera = camera_id, segment_id, error = %e, "Archive read failed"),
+            }
         }
+        return Ok(None);
     };
 
     // Read segment data from pool.
-    let pool_guard = {
+    let (pool_guard, replica) = {
         let mgr = state.manager.lock();
-        mgr.pool.clone()
+        (mgr.pool.clone(), mgr.replica.clone())
     };
 
-    let p = pool_guard.read();
-
-    // Acquire read guard to prevent pool rotation during read.
-    let _guard = state.read_counters.acquire(seg.location.pool_idx);
+    let result = {
+        let p = pool_guard.read();
+        // Acquire read guard to prevent pool rotation during read.
+        let _guard = state.read_counters.acquire(seg.location.pool_idx);
+        p.read_segment_data(&seg.camera_id, &seg.location)
+    };
 
-    match p.read_segment_data(&seg.location) {
-        Ok(data) => (
-            StatusCode::OK,
-            [("content-type", "video/mp4")],
-            data,
-        ).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            [("content-type", "text/plain")],
-            Vec::from(format!("Read error: {e}").as_bytes()),
-        ).into_response(),
+    // The primary copy is unreadable: serve the replica's, if it has one.
+    match (result, replica) {
+        (Err(e), Some(replica)) => match replica.read_segment(&seg) {
+            Ok(Some(data)) => {
+                warn!(camera = seg.camera_id, segment_id, error = %e, "Primary read failed, served from replica");
+                Ok(Some(data))
+            }
+            _ => Err(e),
+        },
+        (result, _) => result.map(Some),
     }
 }
 
@@ -711,12 +1209,19 @@ async fn handle_dash_manifest(
                     ).into_response();
                 }
             };
+            let (from, to) = (from_naive.and_utc(), to_naive.and_utc());
+            let first = state.index.read().segments_in_range(&camera_id, from, to).first().map(|s| s.segment_id);
+            let codecs = first.and_then(|id| segment_codecs(&state, &camera_id, Stream::Main, id));
             let idx = state.index.read();
-            dash::generate_vod_mpd(&idx, &camera_id, from_naive.and_utc(), to_naive.and_utc(), seg_dur)
+            dash::generate_vod_mpd(&idx, &camera_id, from, to, seg_dur, codecs.as_deref())
         }
         _ => {
+            let [codecs, sub_codecs] = [Stream::Main, Stream::Sub].map(|stream| {
+                let newest = state.index.read().latest_for_stream(&camera_id, stream, 1).first().map(|s| s.segment_id);
+                newest.and_then(|id| segment_codecs(&state, &camera_id, stream, id))
+            });
             let idx = state.index.read();
-            dash::generate_live_mpd(&idx, &camera_id, seg_dur)
+            dash::generate_live_mpd(&idx, &camera_id, seg_dur, codecs.as_deref(), sub_codecs.as_deref())
         }
     };
 
@@ -755,6 +1260,7 @@ async fn handle_list_cameras(
                 "id": c.id,
                 "name": c.name,
                 "url": c.url,
+                "substream_url": c.substream_url,
                 "status": "active"
             })
         })
@@ -835,3 +1341,501 @@ async fn handle_remove_camera(
         })))
     }
 }
+
+/// Recording coverage of a camera: continuous spans, gaps, and the
+/// recorded share of each hour or day in the range (ring and archive).
+async fn handle_coverage(
+    State(state): State<Arc<AppState>>,
+    Path(camera_id): Path<String>,
+    Query(params): Query<CoverageParams>,
+) -> impl IntoResponse {
+    let bad_request = |msg: String| {
+        (StatusCode::BAD_REQUEST, axum::Json(serde_json::json!({"error": msg}))).into_response()
+    };
+    let parse = |name: &str, value: &str| {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+            .map(|dt| dt.and_utc())
+            .map_err(|e| format!("Invalid '{name}': {e}. Use format: 2026-02-19T14:00:00"))
+    };
+
+    let to = match params.to.as_deref().map(|v| parse("to", v)).transpose() {
+        Ok(to) => to.unwrap_or_else(chrono::Utc::now),
+        Err(e) => return bad_request(e),
+    };
+    let from = match params.from.as_deref().map(|v| parse("from", v)).transpose() {
+        Ok(from) => from.unwrap_or(to - chrono::Duration::hours(24)),
+        Err(e) => return bad_request(e),
+    };
+    if from >= to {
+        return bad_request("'from' must be before 'to'".into());
+    }
+    let (resolution, bucket) = match params.resolution.as_deref().unwrap_or("hour") {
+        "hour" => ("hour", chrono::Duration::hours(1)),
+        "day" => ("day", chrono::Duration::days(1)),
+        other => return bad_request(format!("Invalid 'resolution': {other}. Use 'hour' or 'day'")),
+    };
+    // Keeps the summary a timeline, not a dump (over a year of hours).
+    if (to - from).num_seconds() / bucket.num_seconds() > 10_000 {
+        return bad_request(format!("Range too long for '{resolution}' resolution"));
+    }
+
+    let coverage = {
+        let archive = archive_of(&state);
+        let archive_index = archive.as_ref().map(|a| a.index());
+        let index = state.index.read();
+        let segments =
+            archive::segments_in_range(&index, archive_index.as_deref(), &camera_id, from, to);
+        Coverage::from_segments(segments.iter().map(|s| (s.start_ts(), s.end_ts())), from, to)
+    };
+
+    let secs = |d: chrono::Duration| d.num_milliseconds() as f64 / 1000.0;
+    let recorded = coverage.recorded();
+    let resp = CoverageResponse {
+        camera: camera_id,
+        from: from.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        to: to.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        resolution,
+        recorded_secs: secs(recorded),
+        coverage: secs(recorded) / secs(to - from),
+        spans: coverage.spans.iter().map(SpanInfo::from).collect(),
+        gaps: coverage.gaps.iter().map(SpanInfo::from).collect(),
+        buckets: coverage
+            .buckets(bucket)
+            .iter()
+            .map(|b| CoverageBucketInfo {
+                start: b.span.start.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                recorded_secs: secs(b.recorded),
+                coverage: b.ratio(),
+            })
+            .collect(),
+    };
+
+    (StatusCode::OK, axum::Json(serde_json::to_value(resp).unwrap())).into_response()
+}
+
+// ──────────────── thumbnail and snapshot handlers ─────────────────────────
+
+fn thumbnails(state: &AppState) -> crate::error::Result<ThumbnailCache> {
+    let cfg = state.config.read().unwrap();
+    Ok(ThumbnailCache::new(
+        &cfg.storage.base_path,
+        RecordCipher::from_config(&cfg.storage)?,
+    ))
+}
+
+/// Thumbnail of the footage recorded at `?at=`.
+async fn handle_camera_thumbnail(
+    State(state): State<Arc<AppState>>,
+    Path(camera_id): Path<String>,
+    Query(params): Query<ThumbnailParams>,
+) -> axum::response::Response {
+    let at = match NaiveDateTime::parse_from_str(&params.at, "%Y-%m-%dT%H:%M:%S") {
+        Ok(dt) => dt.and_utc(),
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                [("content-type", "text/plain")],
+                format!("Invalid 'at': {e}. Use format: 2026-02-19T14:00:00"),
+            ).into_response();
+        }
+    };
+
+    let Some((segment_id, _)) = segment_at(&state, &camera_id, at) else {
+        return (
+            StatusCode::NOT_FOUND,
+            [("content-type", "text/plain")],
+            format!("No recording of camera '{camera_id}' at {}", params.at),
+        ).into_response();
+    };
+
+    thumbnail_response(state, camera_id, segment_id).await
+}
+
+/// Thumbnail of a segment by ID, as referenced from `thumbnails.vtt`.
+async fn handle_hls_thumbnail(
+    State(state): State<Arc<AppState>>,
+    Path((camera_id, segment_id)): Path<(String, u64)>,
+) -> axum::response::Response {
+    thumbnail_response(state, camera_id, segment_id).await
+}
+
+/// WebVTT thumbnail track for the VOD playlist of the same range.
+async fn handle_hls_thumbnail_track(
+    State(state): State<Arc<AppState>>,
+    Path(camera_id): Path<String>,
+    Query(params): Query<VodParams>,
+) -> impl IntoResponse {
+    let parse = |name: &str, value: &str| {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+            .map(|dt| dt.and_utc())
+            .map_err(|e| format!("Invalid '{name}': {e}"))
+    };
+    let (from, to) = match (parse("from", &params.from), parse("to", &params.to)) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(e), _) | (_, Err(e)) => {
+            return (StatusCode::BAD_REQUEST, [("content-type", "text/plain")], e).into_response();
+        }
+    };
+
+    let seg_dur = state.config.read().unwrap().storage.segment_duration_secs;
+    let archive = archive_of(&state);
+    let archive_index = archive.as_ref().map(|a| a.index());
+    let idx = state.index.read();
+    match hls::generate_thumbnail_track(&idx, archive_index.as_deref(), &camera_id, from, to, seg_dur) {
+        Some(vtt) => (StatusCode::OK, [("content-type", "text/vtt")], vtt).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            [("content-type", "text/plain")],
+            format!("No segments found for camera '{}' in range", camera_id),
+        ).into_response(),
+    }
+}
+
+/// Serve a segment's thumbnail, decoding it first if it isn't cached yet.
+/// Decoding blocks for a while, so it runs off the async workers.
+async fn thumbnail_response(
+    state: Arc<AppState>,
+    camera_id: String,
+    segment_id: u64,
+) -> axum::response::Response {
+    let result = tokio::task::spawn_blocking(move || {
+        thumbnails(&state)?.get_or_create(&camera_id, segment_id, || {
+            match read_segment_by_id(&state, &camera_id, segment_id)? {
+                Some(data) => playback::keyframe_jpeg(&data, Some(THUMBNAIL_WIDTH)).map(Some),
+                None => Ok(None),
+            }
+        })
+    })
+    .await
+    .unwrap_or_else(|e| Err(NvrError::Storage(format!("Thumbnail task failed: {e}"))));
+
+    // A segment's content never changes, so neither does its thumbnail.
+    image_response(result, "max-age=86400", segment_id)
+}
+
+/// Still image of a camera for integrations: the first keyframe of its
+/// newest segment (so at most one segment old), or of the footage at
+/// `?at=`. The `x-recorded-at` header says when the frame was recorded.
+async fn handle_snapshot(
+    State(state): State<Arc<AppState>>,
+    Path(camera_id): Path<String>,
+    Query(params): Query<SnapshotParams>,
+) -> axum::response::Response {
+    let bad_request = |msg: String| {
+        (StatusCode::BAD_REQUEST, [("content-type", "text/plain")], msg).into_response()
+    };
+    let at = match params
+        .at
+        .as_deref()
+        .map(|at| NaiveDateTime::parse_from_str(at, "%Y-%m-%dT%H:%M:%S"))
+        .transpose()
+    {
+        Ok(at) => at.map(|dt| dt.and_utc()),
+        Err(e) => return bad_request(format!("Invalid 'at': {e}. Use format: 2026-02-19T14:00:00")),
+    };
+    let width = params.width;
+    if width.is_some_and(|w| !(16..=7680).contains(&w)) {
+        return bad_request("'width' must be between 16 and 7680".into());
+    }
+
+    let segment = match at {
+        Some(at) => segment_at(&state, &camera_id, at),
+        None => latest_segment(&state, &camera_id),
+    };
+    let Some((segment_id, recorded_at)) = segment else {
+        return (
+            StatusCode::NOT_FOUND,
+            [("content-type", "text/plain")],
+            format!("No recording of camera '{camera_id}'"),
+        ).into_response();
+    };
+
+    let live = at.is_none();
+    let cached = if live { state.snapshots.get(&camera_id, segment_id, width) } else { None };
+    let result = match cached {
+        Some(jpeg) => Ok(Some(jpeg)),
+        None => {
+            let task_state = state.clone();
+            let task_camera = camera_id.clone();
+            tokio::task::spawn_blocking(move || {
+                match read_segment_by_id(&task_state, &task_camera, segment_id)? {
+                    Some(data) => playback::keyframe_jpeg(&data, width).map(Some),
+                    None => Ok(None),
+                }
+            })
+            .await
+            .unwrap_or_else(|e| Err(NvrError::Storage(format!("Snapshot task failed: {e}"))))
+        }
+    };
+    if let (true, Ok(Some(jpeg))) = (live, &result) {
+        state.snapshots.put(&camera_id, segment_id, width, jpeg.clone());
+    }
+
+    // A live snapshot changes with every new segment; one from history never.
+    let cache_control = if live { "no-cache" } else { "max-age=86400" };
+    let mut response = image_response(result, cache_control, segment_id);
+    if response.status() == StatusCode::OK {
+        let recorded_at = recorded_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        if let Ok(value) = axum::http::HeaderValue::from_str(&recorded_at) {
+            response.headers_mut().insert("x-recorded-at", value);
+        }
+    }
+    response
+}
+
+/// The camera's newest segment, from the ring or else the archive, with
+/// its start time.
+fn latest_segment(
+    state: &AppState,
+    camera_id: &str,
+) -> Option<(u64, chrono::DateTime<chrono::Utc>)> {
+    {
+        let index = state.index.read();
+        if let Some(seg) = index.latest_for_camera(camera_id, 1).first() {
+            return Some((seg.segment_id, seg.start_ts));
+        }
+    }
+    let archive = archive_of(state)?;
+    let archive_index = archive.index();
+    archive_index.latest(camera_id).map(|s| (s.segment_id, s.start_ts))
+}
+
+/// The segment recorded at `at` (or, in a gap, the next one within a
+/// segment's duration), with its start time.
+fn segment_at(
+    state: &AppState,
+    camera_id: &str,
+    at: chrono::DateTime<chrono::Utc>,
+) -> Option<(u64, chrono::DateTime<chrono::Utc>)> {
+    let seg_dur = state.config.read().unwrap().storage.segment_duration_secs;
+    let archive = archive_of(state);
+    let archive_index = archive.as_ref().map(|a| a.index());
+    let index = state.index.read();
+    let until = at + chrono::Duration::seconds(seg_dur.max(1) as i64);
+    archive::segments_in_range(&index, archive_index.as_deref(), camera_id, at, until)
+        .first()
+        .map(|s| (s.segment_id(), s.start_ts()))
+}
+
+/// Respond with a JPEG made from segment `segment_id`.
+fn image_response(
+    result: crate::error::Result<Option<Vec<u8>>>,
+    cache_control: &'static str,
+    segment_id: u64,
+) -> axum::response::Response {
+    match result {
+        Ok(Some(jpeg)) => (
+            StatusCode::OK,
+            [("content-type", "image/jpeg"), ("cache-control", cache_control)],
+            jpeg,
+        ).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            [("content-type", "text/plain")],
+            Vec::from("Segment not found".as_bytes()),
+        ).into_response(),
+        Err(e @ NvrError::SegmentEvicted { .. }) => (
+            StatusCode::GONE,
+            [("content-type", "text/plain")],
+            Vec::from(e.to_string().as_bytes()),
+        ).into_response(),
+        Err(e) => {
+            warn!(segment_id, error = %e, "Decoding segment to JPEG failed");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [("content-type", "text/plain")],
+                Vec::from(format!("Image error: {e}").as_bytes()),
+            ).into_response()
+        }
+    }
+}
+
+/// Prune the thumbnail cache on startup and then hourly.
+async fn prune_thumbnails_periodically(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+    loop {
+        interval.tick().await;
+        let state = state.clone();
+        match tokio::task::spawn_blocking(move || prune_thumbnails(&state)).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(removed)) => info!(removed, "Pruned thumbnails of overwritten segments"),
+            Ok(Err(e)) => warn!(error = %e, "Failed to prune thumbnails"),
+            Err(e) => warn!(error = %e, "Thumbnail prune task failed"),
+        }
+    }
+}
+
+/// Delete cached thumbnails of segments that are gone from the ring, the
+/// archive and the vault.
+fn prune_thumbnails(state: &AppState) -> crate::error::Result<usize> {
+    let mut live: std::collections::HashSet<(String, u64)> = state
+        .index
+        .read()
+        .all_segments()
+        .map(|m| (m.camera_id.clone(), m.segment_id))
+        .collect();
+    for hold in vault(state)?.list()? {
+        live.extend(hold.segments.iter().map(|s| (hold.camera_id.clone(), s.segment_id)));
+    }
+    let cache = thumbnails(state)?;
+    if let Some(archive) = archive_of(state) {
+        let cameras = cache.cameras()?;
+        let archive_index = archive.index();
+        for camera_id in cameras {
+            live.extend(
+                archive_index
+                    .segments_for_camera(&camera_id)
+                    .into_iter()
+                    .map(|s| (s.camera_id.clone(), s.segment_id)),
+            );
+        }
+    }
+    cache.prune(|camera_id, segment_id| live.contains(&(camera_id.to_string(), segment_id)))
+}
+
+// ──────────────── evidence hold handlers ─────────────────────────────────
+
+fn vault(state: &AppState) -> crate::error::Result<Vault> {
+    let cfg = state.config.read().unwrap();
+    Ok(Vault::new(&cfg.storage.base_path, RecordCipher::from_config(&cfg.storage)?))
+}
+
+/// List evidence holds. Expired holds are released first.
+async fn handle_list_holds(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let vault = match vault(&state) {
+        Ok(v) => v,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                axum::Json(serde_json::json!({ "error": e.to_string() })),
+            );
+        }
+    };
+    if let Err(e) = vault.purge_expired(chrono::Utc::now()) {
+        error!(error = %e, "Failed to release expired holds");
+    }
+    match vault.list() {
+        Ok(holds) => {
+            let holds: Vec<HoldInfo> = holds.iter().map(HoldInfo::from).collect();
+            (StatusCode::OK, axum::Json(serde_json::json!({
+                "total": holds.len(),
+                "holds": holds,
+            })))
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            axum::Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Copy a camera's footage in a time range into the vault.
+async fn handle_create_hold(
+    State(state): State<Arc<AppState>>,
+    axum::Json(params): axum::Json<HoldParams>,
+) -> impl IntoResponse {
+    let parse = |name: &str, value: &str| {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+            .map(|dt| dt.and_utc())
+            .map_err(|e| format!("Invalid '{name}': {e}. Use format: 2026-02-19T14:00:00"))
+    };
+    let parsed = parse("from", &params.from).and_then(|from| {
+        let to = parse("to", &params.to)?;
+        let expires_at = params
+            .expires_at
+            .as_deref()
+            .map(|v| parse("expires_at", v))
+            .transpose()?;
+        Ok((from, to, expires_at))
+    });
+    let (from, to, expires_at) = match parsed {
+        Ok(p) => p,
+        Err(msg) => {
+            return (StatusCode::BAD_REQUEST, axum::Json(serde_json::json!({ "error": msg })));
+        }
+    };
+    let req = HoldRequest {
+        camera_id: params.camera,
+        from,
+        to,
+        expires_at,
+        reason: params.reason,
+    };
+
+    let pool = read_pool_view(&state);
+
+    // Copying and syncing every segment takes a while: don't hold the index
+    // lock (the writer would stall behind it) or a pool's read guard (it
+    // would hold up rotation) for all of it. A segment overwritten while
+    // the copy runs fails `read_segment_data`'s identity check and is
+    // skipped, as when it was overwritten before.
+    let segments: Vec<SegmentMeta> = state
+        .index
+        .read()
+        .segments_in_range(&req.camera_id, req.from, req.to)
+        .into_iter()
+        .cloned()
+        .collect();
+    let vault = match vault(&state) {
+        Ok(v) => v,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                axum::Json(serde_json::json!({ "error": e.to_string() })),
+            );
+        }
+    };
+    let task = tokio::task::spawn_blocking(move || {
+        vault.create_hold(&req, &segments, &pool, chrono::Utc::now())
+    });
+    let result = match task.await {
+        Ok(result) => result,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                axum::Json(serde_json::json!({ "error": format!("Hold task failed: {e}") })),
+            );
+        }
+    };
+
+    match result {
+        Ok(hold) => (
+            StatusCode::CREATED,
+            axum::Json(serde_json::json!({ "status": "held", "hold": HoldInfo::from(&hold) })),
+        ),
+        Err(e @ NvrError::Config(_)) => (
+            StatusCode::BAD_REQUEST,
+            axum::Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+        Err(NvrError::Storage(msg)) => (
+            StatusCode::NOT_FOUND,
+            axum::Json(serde_json::json!({ "error": msg })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            axum::Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Release a hold, deleting its copies.
+async fn handle_release_hold(
+    State(state): State<Arc<AppState>>,
+    Path(hold_id): Path<u64>,
+) -> impl IntoResponse {
+    match vault(&state).and_then(|v| v.release(hold_id)) {
+        Ok(true) => (StatusCode::OK, axum::Json(serde_json::json!({
+            "status": "released",
+            "hold_id": hold_id,
+        }))),
+        Ok(false) => (StatusCode::NOT_FOUND, axum::Json(serde_json::json!({
+            "error": format!("Hold {} not found", hold_id),
+        }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            axum::Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
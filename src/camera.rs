@@ -8,12 +8,17 @@
 //! Each camera runs a GStreamer pipeline:
 //!   rtspsrc → (depay → parse, chosen at runtime from the negotiated RTP
 //!   encoding) → splitmuxsink(mp4mux)
+//! plus, with `record_audio`, a second branch for the audio track into the
+//! same splitmuxsink, so every segment carries both.
 //!
 //! The depayloader/parser pair isn't known until `rtspsrc` negotiates the
 //! stream's RTP caps with the camera, so it's wired up dynamically from the
 //! `pad-added` signal instead of being part of a static pipeline string.
-//! Currently supported encodings: H264 (`rtph264depay ! h264parse`) and AV1
-//! (`rtpav1depay ! av1parse`).
+//! Currently supported encodings: H264 (`rtph264depay ! h264parse`), H265
+//! (`rtph265depay ! h265parse`, recorded as `hvc1`) and AV1
+//! (`rtpav1depay ! av1parse`). Audio: AAC (`rtpmp4gdepay`/`rtpmp4adepay !
+//! aacparse`), and G.711 PCMU/PCMA, transcoded to AAC since MP4 players
+//! (browsers included) don't play it.
 //!
 //! `splitmuxsink` owns segment cutting: it always splits at the next
 //! keyframe at/after `max-size-time`, so every resulting fragment file is a
@@ -140,10 +145,12 @@ impl CameraStream {
         // The depay/parse chain depends on the codec the camera actually
         // negotiates over RTSP, which isn't known until `rtspsrc` creates its
         // (sometimes) src pad for the stream. Build and link it dynamically
-        // here rather than assuming H264 up front.
+        // here rather than assuming H264 up front. Audio, if recorded, gets
+        // a chain of its own into one of splitmuxsink's audio pads.
         let pipeline_for_pad = pipeline.clone();
         let splitmux_for_pad = splitmux.clone();
         let camera_id_for_pad = config.id.clone();
+        let record_audio = config.record_audio;
         rtspsrc.connect_pad_added(move |_src, pad| {
             let Some(caps) = pad.current_caps() else {
                 warn!(camera = camera_id_for_pad, "rtspsrc pad has no negotiated caps yet, ignoring");
@@ -154,24 +161,34 @@ impl CameraStream {
                 return;
             };
 
-            // Only the video media is recorded; silently ignore any other
-            // pad (e.g. an audio track) rather than treating it as an error.
-            if s.get::<String>("media").ok().as_deref() != Some("video") {
-                return;
-            }
-
+            let media = s.get::<String>("media").ok();
             let encoding_name = s.get::<String>("encoding-name").ok();
-            let (depay_factory, parse_factory) = match encoding_name.as_deref() {
-                Some("H264") => ("rtph264depay", "h264parse"),
-                Some("AV1") => ("rtpav1depay", "av1parse"),
-                other => {
-                    error!(
-                        camera = camera_id_for_pad,
-                        encoding = ?other,
-                        "Unsupported video RTP encoding, cannot record this camera"
-                    );
-                    return;
-                }
+            let (factories, splitmux_pad) = match media.as_deref() {
+                Some("video") => match video_chain(encoding_name.as_deref()) {
+                    Some(factories) => (factories, "video"),
+                    None => {
+                        error!(
+                            camera = camera_id_for_pad,
+                            encoding = ?encoding_name,
+                            "Unsupported video RTP encoding, cannot record this camera"
+                        );
+                        return;
+                    }
+                },
+                Some("audio") if record_audio => match audio_chain(encoding_name.as_deref()) {
+                    Some(factories) => (factories, "audio_%u"),
+                    None => {
+                        warn!(
+                            camera = camera_id_for_pad,
+                            encoding = ?encoding_name,
+                            "Cannot record this audio RTP encoding, recording video only"
+                        );
+                        return;
+                    }
+                },
+                // Audio with `record_audio` off, or any other media (e.g.
+                // ONVIF metadata): silently ignored rather than an error.
+                _ => return,
             };
 
             let make = |factory: &str| -> Option<gst::Element> {
@@ -184,14 +201,31 @@ impl CameraStream {
                 }
             };
 
-            let (Some(depay), Some(parse)) = (make(depay_factory), make(parse_factory)) else {
-                return;
-            };
-            if parse_factory == "h264parse" {
-                parse.set_property("config-interval", -1i32);
+            let mut chain = Vec::with_capacity(factories.len());
+            for factory in factories {
+                let Some(el) = make(factory) else {
+                    return;
+                };
+                match factory {
+                    "h264parse" => el.set_property("config-interval", -1i32),
+                    // Only follows `h265parse`. HEVC is recorded with an
+                    // `hvc1` sample entry (parameter sets in the `moov`, not
+                    // in-band): the only form Safari and other Apple players
+                    // accept. mp4mux writes `hev1` for byte-stream caps, so
+                    // ask the parser for `hvc1` explicitly.
+                    "capsfilter" => el.set_property(
+                        "caps",
+                        gst::Caps::builder("video/x-h265")
+                            .field("stream-format", "hvc1")
+                            .field("alignment", "au")
+                            .build(),
+                    ),
+                    _ => {}
+                }
+                chain.push(el);
             }
 
-            for el in [&depay, &parse] {
+            for el in &chain {
                 if let Err(e) = pipeline_for_pad.add(el) {
                     error!(camera = camera_id_for_pad, error = %e, "Failed to add element to pipeline");
                     return;
@@ -202,12 +236,12 @@ impl CameraStream {
                 }
             }
 
-            if let Err(e) = depay.link(&parse) {
+            if let Err(e) = gst::Element::link_many(&chain) {
                 error!(camera = camera_id_for_pad, error = %e, "Failed to link depayloader to parser");
                 return;
             }
 
-            let Some(depay_sink) = depay.static_pad("sink") else {
+            let Some(depay_sink) = chain[0].static_pad("sink") else {
                 error!(camera = camera_id_for_pad, "depayloader has no sink pad");
                 return;
             };
@@ -216,16 +250,20 @@ impl CameraStream {
                 return;
             }
 
-            let Some(splitmux_sink) = splitmux_for_pad.request_pad_simple("video") else {
-                error!(camera = camera_id_for_pad, "splitmuxsink has no video pad available");
+            let Some(splitmux_sink) = splitmux_for_pad.request_pad_simple(splitmux_pad) else {
+                error!(camera = camera_id_for_pad, pad = splitmux_pad, "splitmuxsink has no pad available");
                 return;
             };
-            let Some(parse_src) = parse.static_pad("src") else {
+            let Some(parse_src) = chain.last().and_then(|el| el.static_pad("src")) else {
                 error!(camera = camera_id_for_pad, "parser has no src pad");
                 return;
             };
             if let Err(e) = parse_src.link(&splitmux_sink) {
                 error!(camera = camera_id_for_pad, error = ?e, "Failed to link parser to splitmuxsink");
+                return;
+            }
+            if splitmux_pad != "video" {
+                info!(camera = camera_id_for_pad, encoding = ?encoding_name, "Recording audio track");
             }
         });
 
@@ -348,6 +386,41 @@ impl Drop for CameraStream {
     }
 }
 
+/// Elements from an RTP video pad to `splitmuxsink`, for an RTP encoding.
+fn video_chain(encoding: Option<&str>) -> Option<Vec<&'static str>> {
+    match encoding? {
+        "H264" => Some(vec!["rtph264depay", "h264parse"]),
+        "H265" => Some(vec!["rtph265depay", "h265parse", "capsfilter"]),
+        "AV1" => Some(vec!["rtpav1depay", "av1parse"]),
+        _ => None,
+    }
+}
+
+/// AAC encoders for transcoding G.711, in order of preference.
+const AAC_ENCODERS: &[&str] = &["fdkaacenc", "voaacenc", "avenc_aac"];
+
+/// Elements from an RTP audio pad to `splitmuxsink`, for an RTP encoding.
+/// AAC is recorded as sent; G.711, which browsers won't play from MP4, is
+/// decoded and encoded to AAC with the first installed encoder.
+fn audio_chain(encoding: Option<&str>) -> Option<Vec<&'static str>> {
+    let (depay, decoder) = match encoding? {
+        "MPEG4-GENERIC" => return Some(vec!["rtpmp4gdepay", "aacparse"]),
+        "MP4A-LATM" => return Some(vec!["rtpmp4adepay", "aacparse"]),
+        "PCMU" => ("rtppcmudepay", "mulawdec"),
+        "PCMA" => ("rtppcmadepay", "alawdec"),
+        _ => return None,
+    };
+    let Some(encoder) = AAC_ENCODERS
+        .iter()
+        .copied()
+        .find(|f| gst::ElementFactory::find(f).is_some())
+    else {
+        warn!(encoders = ?AAC_ENCODERS, "No AAC encoder installed, cannot transcode G.711 audio");
+        return None;
+    };
+    Some(vec![depay, decoder, "audioconvert", "audioresample", encoder, "aacparse"])
+}
+
 /// Spawn a task that keeps a camera connected, reconnecting on failure.
 ///
 /// Returns a ready-to-use `CameraStream`. When a stream errors or closes,
@@ -0,0 +1,225 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Codec strings for manifests, read from recorded segments.
+//!
+//! HLS (`CODECS` in the multivariant playlist) and DASH (`codecs` on the
+//! `Representation`) tell players which codecs a stream uses before they
+//! fetch any media, so a browser can pick decoders — or skip a stream it
+//! can't play. Codecs aren't stored in the index; they're read from the
+//! sample entries in a segment's `moov` box
+//! (`moov/trak/mdia/minf/stbl/stsd`), which every segment carries, and
+//! formatted as RFC 6381 strings:
+//!
+//! ```text
+//! avc1 / avc3  →  avc1.64001F          (profile, constraints, level)
+//! hvc1 / hev1  →  hvc1.1.6.L120.90     (ISO/IEC 14496-15 annex E)
+//! av01         →  av01.0.08M.08        (AV1 codec ISO-BMFF binding)
+//! mp4a         →  mp4a.40.2            (object type, audio object type)
+//! ```
+
+use byteorder::{BigEndian, ByteOrder};
+
+/// RFC 6381 codecs of an MP4 segment: its video track's, followed by its
+/// audio track's if it has one (`avc1.64001F,mp4a.40.2`). `None` if it has
+/// no recognizable H.264, H.265 or AV1 video track; an unrecognized audio
+/// track is left out.
+pub fn codec_string(segment: &[u8]) -> Option<String> {
+    let mut video = None;
+    let mut audio = None;
+    for (handler, kind, entry) in sample_entries(segment) {
+        match &handler {
+            b"vide" if video.is_none() => video = video_codec(&kind, entry),
+            b"soun" if audio.is_none() => audio = audio_codec(&kind, entry),
+            _ => {}
+        }
+    }
+    let video = video?;
+    Some(match audio {
+        Some(audio) => format!("{video},{audio}"),
+        None => video,
+    })
+}
+
+/// Whether an MP4 segment has an audio track.
+pub fn has_audio(segment: &[u8]) -> bool {
+    sample_entries(segment).any(|(handler, _, _)| &handler == b"soun")
+}
+
+/// Handler type (`vide`, `soun`, ...), sample entry type and sample entry
+/// of each track in a segment's `moov`.
+fn sample_entries(segment: &[u8]) -> impl Iterator<Item = ([u8; 4], [u8; 4], &[u8])> {
+    child(segment, b"moov")
+        .into_iter()
+        .flat_map(boxes)
+        .filter(|(kind, _)| kind == b"trak")
+        .filter_map(|(_, trak)| {
+            let mdia = child(trak, b"mdia")?;
+            // version/flags, pre_defined, then handler_type
+            let handler = child(mdia, b"hdlr")?.get(8..12)?.try_into().ok()?;
+            let stsd = child(child(child(mdia, b"minf")?, b"stbl")?, b"stsd")?;
+            // version/flags and entry_count precede the first sample entry.
+            let (kind, entry) = boxes(stsd.get(8..)?).next()?;
+            Some((handler, kind, entry))
+        })
+}
+
+/// Size of a VisualSampleEntry's fixed fields, before its child boxes.
+const VISUAL_SAMPLE_ENTRY_SIZE: usize = 78;
+
+/// Size of a (version 0) AudioSampleEntry's fixed fields.
+const AUDIO_SAMPLE_ENTRY_SIZE: usize = 28;
+
+fn video_codec(kind: &[u8; 4], entry: &[u8]) -> Option<String> {
+    let config_boxes = entry.get(VISUAL_SAMPLE_ENTRY_SIZE..)?;
+    match kind {
+        b"avc1" | b"avc3" => avc_codec(kind, child(config_boxes, b"avcC")?),
+        b"hvc1" | b"hev1" => hevc_codec(kind, child(config_boxes, b"hvcC")?),
+        b"av01" => av1_codec(child(config_boxes, b"av1C")?),
+        _ => None,
+    }
+}
+
+fn audio_codec(kind: &[u8; 4], entry: &[u8]) -> Option<String> {
+    match kind {
+        b"mp4a" => mp4a_codec(child(entry.get(AUDIO_SAMPLE_ENTRY_SIZE..)?, b"esds")?),
+        _ => None,
+    }
+}
+
+/// `avc1.PPCCLL` from an AVCDecoderConfigurationRecord.
+fn avc_codec(kind: &[u8; 4], avcc: &[u8]) -> Option<String> {
+    let record = avcc.get(1..4)?;
+    Some(format!(
+        "{}.{:02X}{:02X}{:02X}",
+        std::str::from_utf8(kind).ok()?,
+        record[0],
+        record[1],
+        record[2]
+    ))
+}
+
+/// `hvc1.<space><profile>.<compat>.<tier><level>[.<constraints>]` from an
+/// HEVCDecoderConfigurationRecord.
+fn hevc_codec(kind: &[u8; 4], hvcc: &[u8]) -> Option<String> {
+    let record = hvcc.get(..13)?;
+    let profile_space = ["", "A", "B", "C"][(record[1] >> 6) as usize];
+    let tier = if record[1] & 0x20 != 0 { 'H' } else { 'L' };
+    let profile_idc = record[1] & 0x1F;
+    // Written in reverse bit order, as hex without leading zeros.
+    let compatibility = BigEndian::read_u32(&record[2..6]).reverse_bits();
+    let level_idc = record[12];
+
+    let mut codec = format!(
+        "{}.{profile_space}{profile_idc}.{compatibility:X}.{tier}{level_idc}",
+        std::str::from_utf8(kind).ok()?
+    );
+    // Constraint flag bytes, with trailing zero bytes left out.
+    let constraints = &record[6..12];
+    let used = constraints.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    for byte in &constraints[..used] {
+        codec.push_str(&format!(".{byte:X}"));
+    }
+    Some(codec)
+}
+
+/// `av01.<profile>.<level><tier>.<bitdepth>` from an
+/// AV1CodecConfigurationRecord.
+fn av1_codec(av1c: &[u8]) -> Option<String> {
+    let record = av1c.get(..3)?;
+    let profile = record[1] >> 5;
+    let level = record[1] & 0x1F;
+    let tier = if record[2] & 0x80 != 0 { 'H' } else { 'M' };
+    let bit_depth = match (record[2] & 0x40 != 0, record[2] & 0x20 != 0) {
+        (true, true) => 12,
+        (true, false) => 10,
+        _ => 8,
+    };
+    Some(format!("av01.{profile}.{level:02}{tier}.{bit_depth:02}"))
+}
+
+/// `mp4a.<object type>.<audio object type>` from the ES_Descriptor in an
+/// `esds` box (ISO/IEC 14496-1 and 14496-3).
+fn mp4a_codec(esds: &[u8]) -> Option<String> {
+    // version/flags precede the descriptor.
+    let es = descriptor(esds.get(4..)?, 0x03)?;
+    // ES_ID, then flags for the optional fields before the config.
+    let flags = *es.get(2)?;
+    let mut at = 3;
+    if flags & 0x80 != 0 {
+        at += 2;
+    }
+    if flags & 0x40 != 0 {
+        at += 1 + *es.get(at)? as usize;
+    }
+    if flags & 0x20 != 0 {
+        at += 2;
+    }
+    let config = descriptor(es.get(at..)?, 0x04)?;
+    let object_type = *config.first()?;
+    // The AudioSpecificConfig follows 13 bytes of stream type and rates.
+    let audio_object_type = descriptor(config.get(13..)?, 0x05).and_then(|info| {
+        match info.first()? >> 3 {
+            // Escape: the actual type follows in 6 more bits.
+            31 => Some(32 + (((info[0] & 0x07) << 3) | (info.get(1)? >> 5))),
+            aot => Some(aot),
+        }
+    });
+    Some(match audio_object_type {
+        Some(aot) => format!("mp4a.{object_type:x}.{aot}"),
+        None => format!("mp4a.{object_type:x}"),
+    })
+}
+
+/// The body of the descriptor at the start of `data`, if its tag is `tag`.
+fn descriptor(data: &[u8], tag: u8) -> Option<&[u8]> {
+    if *data.first()? != tag {
+        return None;
+    }
+    // Up to 4 length bytes, 7 bits each, high bit set on all but the last.
+    let mut len = 0usize;
+    let mut at = 1;
+    loop {
+        let byte = *data.get(at)?;
+        at += 1;
+        len = (len << 7) | (byte & 0x7F) as usize;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if at > 4 {
+            return None;
+        }
+    }
+    data.get(at..at + len)
+}
+
+/// The payload of the first child box of type `kind`.
+fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data).find(|(k, _)| k == kind).map(|(_, payload)| payload)
+}
+
+/// The (type, payload) of each box in `data`, stopping at the first one
+/// that is truncated or malformed.
+fn boxes(data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let header = rest.get(..8)?;
+        let kind: [u8; 4] = header[4..8].try_into().ok()?;
+        let (size, header_len) = match BigEndian::read_u32(&header[..4]) {
+            // Extends to the end of the enclosing box.
+            0 => (rest.len() as u64, 8),
+            // 64-bit size follows the type.
+            1 => (BigEndian::read_u64(rest.get(8..16)?), 16),
+            size => (size as u64, 8),
+        };
+        let size = usize::try_from(size).ok()?;
+        if size < header_len || size > rest.len() {
+            return None;
+        }
+        let payload = &rest[header_len..size];
+        rest = &rest[size..];
+        Some((kind, payload))
+    })
+}
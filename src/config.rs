@@ -57,6 +57,12 @@ fn default_api_password() -> String { "1234".to_string() }
 pub struct StorageConfig {
     /// Base directory where pool files are stored.
     pub base_path: PathBuf,
+    /// Optional pool file directories, one per disk. Pool slots are striped
+    /// across them in ring order; `base_path` then only holds metadata.
+    /// Directories are never created automatically, so an unmounted disk
+    /// takes its slots offline instead of filling the root filesystem.
+    #[serde(default)]
+    pub volumes: Vec<PathBuf>,
     /// Size of each pre-allocated pool file in megabytes.
     /// All cameras share the same pool files (sequential I/O, HDD friendly).
     #[serde(default = "default_chunk_size_mb")]
@@ -71,8 +77,153 @@ pub struct StorageConfig {
     /// Bounded channel capacity for the global writer queue.
     #[serde(default = "default_writer_queue")]
     pub writer_queue_size: usize,
+    /// Verify each record's checksum when serving it (startup scans always
+    /// verify). Costs a CRC pass over every segment read.
+    #[serde(default)]
+    pub verify_checksums_on_read: bool,
+    /// Keep an index snapshot (`index.snapshot`) so startup only scans pools
+    /// written since it was taken.
+    #[serde(default = "default_index_snapshot")]
+    pub index_snapshot: bool,
+    /// When pool writes reach stable storage, and how they are issued.
+    #[serde(default)]
+    pub durability: DurabilityConfig,
+    /// What camera workers do when the writer queue is full.
+    #[serde(default)]
+    pub overload: OverloadConfig,
+    /// Key file for encrypting record payloads at rest (32 raw bytes or 64
+    /// hex digits). Unset = footage is stored in the clear. See
+    /// [`crypto`](crate::storage::crypto).
+    #[serde(default)]
+    pub encryption_key_file: Option<PathBuf>,
+    /// Optional second ring on another disk that every recorded segment is
+    /// mirrored into. See [`replica`](crate::storage::replica).
+    #[serde(default)]
+    pub replica: Option<ReplicaConfig>,
+    /// Optional cold archive that segments are copied into before the ring
+    /// overwrites them. See [`archive`](crate::storage::archive).
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+}
+
+/// Cold archive settings.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ArchiveConfig {
+    /// Directory of the per-camera, per-day archive files.
+    pub path: PathBuf,
+    /// Cameras to archive; empty = all.
+    #[serde(default)]
+    pub cameras: Vec<String>,
+    /// Archive at most one segment per camera in this many seconds
+    /// (0 = every segment).
+    #[serde(default)]
+    pub min_interval_secs: u64,
+    /// Delete archived days older than this (0 = keep forever).
+    #[serde(default)]
+    pub max_age_days: u32,
+}
+
+/// Replica ring settings. The replica has its own ring size, so it can
+/// keep more (or less) history than the primary.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReplicaConfig {
+    /// Directory of the replica's pool files, on a different disk than
+    /// `storage.base_path`.
+    pub base_path: PathBuf,
+    /// Size of each replica pool file in megabytes.
+    #[serde(default = "default_chunk_size_mb")]
+    pub chunk_size_mb: u64,
+    /// Number of pool files in the replica ring.
+    #[serde(default = "default_max_chunks")]
+    pub max_pools: usize,
+    /// Segments waiting to be mirrored; beyond that the replica falls behind
+    /// and segments are skipped rather than slowing down the primary.
+    #[serde(default = "default_writer_queue")]
+    pub queue_size: usize,
+}
+
+/// Writer overload settings (see [`overload`](crate::overload)).
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OverloadConfig {
+    /// Which segments are dropped once a spill buffer is full.
+    #[serde(default)]
+    pub policy: OverloadPolicy,
+    /// Per-camera cap on segments held in memory while the writer queue is
+    /// full, in megabytes.
+    #[serde(default = "default_spill_buffer_mb")]
+    pub spill_buffer_mb: u64,
+}
+
+/// How camera workers shed load when the writer can't keep up.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverloadPolicy {
+    /// A camera's oldest spilled segments make room for its new ones.
+    #[default]
+    DropOldest,
+    /// Like `drop_oldest`, and while a camera is spilling, cameras with a
+    /// lower `priority` drop their segments.
+    DropByPriority,
+}
+
+impl Default for OverloadConfig {
+    fn default() -> Self {
+        Self {
+            policy: OverloadPolicy::default(),
+            spill_buffer_mb: default_spill_buffer_mb(),
+        }
+    }
 }
 
+fn default_spill_buffer_mb() -> u64 { 64 }
+
+/// Write durability settings for the pool writer.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DurabilityConfig {
+    /// When written records are `fdatasync`ed.
+    #[serde(default)]
+    pub mode: DurabilityMode,
+    /// Longest a record stays unsynced in `interval` mode.
+    #[serde(default = "default_sync_interval_ms")]
+    pub sync_interval_ms: u64,
+    /// Write pool files with `O_DIRECT` (Linux only), bypassing the page
+    /// cache so write-once video doesn't evict everything else. Falls back
+    /// to buffered writes where the filesystem doesn't support it.
+    #[serde(default)]
+    pub direct_io: bool,
+    /// Most queued segments the writer combines into one sequential write
+    /// (1 = one write per segment).
+    #[serde(default = "default_coalesce_max_records")]
+    pub coalesce_max_records: usize,
+}
+
+/// When the writer `fdatasync`s pool files.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DurabilityMode {
+    /// Never; the OS writes pages back when it sees fit.
+    #[default]
+    None,
+    /// After every write, before its segments are indexed.
+    PerRecord,
+    /// At most `sync_interval_ms` after a write.
+    Interval,
+}
+
+impl Default for DurabilityConfig {
+    fn default() -> Self {
+        Self {
+            mode: DurabilityMode::default(),
+            sync_interval_ms: default_sync_interval_ms(),
+            direct_io: false,
+            coalesce_max_records: default_coalesce_max_records(),
+        }
+    }
+}
+
+fn default_sync_interval_ms() -> u64 { 1000 }
+fn default_coalesce_max_records() -> usize { 16 }
+
 /// Per-camera configuration.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CameraConfig {
@@ -82,15 +233,87 @@ pub struct CameraConfig {
     pub name: String,
     /// RTSP (or HTTP) URL of the camera stream.
     pub url: String,
+    /// Optional lower-resolution stream of the same camera, recorded
+    /// alongside `url` and shown in the live grid. Not archived or
+    /// replicated; only the main stream is.
+    #[serde(default)]
+    pub substream_url: Option<String>,
     /// Optional reconnection attempt limit (0 = unlimited).
     #[serde(default)]
     pub max_reconnect_attempts: u32,
+    /// Keep this camera's footage at least this many days, even when other
+    /// cameras fill the ring faster (0 = no guarantee). See
+    /// [`retention`](crate::storage::retention).
+    #[serde(default)]
+    pub min_retention_days: u32,
+    /// Largest fraction of the total ring this camera's footage may occupy
+    /// through `min_retention_days` (0 < share <= 1).
+    #[serde(default = "default_max_share")]
+    pub max_share: f64,
+    /// Under the `drop_by_priority` overload policy, cameras with a lower
+    /// priority give way to this one when the writer falls behind.
+    #[serde(default)]
+    pub priority: u32,
+    /// Record the camera's audio track (AAC as sent, G.711 transcoded to
+    /// AAC) into the same segments as its video. Off by default.
+    #[serde(default)]
+    pub record_audio: bool,
+}
+
+fn default_max_share() -> f64 { 1.0 }
+
+/// Length of the camera ID prefix stored in record headers. Recordings made
+/// before the camera registry only carry this prefix.
+const RECORDED_ID_PREFIX: usize = 16;
+
+/// Validate camera settings. Rejects IDs that are empty, unusable in URLs
+/// and directory names, or that collide: duplicates, and IDs equal to
+/// another ID's 16-byte prefix (older recordings of the longer one would be
+/// attributed to the shorter). Also checks `max_share`.
+pub fn validate_cameras(cameras: &[CameraConfig]) -> Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    for cam in cameras {
+        let id = cam.id.as_str();
+        if id.is_empty()
+            || id == "."
+            || id == ".."
+            || id.chars().any(|c| c == '/' || c == '\\' || c.is_control())
+        {
+            return Err(NvrError::Config(format!(
+                "Camera ID {id:?} must be non-empty, not `.` or `..`, without slashes or control characters"
+            )));
+        }
+        if !seen.insert(id) {
+            return Err(NvrError::Config(format!("Duplicate camera ID '{id}'")));
+        }
+        if !(cam.max_share > 0.0 && cam.max_share <= 1.0) {
+            return Err(NvrError::Config(format!(
+                "Camera '{id}': max_share must be in (0, 1], got {}",
+                cam.max_share
+            )));
+        }
+    }
+    for cam in cameras {
+        let mut n = cam.id.len().min(RECORDED_ID_PREFIX);
+        while !cam.id.is_char_boundary(n) {
+            n -= 1;
+        }
+        let prefix = &cam.id[..n];
+        if prefix != cam.id && seen.contains(prefix) {
+            return Err(NvrError::Config(format!(
+                "Camera ID '{prefix}' collides with the recorded prefix of '{}'",
+                cam.id
+            )));
+        }
+    }
+    Ok(())
 }
 
 fn default_chunk_size_mb() -> u64 { 512 }
 fn default_max_chunks() -> usize { 20 }
 fn default_segment_duration() -> u64 { 60 }
 fn default_writer_queue() -> usize { 256 }
+fn default_index_snapshot() -> bool { true }
 
 impl Config {
     /// Load configuration from a TOML file at `path`.
@@ -116,6 +339,28 @@ impl Config {
         if self.storage.segment_duration_secs == 0 {
             return Err(NvrError::Config("segment_duration_secs must be > 0".into()));
         }
+        let durability = &self.storage.durability;
+        if durability.mode == DurabilityMode::Interval && durability.sync_interval_ms == 0 {
+            return Err(NvrError::Config("durability.sync_interval_ms must be > 0".into()));
+        }
+        if durability.coalesce_max_records == 0 {
+            return Err(NvrError::Config("durability.coalesce_max_records must be > 0".into()));
+        }
+        if let Some(replica) = &self.storage.replica {
+            if replica.base_path == self.storage.base_path
+                || self.storage.volumes.contains(&replica.base_path)
+            {
+                return Err(NvrError::Config(
+                    "replica.base_path must differ from the primary storage paths".into(),
+                ));
+            }
+            if replica.chunk_size_mb == 0 || replica.max_pools == 0 || replica.queue_size == 0 {
+                return Err(NvrError::Config(
+                    "replica chunk_size_mb, max_pools and queue_size must be > 0".into(),
+                ));
+            }
+        }
+        validate_cameras(&self.cameras)?;
         Ok(())
     }
 
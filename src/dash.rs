@@ -13,13 +13,14 @@
 //! entry — there is no shared, separate init segment.
 //!
 //! Endpoints served via the HTTP API:
-//!   GET /api/dash/{camera_id}/manifest.mpd             → live manifest
+//!   GET /api/dash/{camera_id}/manifest.mpd             → live manifest (main + substream)
 //!   GET /api/dash/{camera_id}/manifest.mpd?from=...&to=...  → VOD manifest for time range
 
 use std::fmt::Write as FmtWrite;
 
 use chrono::{DateTime, Utc};
 
+use crate::storage::chunk_pool::Stream;
 use crate::storage::index::{SegmentIndex, SegmentMeta};
 
 /// Number of segments to include in the live sliding-window manifest.
@@ -27,31 +28,45 @@ const LIVE_WINDOW_SEGMENTS: usize = 10;
 
 /// Generate a live DASH manifest for a camera using an explicit
 /// `SegmentTimeline` (segment durations vary since cuts are keyframe-aligned,
-/// not fixed-duration).
+/// not fixed-duration). A camera with a substream gets a second
+/// `Representation` (`{camera_id}-sub`) in the same `AdaptationSet`, so
+/// players can switch down to it.
 pub fn generate_live_mpd(
     index: &SegmentIndex,
     camera_id: &str,
     segment_duration_secs: u64,
+    codecs: Option<&str>,
+    sub_codecs: Option<&str>,
 ) -> Option<String> {
-    let all_segments = index.segments_for_camera(camera_id);
-
-    if all_segments.is_empty() {
+    let renditions: Vec<Rendition> = [
+        (Stream::Main, camera_id.to_string(), codecs),
+        (Stream::Sub, format!("{camera_id}-sub"), sub_codecs),
+    ]
+    .into_iter()
+    .map(|(stream, id, codecs)| Rendition {
+        id,
+        segments: index.latest_for_stream(camera_id, stream, LIVE_WINDOW_SEGMENTS),
+        codecs,
+    })
+    .filter(|r| !r.segments.is_empty())
+    .collect();
+
+    if renditions.is_empty() {
         return Some(empty_mpd(segment_duration_secs, true));
     }
 
-    let window_start = all_segments.len().saturating_sub(LIVE_WINDOW_SEGMENTS);
-    let window = &all_segments[window_start..];
-
-    Some(render_mpd(window, segment_duration_secs, true, camera_id))
+    Some(render_mpd(&renditions, segment_duration_secs, true))
 }
 
-/// Generate a VOD DASH manifest for a camera in a time range.
+/// Generate a VOD DASH manifest for a camera in a time range (main stream
+/// only).
 pub fn generate_vod_mpd(
     index: &SegmentIndex,
     camera_id: &str,
     from: DateTime<Utc>,
     to: DateTime<Utc>,
     segment_duration_secs: u64,
+    codecs: Option<&str>,
 ) -> Option<String> {
     let segments = index.segments_in_range(camera_id, from, to);
 
@@ -59,15 +74,20 @@ pub fn generate_vod_mpd(
         return None;
     }
 
-    Some(render_mpd(&segments, segment_duration_secs, false, camera_id))
+    let rendition = Rendition { id: camera_id.to_string(), segments, codecs };
+    Some(render_mpd(&[rendition], segment_duration_secs, false))
 }
 
-fn render_mpd(
-    segments: &[&SegmentMeta],
-    segment_duration_secs: u64,
-    is_live: bool,
-    camera_id: &str,
-) -> String {
+/// One stream's `Representation`: never without segments.
+struct Rendition<'a> {
+    id: String,
+    segments: Vec<&'a SegmentMeta>,
+    codecs: Option<&'a str>,
+}
+
+fn render_mpd(renditions: &[Rendition], segment_duration_secs: u64, is_live: bool) -> String {
+    // Timing follows the first rendition: the main stream when there is one.
+    let segments = &renditions[0].segments;
     let media_present_time = segments
         .first()
         .map(|s| s.start_ts.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
@@ -100,10 +120,26 @@ fn render_mpd(
         r#"    <AdaptationSet id="0" mimeType="video/mp4" segmentAlignment="true" startWithSAP="1">"#
     )
     .unwrap();
+    for rendition in renditions {
+        render_representation(&mut mpd, rendition, segment_duration_secs);
+    }
+    writeln!(mpd, r#"    </AdaptationSet>"#).unwrap();
+    writeln!(mpd, r#"  </Period>"#).unwrap();
+    writeln!(mpd, r#"</MPD>"#).unwrap();
+
+    mpd
+}
+
+fn render_representation(mpd: &mut String, rendition: &Rendition, segment_duration_secs: u64) {
+    let segments = &rendition.segments;
+    // RFC 6381 codec string, read from a segment (see `codec.rs`); without
+    // it players have to guess, and most assume H.264.
     writeln!(
         mpd,
-        r#"      <Representation id="{}" bandwidth="2000000">"#,
-        camera_id
+        r#"      <Representation id="{}" bandwidth="{}"{}>"#,
+        rendition.id,
+        bandwidth(segments, segment_duration_secs),
+        rendition.codecs.map(|c| format!(r#" codecs="{c}""#)).unwrap_or_default()
     )
     .unwrap();
     writeln!(mpd, r#"        <SegmentTemplate media="segment/mp4/$Number$" timescale="1000" startNumber="{}">"#,
@@ -123,11 +159,17 @@ fn render_mpd(
     writeln!(mpd, r#"          </SegmentTimeline>"#).unwrap();
     writeln!(mpd, r#"        </SegmentTemplate>"#).unwrap();
     writeln!(mpd, r#"      </Representation>"#).unwrap();
-    writeln!(mpd, r#"    </AdaptationSet>"#).unwrap();
-    writeln!(mpd, r#"  </Period>"#).unwrap();
-    writeln!(mpd, r#"</MPD>"#).unwrap();
+}
 
-    mpd
+/// Average bit rate of `segments`, which players compare to pick a
+/// `Representation`.
+fn bandwidth(segments: &[&SegmentMeta], segment_duration_secs: u64) -> u64 {
+    let bytes: u64 = segments.iter().map(|s| s.location.record_size).sum();
+    let secs: f64 = segments
+        .iter()
+        .map(|s| segment_actual_duration(s, segment_duration_secs))
+        .sum();
+    if secs > 0.0 { (bytes as f64 * 8.0 / secs) as u64 } else { 2_000_000 }
 }
 
 fn segment_actual_duration(seg: &SegmentMeta, fallback_secs: u64) -> f64 {
@@ -25,6 +25,9 @@ pub enum NvrError {
     #[error("Index error: {0}")]
     Index(String),
 
+    #[error("Segment evicted: pool {pool_idx} offset {offset} has been overwritten")]
+    SegmentEvicted { pool_idx: usize, offset: u64 },
+
     #[error("Camera '{id}' not found")]
     CameraNotFound { id: String },
 }
@@ -10,22 +10,29 @@
 //! `EXT-X-MAP` init segment is needed.
 //!
 //! Endpoints served via the HTTP API:
+//!   GET /api/hls/{camera_id}/index.m3u8             → multivariant playlist for live
+//!   GET /api/hls/{camera_id}/vod/index.m3u8?from=...&to=... → multivariant playlist for VOD
 //!   GET /api/hls/{camera_id}/live.m3u8              → live sliding-window playlist
+//!   GET /api/hls/{camera_id}/live.m3u8?stream=sub   → same, for the camera's substream
 //!   GET /api/hls/{camera_id}/live.m3u8?_HLS_msn=N   → blocking reload until segment N
 //!   GET /api/hls/{camera_id}/vod.m3u8?from=...&to=...  → VOD playlist for time range
 //!   GET /api/hls/{camera_id}/segment/mp4/{segment_id}  → raw fMP4 segment data
+//!   GET /api/hls/{camera_id}/thumbnails.vtt?from=...&to=... → VOD thumbnail track
+//!   GET /api/hls/{camera_id}/thumbnail/{segment_id}    → segment thumbnail (JPEG)
 
 use std::fmt::Write as FmtWrite;
 
 use chrono::DateTime;
 use chrono::Utc;
 
-use crate::storage::index::{SegmentIndex, SegmentMeta};
+use crate::storage::archive::{self, ArchiveIndex};
+use crate::storage::chunk_pool::Stream;
+use crate::storage::index::{SegmentIndex, SegmentMeta, CONTINUITY_GAP};
 
 /// Number of segments to include in the live sliding-window playlist.
 const LIVE_WINDOW_SEGMENTS: usize = 10;
 
-/// Generate a live HLS playlist for a camera.
+/// Generate a live HLS playlist for one of a camera's streams.
 ///
 /// Uses standard HLS with `CAN-BLOCK-RELOAD` for low-latency polling.
 /// Safari, VLC, HLS.js all support this format natively.
@@ -35,27 +42,25 @@ const LIVE_WINDOW_SEGMENTS: usize = 10;
 pub fn generate_live_playlist(
     index: &SegmentIndex,
     camera_id: &str,
+    stream: Stream,
     segment_duration_secs: u64,
     block_msn: Option<u64>,
 ) -> Option<String> {
-    let all_segments = index.segments_for_camera(camera_id);
+    // The last N segments form the sliding window.
+    let window = index.latest_for_stream(camera_id, stream, LIVE_WINDOW_SEGMENTS);
 
-    if all_segments.is_empty() {
+    if window.is_empty() {
         return Some(empty_live_playlist(segment_duration_secs));
     }
 
     // If blocking, check if the requested MSN exists yet.
     if let Some(msn) = block_msn {
-        let max_id = all_segments.iter().map(|s| s.segment_id).max().unwrap_or(0);
+        let max_id = window.iter().map(|s| s.segment_id).max().unwrap_or(0);
         if msn > max_id {
             return None; // Caller should wait/poll.
         }
     }
 
-    // Take the last N segments for the sliding window.
-    let window_start = all_segments.len().saturating_sub(LIVE_WINDOW_SEGMENTS);
-    let window = &all_segments[window_start..];
-
     let first_seq = window.first().map(|s| s.segment_id).unwrap_or(0);
 
     let mut m3u8 = String::with_capacity(2048);
@@ -79,7 +84,7 @@ pub fn generate_live_playlist(
     for seg in window {
         if let Some(prev_end) = prev_end_ts {
             let gap: chrono::TimeDelta = seg.start_ts - prev_end;
-            if gap.num_milliseconds() > 1500 {
+            if gap > CONTINUITY_GAP {
                 writeln!(m3u8, "#EXT-X-DISCONTINUITY").unwrap();
             }
         }
@@ -103,25 +108,61 @@ pub fn generate_live_playlist(
     Some(m3u8)
 }
 
-/// Generate a VOD playlist for a camera in a time range.
+/// One variant stream of a multivariant playlist.
+pub struct Variant {
+    /// Media playlist URI, relative to the multivariant playlist.
+    pub uri: String,
+    /// RFC 6381 codecs, if known.
+    pub codecs: Option<String>,
+    /// Peak bit rate, bits per second.
+    pub bandwidth: u64,
+}
+
+/// Generate a multivariant playlist listing `variants` (a camera's main
+/// stream and, for live viewing, its substream). Media playlists can't say
+/// which codec they carry, so this is where a player learns it (`CODECS`,
+/// RFC 6381) and can tell whether it can play the stream — H.265 in
+/// particular isn't supported everywhere — and picks a variant by
+/// `BANDWIDTH`.
+pub fn generate_master_playlist(variants: &[Variant]) -> String {
+    let mut m3u8 = String::with_capacity(256);
+    writeln!(m3u8, "#EXTM3U").unwrap();
+    writeln!(m3u8, "#EXT-X-VERSION:7").unwrap();
+    writeln!(m3u8, "#EXT-X-INDEPENDENT-SEGMENTS").unwrap();
+    for variant in variants {
+        let bandwidth = variant.bandwidth;
+        match &variant.codecs {
+            Some(codecs) => {
+                writeln!(m3u8, "#EXT-X-STREAM-INF:BANDWIDTH={bandwidth},CODECS=\"{codecs}\"").unwrap()
+            }
+            None => writeln!(m3u8, "#EXT-X-STREAM-INF:BANDWIDTH={bandwidth}").unwrap(),
+        }
+        writeln!(m3u8, "{}", variant.uri).unwrap();
+    }
+    m3u8
+}
+
+/// Generate a VOD playlist for a camera in a time range, from the ring and
+/// (if given) the cold archive.
 pub fn generate_vod_playlist(
     index: &SegmentIndex,
+    archive: Option<&ArchiveIndex>,
     camera_id: &str,
     from: DateTime<Utc>,
     to: DateTime<Utc>,
     segment_duration_secs: u64,
 ) -> Option<String> {
-    let segments = index.segments_in_range(camera_id, from, to);
+    let segments = archive::segments_in_range(index, archive, camera_id, from, to);
 
     if segments.is_empty() {
         return None;
     }
 
-    let first_seq = segments.first().map(|s| s.segment_id).unwrap_or(0);
+    let first_seq = segments.first().map(|s| s.segment_id()).unwrap_or(0);
 
     let mut max_duration = 0.0f64;
     for seg in &segments {
-        let duration = segment_actual_duration(seg, segment_duration_secs);
+        let duration = span_duration(seg.start_ts(), seg.end_ts(), segment_duration_secs);
         if duration > max_duration {
             max_duration = duration;
         }
@@ -129,7 +170,7 @@ pub fn generate_vod_playlist(
 
     // Calculate start offset if the requested "from" is after the start of the first segment.
     let start_offset = if let Some(first) = segments.first() {
-        let diff = (from - first.start_ts).num_milliseconds() as f64 / 1000.0;
+        let diff = (from - first.start_ts()).num_milliseconds() as f64 / 1000.0;
         if diff > 0.0 { diff } else { 0.0 }
     } else {
         0.0
@@ -152,24 +193,24 @@ pub fn generate_vod_playlist(
 
     for seg in &segments {
         if let Some(prev_end) = prev_end_ts {
-            let gap: chrono::TimeDelta = seg.start_ts - prev_end;
-            if gap.num_milliseconds() > 1500 {
+            let gap: chrono::TimeDelta = seg.start_ts() - prev_end;
+            if gap > CONTINUITY_GAP {
                 writeln!(m3u8, "#EXT-X-DISCONTINUITY").unwrap();
             }
         }
-        prev_end_ts = Some(seg.end_ts);
+        prev_end_ts = Some(seg.end_ts());
 
-        let duration = segment_actual_duration(seg, segment_duration_secs);
+        let duration = span_duration(seg.start_ts(), seg.end_ts(), segment_duration_secs);
         writeln!(
             m3u8,
             "#EXT-X-PROGRAM-DATE-TIME:{}",
-            seg.start_ts.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+            seg.start_ts().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
         ).unwrap();
         writeln!(m3u8, "#EXTINF:{:.3},", duration).unwrap();
         writeln!(
             m3u8,
             "segment/mp4/{}",
-            seg.segment_id
+            seg.segment_id()
         )
         .unwrap();
     }
@@ -178,9 +219,58 @@ pub fn generate_vod_playlist(
     Some(m3u8)
 }
 
+/// Generate the WebVTT thumbnail track of the VOD playlist for the same
+/// range: one cue per segment, on the playlist's timeline, whose text is
+/// the URL of the segment's thumbnail.
+pub fn generate_thumbnail_track(
+    index: &SegmentIndex,
+    archive: Option<&ArchiveIndex>,
+    camera_id: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    segment_duration_secs: u64,
+) -> Option<String> {
+    let segments = archive::segments_in_range(index, archive, camera_id, from, to);
+
+    if segments.is_empty() {
+        return None;
+    }
+
+    let mut vtt = String::with_capacity(64 * segments.len() + 16);
+    writeln!(vtt, "WEBVTT").unwrap();
+
+    // Playback time runs through the segments back to back (gaps between
+    // them are discontinuities, not time), in whole milliseconds.
+    let mut start_ms = 0u64;
+    for seg in &segments {
+        let duration = span_duration(seg.start_ts(), seg.end_ts(), segment_duration_secs);
+        let end_ms = start_ms + (duration * 1000.0).round() as u64;
+        writeln!(vtt).unwrap();
+        writeln!(vtt, "{} --> {}", vtt_timestamp(start_ms), vtt_timestamp(end_ms)).unwrap();
+        writeln!(vtt, "thumbnail/{}", seg.segment_id()).unwrap();
+        start_ms = end_ms;
+    }
+
+    Some(vtt)
+}
+
+fn vtt_timestamp(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
 /// Compute the actual duration of a segment from its timestamps.
 fn segment_actual_duration(seg: &SegmentMeta, fallback_secs: u64) -> f64 {
-    let d = (seg.end_ts - seg.start_ts).num_milliseconds() as f64 / 1000.0;
+    span_duration(seg.start_ts, seg.end_ts, fallback_secs)
+}
+
+fn span_duration(start: DateTime<Utc>, end: DateTime<Utc>, fallback_secs: u64) -> f64 {
+    let d = (end - start).num_milliseconds() as f64 / 1000.0;
     if d > 0.0 { d } else { fallback_secs as f64 }
 }
 
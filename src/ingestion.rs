@@ -11,6 +11,11 @@
 //! bytes, deletes the temp file, and forwards them as a [`WriteRequest`] to
 //! the global chunk writer through an `mpsc` channel. NO direct disk writes
 //! to the pool from here.
+//!
+//! A worker never waits for room in the writer queue while a segment is
+//! pending from its stream: segments the queue can't take are spilled to
+//! memory under the [overload policy](crate::overload) and drained in the
+//! background of the same loop.
 
 use std::path::PathBuf;
 use std::time::Duration;
@@ -20,17 +25,32 @@ use tracing::{error, info, warn};
 
 use crate::camera::{supervised_connect, SegmentReady};
 use crate::config::CameraConfig;
+use crate::overload::{CameraSpill, SharedOverload, Submitted};
+use crate::storage::chunk_pool::Stream;
 use crate::storage::global_writer::WriteRequest;
 
-/// Per-camera ingestion task handle.
+/// Per-camera ingestion task handle. A camera with a substream has one
+/// worker per stream.
 pub struct CameraWorker {
     pub camera_id: String,
+    pub stream: Stream,
     pub writer_tx: mpsc::Sender<WriteRequest>,
+    pub overload: SharedOverload,
 }
 
 impl CameraWorker {
-    pub fn new(camera_id: String, writer_tx: mpsc::Sender<WriteRequest>) -> Self {
-        Self { camera_id, writer_tx }
+    pub fn new(
+        camera_id: String,
+        writer_tx: mpsc::Sender<WriteRequest>,
+        overload: SharedOverload,
+    ) -> Self {
+        Self { camera_id, stream: Stream::Main, writer_tx, overload }
+    }
+
+    /// Record `stream` instead of the main stream.
+    pub fn with_stream(mut self, stream: Stream) -> Self {
+        self.stream = stream;
+        self
     }
 
     /// Spawn the ingestion loop as an async task.
@@ -46,7 +66,9 @@ impl CameraWorker {
     }
 
     async fn run(self, config: CameraConfig, segment_duration: Duration, tmp_dir: PathBuf) {
-        info!(camera = self.camera_id, "Ingestion worker started");
+        info!(camera = self.camera_id, stream = self.stream.as_str(), "Ingestion worker started");
+        let mut spill =
+            CameraSpill::new(self.camera_id.clone(), self.overload.clone()).with_stream(self.stream);
 
         loop {
             let Some(mut stream) = supervised_connect(&config, segment_duration, &tmp_dir).await else {
@@ -56,12 +78,16 @@ impl CameraWorker {
             info!(camera = self.camera_id, "Stream connected, recording");
 
             loop {
-                match stream.read_segment().await {
-                    Some(seg) => self.forward_segment(seg).await,
-                    None => {
-                        warn!(camera = self.camera_id, "Stream closed, waiting for reconnect");
-                        break;
-                    }
+                tokio::select! {
+                    seg = stream.read_segment() => match seg {
+                        Some(seg) => self.forward_segment(seg, &mut spill).await,
+                        None => {
+                            warn!(camera = self.camera_id, "Stream closed, waiting for reconnect");
+                            break;
+                        }
+                    },
+                    // Only polled while something is spilled.
+                    _ = spill.drain_one(&self.writer_tx), if !spill.is_empty() => {}
                 }
             }
         }
@@ -71,7 +97,7 @@ impl CameraWorker {
 
     /// Read a completed fragment file's bytes, delete it, and hand it off
     /// to the global writer as a [`WriteRequest`].
-    async fn forward_segment(&self, seg: SegmentReady) {
+    async fn forward_segment(&self, seg: SegmentReady, spill: &mut CameraSpill) {
         // `splitmuxsink` runs with `async-finalize=true` (see camera.rs) so
         // the *previous* fragment's file may still be getting its trailing
         // moov/mfra flushed in the background when we're notified about it.
@@ -100,24 +126,21 @@ impl CameraWorker {
         let bytes = data.len();
         let req = WriteRequest {
             camera_id: self.camera_id.clone(),
+            stream: self.stream,
             start_ts: seg.start_ts,
             end_ts: seg.end_ts,
             data,
         };
 
-        match self.writer_tx.send(req).await {
-            Ok(()) => {
-                info!(
-                    camera = self.camera_id,
-                    bytes,
-                    start = %seg.start_ts,
-                    end = %seg.end_ts,
-                    "Segment queued for global writer"
-                );
-            }
-            Err(_) => {
-                error!(camera = self.camera_id, "Global writer channel closed, segment dropped");
-            }
+        if spill.submit(&self.writer_tx, req) == Submitted::Queued {
+            info!(
+                camera = self.camera_id,
+                stream = self.stream.as_str(),
+                bytes,
+                start = %seg.start_ts,
+                end = %seg.end_ts,
+                "Segment queued for global writer"
+            );
         }
     }
 
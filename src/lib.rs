@@ -5,11 +5,14 @@
 
 pub mod api;
 pub mod camera;
+pub mod codec;
 pub mod config;
 pub mod dash;
 pub mod error;
 pub mod hls;
 pub mod ingestion;
 pub mod manager;
+pub mod overload;
 pub mod playback;
 pub mod storage;
+pub mod thumbnail;
@@ -10,10 +10,15 @@
 //!   oasis status --config config.toml
 //!   oasis list   --config config.toml --camera cam1
 //!   oasis export --config config.toml --camera cam1 --from "2026-02-19T14:00:00" --to "2026-02-19T15:00:00" -o output.ts
+//!   oasis fsck   --config config.toml [--repair]
+//!   oasis migrate-pools --config config.toml [--dry-run]
+//!   oasis hold create  --config config.toml --camera cam1 --from "2026-02-19T14:00:00" --to "2026-02-19T15:00:00"
+//!   oasis hold list    --config config.toml
+//!   oasis hold release --config config.toml 3
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use clap::{Parser, Subcommand};
 use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
@@ -22,8 +27,13 @@ use nvr::api;
 use nvr::config::Config;
 use nvr::manager::RecordingManager;
 use nvr::playback;
+use nvr::storage::archive::Archive;
 use nvr::storage::chunk_pool::ChunkPool;
-use nvr::storage::index::SegmentIndex;
+use nvr::storage::crypto::RecordCipher;
+use nvr::storage::fsck;
+use nvr::storage::index::{SegmentIndex, SegmentMeta};
+use nvr::storage::migrate::{self, PoolLayout};
+use nvr::storage::vault::{HoldRequest, Vault};
 
 #[derive(Parser)]
 #[command(name = "oasis", about = "Network Video Recorder", version)]
@@ -69,6 +79,69 @@ enum Command {
         #[arg(short, long, default_value = "export.ts")]
         output: PathBuf,
     },
+    /// Verify pool files offline (headers, records, checksums).
+    /// Run only while the recorder is stopped.
+    Fsck {
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+        /// Truncate each damaged pool at its first bad record by
+        /// zero-filling the tail.
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Rewrite the pool files (and the replica's) to the configured
+    /// `chunk_size_mb`, `max_pools` and volumes, keeping the newest
+    /// recordings that fit. Run only while the recorder is stopped; it
+    /// won't start until the layouts match.
+    MigratePools {
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+        /// Only report what would be kept and dropped.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Place footage on hold so the ring never overwrites it, or list and
+    /// release holds.
+    Hold {
+        #[command(subcommand)]
+        action: HoldAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum HoldAction {
+    /// Copy a camera's footage in a time range into the vault.
+    Create {
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+        /// Camera ID.
+        #[arg(long)]
+        camera: String,
+        /// Start time, e.g. "2026-02-19T14:00:00"
+        #[arg(long)]
+        from: String,
+        /// End time, e.g. "2026-02-19T15:00:00"
+        #[arg(long)]
+        to: String,
+        /// Release the hold automatically after this time.
+        #[arg(long)]
+        expires: Option<String>,
+        /// Free-form note stored with the hold (case number, incident…).
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+    /// List holds (expired ones are released first).
+    List {
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+    /// Release a hold and delete its copies.
+    Release {
+        #[arg(short, long, default_value = "config.toml")]
+        config: PathBuf,
+        /// Hold ID, as shown by `hold list`.
+        id: u64,
+    },
 }
 
 #[tokio::main]
@@ -94,6 +167,15 @@ async fn main() {
         Command::Export { config, camera, from, to, output } => {
             run_export(config, &camera, &from, &to, &output);
         }
+        Command::Fsck { config, repair } => {
+            run_fsck(config, repair);
+        }
+        Command::MigratePools { config, dry_run } => {
+            run_migrate_pools(config, dry_run);
+        }
+        Command::Hold { action } => {
+            run_hold(action);
+        }
     }
 }
 
@@ -139,6 +221,8 @@ async fn run_record(config_path: PathBuf) {
                 mgr.read_counters.clone()
             },
             manager: manager.clone(),
+            snapshots: Default::default(),
+            codecs: Default::default(),
         });
         let port = cfg.api.port;
         tokio::spawn(async move {
@@ -158,11 +242,12 @@ async fn run_record(config_path: PathBuf) {
 
     match std::sync::Arc::try_unwrap(manager) {
         Ok(mutex) => mutex.into_inner().shutdown(),
-        Err(_arc) => {
+        Err(arc) => {
             // Other references still held (API server); force shutdown via lock.
             warn!("Forcing shutdown while API still holds references");
             // Can't call shutdown() without ownership, but workers are aborted
-            // when the process exits anyway.
+            // when the process exits anyway. Still persist the index snapshot.
+            arc.lock().save_index_snapshot();
         }
     }
 }
@@ -177,13 +262,22 @@ fn run_status(config_path: PathBuf) {
     };
 
     let pool_bytes = cfg.storage.chunk_size_mb * 1024 * 1024;
-    match ChunkPool::open(&cfg.storage.base_path, pool_bytes, cfg.storage.max_pools) {
-        Ok(pool) => {
+    match ChunkPool::open_volumes(
+        &cfg.storage.base_path,
+        &cfg.storage.volumes,
+        pool_bytes,
+        cfg.storage.max_pools,
+    ) {
+        Ok(mut pool) => {
             let (idx, used, cap) = pool.status();
-            let records = pool.scan_all_pools().unwrap_or_default();
+            let records = pool.take_recovered();
             println!("=== NVR Status ===");
             println!("Pool files  : {}", cfg.storage.max_pools);
             println!("Pool size   : {} MB each", cfg.storage.chunk_size_mb);
+            if !cfg.storage.volumes.is_empty() {
+                let offline = (0..pool.pool_count()).filter(|&i| !pool.is_online(i)).count();
+                println!("Volumes     : {} ({} pool(s) offline)", cfg.storage.volumes.len(), offline);
+            }
             println!(
                 "Active pool : pool_{:03}.bin  ({:.1}% full)",
                 idx,
@@ -213,7 +307,12 @@ fn run_list(config_path: PathBuf, camera_id: &str) {
     };
 
     let pool_bytes = cfg.storage.chunk_size_mb * 1024 * 1024;
-    let pool = match ChunkPool::open(&cfg.storage.base_path, pool_bytes, cfg.storage.max_pools) {
+    let mut pool = match ChunkPool::open_volumes(
+        &cfg.storage.base_path,
+        &cfg.storage.volumes,
+        pool_bytes,
+        cfg.storage.max_pools,
+    ) {
         Ok(p) => p,
         Err(e) => {
             eprintln!("Error: {e}");
@@ -222,7 +321,7 @@ fn run_list(config_path: PathBuf, camera_id: &str) {
     };
 
     // Rebuild index from pools.
-    let records = pool.scan_all_pools().unwrap_or_default();
+    let records = pool.take_recovered();
     let mut index = SegmentIndex::new();
     index.rebuild_from_scanned(records);
 
@@ -236,7 +335,7 @@ fn run_list(config_path: PathBuf, camera_id: &str) {
     println!("{:<6} {:<24} {:<24} {:<10} {:<8}", "ID", "Start", "End", "Pool", "Size");
     println!("{}", "-".repeat(76));
     for seg in &segments {
-        let size_kb = (seg.location.record_size - 40) / 1024; // subtract header
+        let size_kb = seg.location.data_len() / 1024;
         println!(
             "{:<6} {:<24} {:<24} pool_{:03}   {} KB",
             seg.segment_id,
@@ -281,20 +380,37 @@ fn run_export(config_path: PathBuf, camera_id: &str, from: &str, to: &str, outpu
 
     // Open pool and rebuild index.
     let pool_bytes = cfg.storage.chunk_size_mb * 1024 * 1024;
-    let pool = match ChunkPool::open(&cfg.storage.base_path, pool_bytes, cfg.storage.max_pools) {
-        Ok(p) => p,
+    let mut pool = match ChunkPool::open_volumes(
+        &cfg.storage.base_path,
+        &cfg.storage.volumes,
+        pool_bytes,
+        cfg.storage.max_pools,
+    ) {
+        Ok(mut p) => {
+            p.set_verify_reads(cfg.storage.verify_checksums_on_read);
+            p.set_encryption(load_cipher(&cfg));
+            p
+        }
         Err(e) => {
             eprintln!("Error opening pool: {e}");
             std::process::exit(1);
         }
     };
 
-    let records = pool.scan_all_pools().unwrap_or_default();
+    let records = pool.take_recovered();
     let mut index = SegmentIndex::new();
     index.rebuild_from_scanned(records);
 
+    // Footage already rotated out of the ring may still be archived.
+    let archive = cfg.storage.archive.as_ref().map(|ac| {
+        Archive::open(ac, load_cipher(&cfg)).unwrap_or_else(|e| {
+            eprintln!("Error opening archive: {e}");
+            std::process::exit(1);
+        })
+    });
+
     // Export.
-    match playback::export_range(&pool, &index, camera_id, from_utc, to_utc, output) {
+    match playback::export_range(&pool, &index, archive.as_ref(), camera_id, from_utc, to_utc, output) {
         Ok(count) => {
             println!(
                 "Exported {} segments for camera '{}' → {}",
@@ -309,3 +425,263 @@ fn run_export(config_path: PathBuf, camera_id: &str, from: &str, to: &str, outpu
         }
     }
 }
+
+fn run_fsck(config_path: PathBuf, repair: bool) {
+    let cfg = match Config::from_file(&config_path) {
+        Ok(c) => c,
+        Err(e) => {
+            error!(error = %e, "Failed to load config");
+            std::process::exit(1);
+        }
+    };
+
+    let report = match fsck::check_pools(
+        &cfg.storage.base_path,
+        &cfg.storage.volumes,
+        cfg.storage.max_pools,
+        repair,
+    ) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("fsck failed: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    println!("=== Pool check: {} ===", cfg.storage.base_path.display());
+    println!("{:<14} {:<10} {:<8} {:<10} {:<12} Status", "Pool", "Pool ID", "Records", "Unchecked", "Used");
+    println!("{}", "-".repeat(70));
+    for pool in &report.pools {
+        let status = if pool.is_clean() {
+            "ok".to_string()
+        } else if pool.repaired {
+            format!("REPAIRED ({} problem(s))", pool.problems.len())
+        } else {
+            format!("BAD ({} problem(s))", pool.problems.len())
+        };
+        println!(
+            "pool_{:03}.bin   {:<10} {:<8} {:<10} {:<12} {}",
+            pool.pool_idx,
+            pool.pool_id.map(|id| id.to_string()).unwrap_or_else(|| "-".into()),
+            pool.good_records,
+            pool.unverified_records,
+            format!("{} KB", pool.bytes_used / 1024),
+            status,
+        );
+        for problem in &pool.problems {
+            println!("    ! {problem}");
+        }
+    }
+    for problem in &report.problems {
+        println!("! {problem}");
+    }
+
+    if report.is_clean() {
+        println!("\nAll {} pool files OK", report.pools.len());
+    } else if repair && report.is_repaired() {
+        println!("\nRepaired. Damaged tails were zero-filled; recording resumes after the last good record.");
+    } else {
+        if !repair {
+            println!("\nProblems found. Re-run with --repair to truncate damaged pools at their first bad record.");
+        } else {
+            println!("\nSome problems could not be repaired automatically.");
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run_migrate_pools(config_path: PathBuf, dry_run: bool) {
+    let cfg = match Config::from_file(&config_path) {
+        Ok(c) => c,
+        Err(e) => {
+            error!(error = %e, "Failed to load config");
+            std::process::exit(1);
+        }
+    };
+
+    let layout = PoolLayout {
+        pool_capacity: cfg.storage.chunk_size_mb * 1024 * 1024,
+        pool_count: cfg.storage.max_pools,
+    };
+    migrate_ring("Pool", &cfg.storage.base_path, &cfg.storage.volumes, layout, dry_run);
+    if let Some(rc) = &cfg.storage.replica {
+        let layout = PoolLayout {
+            pool_capacity: rc.chunk_size_mb * 1024 * 1024,
+            pool_count: rc.max_pools,
+        };
+        migrate_ring("Replica", &rc.base_path, &[], layout, dry_run);
+    }
+}
+
+/// Migrate one ring (the primary or the replica) and print the report.
+fn migrate_ring(name: &str, base_path: &Path, volumes: &[PathBuf], layout: PoolLayout, dry_run: bool) {
+    let report = match migrate::migrate_pools(base_path, volumes, layout, dry_run) {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            println!("{name} files already match the configured layout");
+            return;
+        }
+        Err(e) => {
+            eprintln!("{name} migration failed: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    println!("=== {name} migration{} ===", if dry_run { " (dry run)" } else { "" });
+    println!(
+        "From        : {} pools × {} MB",
+        report.from.pool_count,
+        report.from.pool_capacity / 1_048_576
+    );
+    println!(
+        "To          : {} pools × {} MB",
+        report.to.pool_count,
+        report.to.pool_capacity / 1_048_576
+    );
+    println!("Kept        : {} segments ({} MB) in {} pools",
+        report.records_kept,
+        report.bytes_copied / 1_048_576,
+        report.pools_used
+    );
+    println!("Dropped     : {} oldest segments", report.records_dropped);
+}
+
+/// Parse a `--flag` timestamp or exit with a usage hint.
+fn parse_cli_time(flag: &str, value: &str) -> DateTime<Utc> {
+    match NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        Ok(dt) => dt.and_utc(),
+        Err(e) => {
+            eprintln!("Invalid --{} timestamp '{}': {}", flag, value, e);
+            eprintln!("Expected format: 2026-02-19T14:00:00");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The record cipher for `storage.encryption_key_file`, or exit.
+fn load_cipher(cfg: &Config) -> Option<RecordCipher> {
+    match RecordCipher::from_config(&cfg.storage) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_hold(action: HoldAction) {
+    let config_path = match &action {
+        HoldAction::Create { config, .. }
+        | HoldAction::List { config }
+        | HoldAction::Release { config, .. } => config.clone(),
+    };
+    let cfg = match Config::from_file(&config_path) {
+        Ok(c) => c,
+        Err(e) => {
+            error!(error = %e, "Failed to load config");
+            std::process::exit(1);
+        }
+    };
+    let vault = Vault::new(&cfg.storage.base_path, load_cipher(&cfg));
+
+    match action {
+        HoldAction::Create { camera, from, to, expires, reason, .. } => {
+            let req = HoldRequest {
+                camera_id: camera,
+                from: parse_cli_time("from", &from),
+                to: parse_cli_time("to", &to),
+                expires_at: expires.map(|e| parse_cli_time("expires", &e)),
+                reason,
+            };
+
+            let pool_bytes = cfg.storage.chunk_size_mb * 1024 * 1024;
+            let mut pool = match ChunkPool::open_volumes(
+                &cfg.storage.base_path,
+                &cfg.storage.volumes,
+                pool_bytes,
+                cfg.storage.max_pools,
+            ) {
+                Ok(mut p) => {
+                    p.set_verify_reads(cfg.storage.verify_checksums_on_read);
+                    p.set_encryption(load_cipher(&cfg));
+                    p
+                }
+                Err(e) => {
+                    eprintln!("Error opening pool: {e}");
+                    std::process::exit(1);
+                }
+            };
+            let mut index = SegmentIndex::new();
+            index.rebuild_from_scanned(pool.take_recovered());
+            let segments: Vec<SegmentMeta> = index
+                .segments_in_range(&req.camera_id, req.from, req.to)
+                .into_iter()
+                .cloned()
+                .collect();
+
+            match vault.create_hold(&req, &segments, &pool, Utc::now()) {
+                Ok(hold) => {
+                    println!(
+                        "Hold {} created: {} segments ({} KB) for camera '{}' → {}",
+                        hold.id,
+                        hold.segments.len(),
+                        hold.total_bytes() / 1024,
+                        hold.camera_id,
+                        vault.dir().display(),
+                    );
+                }
+                Err(e) => {
+                    eprintln!("Hold failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        HoldAction::List { .. } => {
+            if let Err(e) = vault.purge_expired(Utc::now()) {
+                eprintln!("Failed to release expired holds: {e}");
+            }
+            let holds = match vault.list() {
+                Ok(h) => h,
+                Err(e) => {
+                    eprintln!("Error reading vault: {e}");
+                    std::process::exit(1);
+                }
+            };
+            if holds.is_empty() {
+                println!("No holds");
+                return;
+            }
+            println!(
+                "{:<6} {:<16} {:<20} {:<20} {:<20} {:<10} Reason",
+                "ID", "Camera", "From", "To", "Expires", "Segments"
+            );
+            println!("{}", "-".repeat(104));
+            for hold in &holds {
+                println!(
+                    "{:<6} {:<16} {:<20} {:<20} {:<20} {:<10} {}",
+                    hold.id,
+                    hold.camera_id,
+                    hold.from.format("%Y-%m-%d %H:%M:%S"),
+                    hold.to.format("%Y-%m-%d %H:%M:%S"),
+                    hold.expires_at
+                        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_else(|| "never".into()),
+                    hold.segments.len(),
+                    hold.reason,
+                );
+            }
+            println!("\nTotal: {} holds", holds.len());
+        }
+        HoldAction::Release { id, .. } => match vault.release(id) {
+            Ok(true) => println!("Hold {id} released"),
+            Ok(false) => {
+                eprintln!("Hold {id} not found");
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Release failed: {e}");
+                std::process::exit(1);
+            }
+        },
+    }
+}
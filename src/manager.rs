@@ -18,24 +18,42 @@ use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tracing::{info, warn};
 
-use crate::config::{CameraConfig, Config};
+use crate::config::{validate_cameras, CameraConfig, Config};
 use crate::error::{NvrError, Result};
 use crate::ingestion::CameraWorker;
-use crate::storage::chunk_pool::{ChunkPool, PoolReadCounters};
-use crate::storage::global_writer::{self, SharedIndex, WriteRequest};
+use crate::overload::{OverloadMonitor, SharedOverload};
+use crate::storage::chunk_pool::{ChunkPool, PoolReadCounters, Stream};
+use crate::storage::archive::{Archive, SharedArchive};
+use crate::storage::crypto::RecordCipher;
+use crate::storage::global_writer::{self, SharedIndex, WriteRequest, WriterHooks};
+use crate::storage::migrate::{self, PoolLayout};
+use crate::storage::replica::{self, Replica, SharedReplica};
+use crate::storage::retention::{RetentionPolicy, SharedRetention};
+use crate::storage::snapshot::snapshot_path;
+use crate::storage::vault::Vault;
 
 /// Top-level manager.
 pub struct RecordingManager {
     /// Per-camera worker handles, keyed by camera ID.
     workers: HashMap<String, WorkerEntry>,
-    /// Global writer task handle.
-    writer_handle: JoinHandle<()>,
+    /// Global writer thread handle.
+    writer_handle: std::thread::JoinHandle<()>,
+    /// Replica ring, if one is configured.
+    pub replica: Option<SharedReplica>,
+    /// Replica writer thread handle.
+    replica_handle: Option<std::thread::JoinHandle<()>>,
+    /// Cold archive, if one is configured.
+    pub archive: Option<SharedArchive>,
     /// Shared index for status / listing.
     pub index: SharedIndex,
     /// Shared pool reader counters for safe reads.
     pub read_counters: Arc<PoolReadCounters>,
     /// Global shared pool reference.
     pub pool: Arc<RwLock<ChunkPool>>,
+    /// Per-camera retention guarantees, shared with the writer.
+    retention: SharedRetention,
+    /// Spill levels and drop counters of the camera workers.
+    pub overload: SharedOverload,
     /// Channel sender — cloned to each new camera worker.
     writer_tx: mpsc::Sender<WriteRequest>,
     /// Segment duration used when spawning new workers.
@@ -47,7 +65,33 @@ pub struct RecordingManager {
 
 struct WorkerEntry {
     config: CameraConfig,
-    handle: JoinHandle<()>,
+    /// The main stream's worker, then the substream's if there is one.
+    handles: Vec<JoinHandle<()>>,
+}
+
+/// Spawn a camera's ingestion workers: one for its main stream and, if it
+/// has a `substream_url`, one for the substream (video only, fragments in
+/// `<tmp_dir>/sub`).
+fn spawn_workers(
+    cam_cfg: &CameraConfig,
+    writer_tx: &mpsc::Sender<WriteRequest>,
+    overload: &SharedOverload,
+    segment_duration: Duration,
+    tmp_dir: std::path::PathBuf,
+) -> Vec<JoinHandle<()>> {
+    let worker = CameraWorker::new(cam_cfg.id.clone(), writer_tx.clone(), overload.clone());
+    let mut handles = vec![worker.spawn(cam_cfg.clone(), segment_duration, tmp_dir.clone())];
+    if let Some(url) = &cam_cfg.substream_url {
+        let sub_cfg = CameraConfig {
+            url: url.clone(),
+            record_audio: false,
+            ..cam_cfg.clone()
+        };
+        let worker = CameraWorker::new(cam_cfg.id.clone(), writer_tx.clone(), overload.clone())
+            .with_stream(Stream::Sub);
+        handles.push(worker.spawn(sub_cfg, segment_duration, tmp_dir.join("sub")));
+    }
+    handles
 }
 
 impl RecordingManager {
@@ -68,44 +112,137 @@ impl RecordingManager {
         std::fs::create_dir_all(&segment_tmp_dir)
             .map_err(|e| NvrError::Storage(format!("Cannot create segment temp dir: {e}")))?;
 
+        // Holds interrupted by a crash are incomplete copies; expired ones
+        // are released here as well as whenever holds are listed. Neither
+        // reads a copy, so no key is needed.
+        let vault = Vault::new(base, None);
+        vault.remove_incomplete()?;
+        vault.purge_expired(chrono::Utc::now())?;
+
         let pool_bytes = config.storage.chunk_size_mb * 1024 * 1024;
         let segment_dur = Duration::from_secs(config.storage.segment_duration_secs);
 
+        // Finish any interrupted migration, then refuse pools on disk that
+        // don't match the configured layout rather than misread them.
+        // Migrating can drop footage and needs free space, so it is left to
+        // the operator (`oasis migrate-pools`). Skipped while a volume is
+        // missing: its pool files would look like lost slots.
+        let volumes = &config.storage.volumes;
+        migrate::recover(base, volumes)?;
+        if volumes.iter().all(|v| v.is_dir()) {
+            let layout = PoolLayout {
+                pool_capacity: pool_bytes,
+                pool_count: config.storage.max_pools,
+            };
+            if migrate::needs_migration(base, volumes, layout)? {
+                return Err(NvrError::Config(
+                    "Pool files on disk don't match chunk_size_mb / max_pools / volumes; \
+                     run `oasis migrate-pools` (see --dry-run) or restore the previous settings"
+                        .into(),
+                ));
+            }
+        } else {
+            warn!("Storage volume missing, pool layout check skipped");
+        }
+
+        // A snapshot left behind while snapshots were enabled would go stale
+        // without being refreshed; drop it rather than trust it later.
+        if !config.storage.index_snapshot {
+            let _ = std::fs::remove_file(snapshot_path(base));
+        }
+
         // Open the global chunk pool.
-        let pool = ChunkPool::open(base, pool_bytes, config.storage.max_pools)?;
+        let mut pool = ChunkPool::open_volumes(
+            base,
+            &config.storage.volumes,
+            pool_bytes,
+            config.storage.max_pools,
+        )?;
+        pool.set_verify_reads(config.storage.verify_checksums_on_read);
+        pool.set_save_snapshots(config.storage.index_snapshot);
+        pool.set_durability(config.storage.durability.clone());
+        let cipher = RecordCipher::from_config(&config.storage)?;
+        pool.set_encryption(cipher.clone());
         let read_counters = pool.read_counters.clone();
         let shared_pool = Arc::new(RwLock::new(pool));
 
+        // Open the replica ring and its writer before the global writer
+        // starts handing it segments.
+        let (replica, replicator, replica_handle) = match &config.storage.replica {
+            Some(rc) => {
+                let replica = Arc::new(Replica::open(
+                    rc,
+                    config.storage.durability.clone(),
+                    cipher.clone(),
+                )?);
+                let (replicator, handle) = replica::spawn_replicator(replica.clone(), rc.queue_size);
+                info!(
+                    path = ?rc.base_path,
+                    pools = rc.max_pools,
+                    pool_size_mb = rc.chunk_size_mb,
+                    "Replica writer started"
+                );
+                (Some(replica), Some(replicator), Some(handle))
+            }
+            None => (None, None, None),
+        };
+
+        let archive = match &config.storage.archive {
+            Some(ac) => Some(Arc::new(Archive::open(ac, cipher)?)),
+            None => None,
+        };
+
         // Spawn the single global writer.
-        let (writer_tx, index, writer_handle) =
-            global_writer::spawn_writer(shared_pool.clone(), config.storage.writer_queue_size);
+        let retention = Arc::new(RwLock::new(RetentionPolicy::from_cameras(&config.cameras)));
+        let (writer_tx, index, writer_handle) = global_writer::spawn_writer_with(
+            shared_pool.clone(),
+            retention.clone(),
+            config.storage.writer_queue_size,
+            WriterHooks {
+                replicator,
+                archive: archive.clone(),
+            },
+        );
 
         info!(
             pools = config.storage.max_pools,
             pool_size_mb = config.storage.chunk_size_mb,
             queue = config.storage.writer_queue_size,
+            durability = ?config.storage.durability.mode,
+            direct_io = config.storage.durability.direct_io,
             "Global chunk writer started"
         );
 
         // Spawn one CameraWorker per camera, all sharing writer_tx.
+        let overload = Arc::new(OverloadMonitor::new(&config.storage.overload));
         let mut workers = HashMap::new();
         for cam_cfg in &config.cameras {
-            let worker = CameraWorker::new(cam_cfg.id.clone(), writer_tx.clone());
+            overload.register(&cam_cfg.id, cam_cfg.priority);
             let cam_tmp_dir = segment_tmp_dir.join(&cam_cfg.id);
-            let handle = worker.spawn(cam_cfg.clone(), segment_dur, cam_tmp_dir);
-            info!(camera = cam_cfg.id, name = cam_cfg.name, "Camera registered");
+            let handles = spawn_workers(cam_cfg, &writer_tx, &overload, segment_dur, cam_tmp_dir);
+            info!(
+                camera = cam_cfg.id,
+                name = cam_cfg.name,
+                substream = cam_cfg.substream_url.is_some(),
+                "Camera registered"
+            );
             workers.insert(cam_cfg.id.clone(), WorkerEntry {
                 config: cam_cfg.clone(),
-                handle,
+                handles,
             });
         }
 
         Ok(RecordingManager {
             workers,
             writer_handle,
+            replica,
+            replica_handle,
+            archive,
             index,
             read_counters,
             pool: shared_pool,
+            retention,
+            overload,
             writer_tx,
             segment_duration: segment_dur,
             segment_tmp_dir,
@@ -119,23 +256,40 @@ impl RecordingManager {
                 "Camera '{}' already exists", cam_cfg.id
             )));
         }
+        let mut all: Vec<CameraConfig> = self.workers.values().map(|e| e.config.clone()).collect();
+        all.push(cam_cfg.clone());
+        validate_cameras(&all)?;
+        self.retention.write().set_camera(&cam_cfg);
+        self.overload.register(&cam_cfg.id, cam_cfg.priority);
 
-        let worker = CameraWorker::new(cam_cfg.id.clone(), self.writer_tx.clone());
         let cam_tmp_dir = self.segment_tmp_dir.join(&cam_cfg.id);
-        let handle = worker.spawn(cam_cfg.clone(), self.segment_duration, cam_tmp_dir);
-        info!(camera = cam_cfg.id, name = cam_cfg.name, "Camera added (hot)");
+        let handles = spawn_workers(
+            &cam_cfg,
+            &self.writer_tx,
+            &self.overload,
+            self.segment_duration,
+            cam_tmp_dir,
+        );
+        info!(
+            camera = cam_cfg.id,
+            name = cam_cfg.name,
+            substream = cam_cfg.substream_url.is_some(),
+            "Camera added (hot)"
+        );
 
         self.workers.insert(cam_cfg.id.clone(), WorkerEntry {
             config: cam_cfg,
-            handle,
+            handles,
         });
         Ok(())
     }
 
-    /// Remove a camera at runtime. Aborts the worker task.
+    /// Remove a camera at runtime. Aborts its worker tasks.
     pub fn remove_camera(&mut self, camera_id: &str) -> bool {
         if let Some(entry) = self.workers.remove(camera_id) {
-            entry.handle.abort();
+            entry.handles.iter().for_each(JoinHandle::abort);
+            self.retention.write().remove_camera(camera_id);
+            self.overload.unregister(camera_id);
             info!(camera = camera_id, "Camera removed (hot)");
             true
         } else {
@@ -149,15 +303,47 @@ impl RecordingManager {
         self.workers.values().map(|e| &e.config).collect()
     }
 
+    /// Write the index snapshot now (if enabled), so the next startup
+    /// doesn't need to rescan the active pool.
+    pub fn save_index_snapshot(&self) {
+        global_writer::save_snapshot(&self.pool, &self.index);
+    }
+
     /// Gracefully abort all workers and the writer. Called on shutdown.
     pub fn shutdown(self) {
         info!("NVR shutting down…");
         for (id, entry) in self.workers {
-            entry.handle.abort();
+            entry.handles.iter().for_each(JoinHandle::abort);
             info!(camera = id, "Worker aborted");
         }
         drop(self.writer_tx);
-        self.writer_handle.abort();
+
+        // The writer exits once the aborted workers have dropped their
+        // senders and it has drained the queue. Only snapshot after that,
+        // or the snapshot could miss a record already in the pool.
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while !self.writer_handle.is_finished() && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        if !self.writer_handle.is_finished() {
+            warn!("Global writer still busy, skipping index snapshot");
+            return;
+        }
         info!("Global writer stopped");
+        global_writer::save_snapshot(&self.pool, &self.index);
+
+        // The writer dropped its replicator on exit; the replica finishes
+        // what is still queued.
+        if let Some(handle) = self.replica_handle {
+            let deadline = std::time::Instant::now() + Duration::from_secs(10);
+            while !handle.is_finished() && std::time::Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            if handle.is_finished() {
+                info!("Replica writer stopped");
+            } else {
+                warn!("Replica writer still busy, not waiting for it");
+            }
+        }
     }
 }
@@ -0,0 +1,264 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Writer overload policy.
+//!
+//! The global writer queue is bounded. When the disk stalls and the queue
+//! fills, camera workers must not block on it: a blocked worker stops
+//! draining its pipeline's segment channel, and once that fills too,
+//! `splitmuxsink`'s `format-location` callback blocks and freezes the
+//! GStreamer pipeline.
+//!
+//! Instead each worker keeps a [`CameraSpill`]: segments the queue can't
+//! take right now are parked in memory and handed over in order as soon as
+//! it has room again. A spill buffer holds at most `spill_buffer_mb`;
+//! beyond that segments are dropped according to the [`OverloadPolicy`]:
+//!
+//!   - `drop_oldest` — the camera's oldest spilled segments make room for
+//!     its new ones.
+//!   - `drop_by_priority` — as above, but in addition, while a camera is
+//!     spilling, every camera with a lower `priority` drops its segments
+//!     outright (spilled ones included), leaving the writer queue to the
+//!     more important cameras until the backlog clears.
+//!
+//! Spill levels and drop counters of every camera are kept in a shared
+//! [`OverloadMonitor`] and reported by `/api/status`. A camera with a
+//! substream has a spill buffer per stream; its spill level is their sum.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{error, info, warn};
+
+use crate::config::{OverloadConfig, OverloadPolicy};
+use crate::storage::chunk_pool::Stream;
+use crate::storage::global_writer::WriteRequest;
+
+/// Spill state and drop counters of one camera.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CameraLoad {
+    pub priority: u32,
+    /// Segments waiting in the spill buffers.
+    pub spilled_segments: usize,
+    pub spilled_bytes: u64,
+    /// Segments dropped since the recorder started.
+    pub dropped_segments: u64,
+    pub dropped_bytes: u64,
+    /// Spilled segments and bytes of each stream, by stream tag.
+    streams: [(usize, u64); 2],
+}
+
+/// Overload state of all cameras, shared by their workers and the API.
+#[derive(Debug)]
+pub struct OverloadMonitor {
+    policy: OverloadPolicy,
+    spill_cap: u64,
+    cameras: Mutex<HashMap<String, CameraLoad>>,
+}
+
+pub type SharedOverload = Arc<OverloadMonitor>;
+
+impl OverloadMonitor {
+    pub fn new(config: &OverloadConfig) -> Self {
+        OverloadMonitor {
+            policy: config.policy,
+            spill_cap: config.spill_buffer_mb * 1024 * 1024,
+            cameras: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Add a camera, or update its priority.
+    pub fn register(&self, camera_id: &str, priority: u32) {
+        self.cameras.lock().entry(camera_id.to_string()).or_default().priority = priority;
+    }
+
+    pub fn unregister(&self, camera_id: &str) {
+        self.cameras.lock().remove(camera_id);
+    }
+
+    /// Current state of every camera, sorted by ID.
+    pub fn cameras(&self) -> Vec<(String, CameraLoad)> {
+        let mut all: Vec<_> = self.cameras.lock().iter().map(|(id, l)| (id.clone(), *l)).collect();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        all
+    }
+
+    pub fn camera(&self, camera_id: &str) -> Option<CameraLoad> {
+        self.cameras.lock().get(camera_id).copied()
+    }
+
+    /// Whether `camera_id` has to give way: under `drop_by_priority`, while
+    /// a camera with a higher priority is spilling.
+    pub fn should_shed(&self, camera_id: &str) -> bool {
+        if self.policy != OverloadPolicy::DropByPriority {
+            return false;
+        }
+        let cameras = self.cameras.lock();
+        let Some(me) = cameras.get(camera_id) else {
+            return false;
+        };
+        cameras
+            .values()
+            .any(|c| c.priority > me.priority && c.spilled_segments > 0)
+    }
+
+    fn set_spilled(&self, camera_id: &str, stream: Stream, segments: usize, bytes: u64) {
+        if let Some(c) = self.cameras.lock().get_mut(camera_id) {
+            c.streams[stream.tag() as usize] = (segments, bytes);
+            c.spilled_segments = c.streams.iter().map(|s| s.0).sum();
+            c.spilled_bytes = c.streams.iter().map(|s| s.1).sum();
+        }
+    }
+
+    fn add_dropped(&self, camera_id: &str, segments: u64, bytes: u64) {
+        if let Some(c) = self.cameras.lock().get_mut(camera_id) {
+            c.dropped_segments += segments;
+            c.dropped_bytes += bytes;
+        }
+    }
+}
+
+/// What [`CameraSpill::submit`] did with a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Submitted {
+    /// Went straight into the writer queue.
+    Queued,
+    /// Parked in the spill buffer.
+    Spilled,
+    /// Dropped under the overload policy.
+    Dropped,
+    /// The writer is gone.
+    Closed,
+}
+
+/// One camera stream's path into the writer queue, with its spill buffer.
+pub struct CameraSpill {
+    camera_id: String,
+    stream: Stream,
+    monitor: SharedOverload,
+    queue: VecDeque<WriteRequest>,
+    bytes: u64,
+}
+
+impl CameraSpill {
+    pub fn new(camera_id: String, monitor: SharedOverload) -> Self {
+        CameraSpill {
+            camera_id,
+            stream: Stream::Main,
+            monitor,
+            queue: VecDeque::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Spill for `stream` instead of the main stream.
+    pub fn with_stream(mut self, stream: Stream) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Hand a segment to the writer without waiting: into the queue if it
+    /// has room and nothing is spilled ahead of it, else into the spill
+    /// buffer, dropping segments as the policy says.
+    pub fn submit(&mut self, tx: &mpsc::Sender<WriteRequest>, req: WriteRequest) -> Submitted {
+        if self.monitor.should_shed(&self.camera_id) {
+            let bytes = req.data.len() as u64;
+            self.shed();
+            self.monitor.add_dropped(&self.camera_id, 1, bytes);
+            warn!(camera = self.camera_id, bytes, "Writer overloaded, segment dropped for higher-priority cameras");
+            return Submitted::Dropped;
+        }
+
+        // Nothing may overtake segments already spilled.
+        let req = if self.queue.is_empty() {
+            match tx.try_send(req) {
+                Ok(()) => return Submitted::Queued,
+                Err(TrySendError::Closed(_)) => {
+                    error!(camera = self.camera_id, "Global writer channel closed, segment dropped");
+                    return Submitted::Closed;
+                }
+                Err(TrySendError::Full(req)) => {
+                    warn!(camera = self.camera_id, "Writer queue full, spilling segments to memory");
+                    req
+                }
+            }
+        } else {
+            req
+        };
+
+        let bytes = req.data.len() as u64;
+        self.queue.push_back(req);
+        self.bytes += bytes;
+        let mut dropped = (0u64, 0u64);
+        while self.bytes > self.monitor.spill_cap {
+            let Some(old) = self.queue.pop_front() else { break };
+            self.bytes -= old.data.len() as u64;
+            dropped.0 += 1;
+            dropped.1 += old.data.len() as u64;
+        }
+        self.publish();
+        if dropped.0 > 0 {
+            self.monitor.add_dropped(&self.camera_id, dropped.0, dropped.1);
+            warn!(
+                camera = self.camera_id,
+                segments = dropped.0,
+                bytes = dropped.1,
+                "Spill buffer full, oldest segments dropped"
+            );
+        }
+        if self.queue.is_empty() {
+            Submitted::Dropped
+        } else {
+            Submitted::Spilled
+        }
+    }
+
+    /// Wait for room in the writer queue and move the oldest spilled segment
+    /// into it. Returns `false` if the writer is gone (the spill buffer is
+    /// then discarded).
+    pub async fn drain_one(&mut self, tx: &mpsc::Sender<WriteRequest>) -> bool {
+        let Ok(permit) = tx.reserve().await else {
+            self.shed();
+            return false;
+        };
+        if self.monitor.should_shed(&self.camera_id) {
+            self.shed();
+            return true;
+        }
+        if let Some(req) = self.queue.pop_front() {
+            self.bytes -= req.data.len() as u64;
+            permit.send(req);
+            self.publish();
+            if self.queue.is_empty() {
+                info!(camera = self.camera_id, "Spill buffer drained");
+            }
+        }
+        true
+    }
+
+    /// Drop everything spilled, counting it as dropped.
+    fn shed(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+        let segments = self.queue.len() as u64;
+        self.monitor.add_dropped(&self.camera_id, segments, self.bytes);
+        warn!(camera = self.camera_id, segments, bytes = self.bytes, "Spilled segments dropped");
+        self.queue.clear();
+        self.bytes = 0;
+        self.publish();
+    }
+
+    fn publish(&self) {
+        self.monitor.set_spilled(&self.camera_id, self.stream, self.queue.len(), self.bytes);
+    }
+}
@@ -6,11 +6,15 @@
 //! Playback / export: retrieve recorded video for a camera in a time range.
 //!
 //! Reads the in-memory `SegmentIndex` (rebuilt from pool files on startup)
-//! to locate matching segments. Each stored segment is an independent,
+//! to locate matching segments, and the cold archive's index for footage
+//! already rotated out of the ring. Each stored segment is an independent,
 //! self-initializing fMP4 file (own `ftyp+moov+moof+mdat`), so unlike the
 //! old MPEG-TS format they can't be concatenated as raw bytes — exporting a
 //! range does a real demux + remux through a short-lived GStreamer pipeline
 //! instead.
+//!
+//! Thumbnails and snapshots are made the same way: [`keyframe_jpeg`] decodes
+//! a segment's first frame through a short-lived pipeline of its own.
 
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -19,13 +23,17 @@ use std::sync::{Arc, Mutex};
 use chrono::{DateTime, Utc};
 use gstreamer as gst;
 use gstreamer::prelude::*;
-use tracing::{error, info};
+use gstreamer_app as gst_app;
+use tracing::{error, info, warn};
 
+use crate::codec;
 use crate::error::{NvrError, Result};
+use crate::storage::archive::{self, Archive};
 use crate::storage::chunk_pool::ChunkPool;
 use crate::storage::index::SegmentIndex;
 
 /// Export recorded video for `camera_id` in the range `[from, to]` to `output_path`.
+/// Segments are taken from the ring and, if given, the archive.
 ///
 /// The output is one continuous, standalone MP4 playable directly with VLC,
 /// ffplay, or any MP4-aware player.
@@ -34,6 +42,7 @@ use crate::storage::index::SegmentIndex;
 pub fn export_range(
     pool: &ChunkPool,
     index: &SegmentIndex,
+    archive: Option<&Archive>,
     camera_id: &str,
     from: DateTime<Utc>,
     to: DateTime<Utc>,
@@ -41,7 +50,8 @@ pub fn export_range(
 ) -> Result<usize> {
     gst::init().map_err(|e| NvrError::GStreamer(format!("gst::init: {e}")))?;
 
-    let segments = index.segments_in_range(camera_id, from, to);
+    let archive_index = archive.map(|a| a.index());
+    let segments = archive::segments_in_range(index, archive_index.as_deref(), camera_id, from, to);
 
     if segments.is_empty() {
         return Err(NvrError::Storage(format!(
@@ -60,14 +70,30 @@ pub fn export_range(
     std::fs::create_dir_all(&tmp_dir)?;
 
     let mut seg_paths = Vec::with_capacity(segments.len());
+    let mut audio_segments = 0;
     for (i, seg) in segments.iter().enumerate() {
-        let data = pool.read_segment_data(&seg.location)?;
+        let data = archive::read_tiered(pool, archive, seg)?;
+        if codec::has_audio(&data) {
+            audio_segments += 1;
+        }
         let seg_path = tmp_dir.join(format!("seg_{i:05}.mp4"));
         std::fs::File::create(&seg_path)?.write_all(&data)?;
         seg_paths.push(seg_path);
     }
 
-    let result = remux_segments(&seg_paths, output_path);
+    // Audio is exported only if every segment has it: concat can't skip a
+    // stream that one of its inputs doesn't have.
+    let with_audio = audio_segments == segments.len();
+    if audio_segments > 0 && !with_audio {
+        warn!(
+            camera = camera_id,
+            audio_segments,
+            segments = segments.len(),
+            "Audio missing from part of the range, exporting video only"
+        );
+    }
+
+    let result = remux_segments(&seg_paths, with_audio, output_path);
 
     // Best-effort cleanup regardless of outcome.
     let _ = std::fs::remove_dir_all(&tmp_dir);
@@ -95,7 +121,11 @@ pub fn export_range(
 /// segments were recorded with, which isn't known until the first segment is
 /// demuxed — all segments in one export share a camera (and therefore a
 /// codec), so it's resolved once, lazily, from the first video pad seen.
-fn remux_segments(seg_paths: &[PathBuf], output_path: &Path) -> Result<()> {
+///
+/// With `with_audio`, the segments' audio tracks go through a second
+/// `concat` into the same `mp4mux`. Recorded audio is always AAC (see
+/// `camera.rs`), so that branch is built up front.
+fn remux_segments(seg_paths: &[PathBuf], with_audio: bool, output_path: &Path) -> Result<()> {
     let pipeline = gst::Pipeline::new();
 
     let make = |factory: &str| -> Result<gst::Element> {
@@ -120,6 +150,19 @@ fn remux_segments(seg_paths: &[PathBuf], output_path: &Path) -> Result<()> {
         .link(&filesink)
         .map_err(|e| NvrError::GStreamer(format!("link mp4mux->filesink: {e}")))?;
 
+    let audio_concat = if with_audio {
+        let audio_concat = make("concat")?;
+        let aacparse = make("aacparse")?;
+        pipeline
+            .add_many([&audio_concat, &aacparse])
+            .map_err(|e| NvrError::GStreamer(format!("add audio elements: {e}")))?;
+        gst::Element::link_many([&audio_concat, &aacparse, &mp4mux])
+            .map_err(|e| NvrError::GStreamer(format!("link audio concat->aacparse->mp4mux: {e}")))?;
+        Some(audio_concat)
+    } else {
+        None
+    };
+
     // Built once, the first time a video pad's caps tell us the codec.
     let parser: Arc<Mutex<Option<gst::Element>>> = Arc::new(Mutex::new(None));
 
@@ -143,12 +186,25 @@ fn remux_segments(seg_paths: &[PathBuf], output_path: &Path) -> Result<()> {
         let concat_sink = concat
             .request_pad_simple("sink_%u")
             .ok_or_else(|| NvrError::GStreamer("concat: no sink pad available".into()))?;
+        let audio_concat_sink = audio_concat
+            .as_ref()
+            .map(|c| {
+                c.request_pad_simple("sink_%u")
+                    .ok_or_else(|| NvrError::GStreamer("audio concat: no sink pad available".into()))
+            })
+            .transpose()?;
 
         let pipeline_for_pad = pipeline.clone();
         let concat_for_pad = concat.clone();
         let mp4mux_for_pad = mp4mux.clone();
         let parser_for_pad = parser.clone();
         qtdemux.connect_pad_added(move |_demux, src_pad| {
+            if src_pad.name().starts_with("audio") {
+                if let Some(audio_concat_sink) = &audio_concat_sink {
+                    let _ = src_pad.link(audio_concat_sink);
+                }
+                return;
+            }
             if !src_pad.name().starts_with("video") {
                 return;
             }
@@ -169,6 +225,7 @@ fn remux_segments(seg_paths: &[PathBuf], output_path: &Path) -> Result<()> {
             };
             let parse_factory = match s.name().as_str() {
                 "video/x-h264" => "h264parse",
+                "video/x-h265" => "h265parse",
                 "video/x-av1" => "av1parse",
                 other => {
                     error!(codec = other, "Unsupported recorded video codec, cannot export");
@@ -236,3 +293,160 @@ fn remux_segments(seg_paths: &[PathBuf], output_path: &Path) -> Result<()> {
     let _ = pipeline.set_state(gst::State::Null);
     result
 }
+
+/// Decode the first frame of a stored segment (always a keyframe, since
+/// segments are cut on keyframes) and encode it as a JPEG: `width` pixels
+/// wide keeping the aspect ratio, or at the recorded size if `None`.
+///
+/// Decoding is done in software only (`avdec_h264`, `avdec_h265`; `dav1ddec`
+/// or `av1dec`), so thumbnails don't compete with anything else for a
+/// hardware decoder.
+pub fn keyframe_jpeg(segment: &[u8], width: Option<u32>) -> Result<Vec<u8>> {
+    gst::init().map_err(|e| NvrError::GStreamer(format!("gst::init: {e}")))?;
+
+    let pipeline = gst::Pipeline::new();
+
+    let make = |factory: &str| -> Result<gst::Element> {
+        gst::ElementFactory::make(factory)
+            .build()
+            .map_err(|e| NvrError::GStreamer(format!("create {factory}: {e}")))
+    };
+
+    let appsrc = gst_app::AppSrc::builder()
+        .caps(&gst::Caps::builder("video/quicktime").build())
+        .format(gst::Format::Bytes)
+        .build();
+    let qtdemux = make("qtdemux")?;
+    let convert = make("videoconvert")?;
+    let scale = make("videoscale")?;
+    let mut caps = gst::Caps::builder("video/x-raw").field("pixel-aspect-ratio", gst::Fraction::new(1, 1));
+    if let Some(width) = width {
+        caps = caps.field("width", width as i32);
+    }
+    let caps = caps.build();
+    let capsfilter = gst::ElementFactory::make("capsfilter")
+        .property("caps", caps)
+        .build()
+        .map_err(|e| NvrError::GStreamer(format!("create capsfilter: {e}")))?;
+    let jpegenc = make("jpegenc")?;
+    let appsink = gst_app::AppSink::builder().sync(false).max_buffers(1).build();
+
+    pipeline
+        .add_many([
+            appsrc.upcast_ref::<gst::Element>(),
+            &qtdemux,
+            &convert,
+            &scale,
+            &capsfilter,
+            &jpegenc,
+            appsink.upcast_ref(),
+        ])
+        .map_err(|e| NvrError::GStreamer(format!("add elements: {e}")))?;
+    appsrc
+        .link(&qtdemux)
+        .map_err(|e| NvrError::GStreamer(format!("link appsrc->qtdemux: {e}")))?;
+    gst::Element::link_many([&convert, &scale, &capsfilter, &jpegenc, appsink.upcast_ref::<gst::Element>()])
+        .map_err(|e| NvrError::GStreamer(format!("link thumbnail chain: {e}")))?;
+
+    // As in the export remux, the parser/decoder pair depends on the codec
+    // the segment was recorded with, known once qtdemux exposes its pad.
+    let pipeline_for_pad = pipeline.clone();
+    let convert_for_pad = convert.clone();
+    qtdemux.connect_pad_added(move |_demux, src_pad| {
+        if !src_pad.name().starts_with("video") {
+            return;
+        }
+        let Some(caps) = src_pad.current_caps() else {
+            error!("Thumbnail video pad has no negotiated caps, cannot pick a decoder");
+            return;
+        };
+        let Some(s) = caps.structure(0) else {
+            error!("Thumbnail video pad caps have no structure, cannot pick a decoder");
+            return;
+        };
+        // Software decoders only, in order of preference.
+        let (parse_factory, decoders): (&str, &[&str]) = match s.name().as_str() {
+            "video/x-h264" => ("h264parse", &["avdec_h264"]),
+            "video/x-h265" => ("h265parse", &["avdec_h265"]),
+            "video/x-av1" => ("av1parse", &["dav1ddec", "av1dec"]),
+            other => {
+                error!(codec = other, "Unsupported recorded video codec, cannot make thumbnail");
+                return;
+            }
+        };
+        let Some(decode_factory) = decoders
+            .iter()
+            .copied()
+            .find(|f| gst::ElementFactory::find(f).is_some())
+        else {
+            error!(decoders = ?decoders, "No software decoder installed, cannot make thumbnail");
+            return;
+        };
+
+        let mut chain = Vec::with_capacity(2);
+        for factory in [parse_factory, decode_factory] {
+            match gst::ElementFactory::make(factory).build() {
+                Ok(el) => chain.push(el),
+                Err(e) => {
+                    error!(factory, error = %e, "Failed to create element");
+                    return;
+                }
+            }
+        }
+        for el in &chain {
+            if let Err(e) = pipeline_for_pad.add(el) {
+                error!(error = %e, "Failed to add element to pipeline");
+                return;
+            }
+            if let Err(e) = el.sync_state_with_parent() {
+                error!(error = %e, "Failed to sync element state with pipeline");
+                return;
+            }
+        }
+        let Some(parse_sink) = chain[0].static_pad("sink") else {
+            error!("parser has no sink pad");
+            return;
+        };
+        if let Err(e) = src_pad.link(&parse_sink) {
+            error!(error = ?e, "Failed to link qtdemux->parser");
+            return;
+        }
+        if let Err(e) = gst::Element::link_many([&chain[0], &chain[1], &convert_for_pad]) {
+            error!(error = %e, "Failed to link parser->decoder->videoconvert");
+        }
+    });
+
+    pipeline
+        .set_state(gst::State::Playing)
+        .map_err(|e| NvrError::GStreamer(format!("set_state Playing (thumbnail): {e}")))?;
+
+    let result = (|| {
+        appsrc
+            .push_buffer(gst::Buffer::from_slice(segment.to_vec()))
+            .map_err(|e| NvrError::GStreamer(format!("push segment to thumbnail pipeline: {e}")))?;
+        let _ = appsrc.end_of_stream();
+
+        let Some(sample) = appsink.try_pull_sample(gst::ClockTime::from_seconds(10)) else {
+            // Decode failed (or produced nothing): report the pipeline error.
+            let reason = pipeline
+                .bus()
+                .and_then(|bus| bus.pop_filtered(&[gst::MessageType::Error]))
+                .and_then(|msg| match msg.view() {
+                    gst::MessageView::Error(err) => Some(err.error().to_string()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| "no frame decoded".into());
+            return Err(NvrError::GStreamer(format!("thumbnail pipeline: {reason}")));
+        };
+        let buffer = sample
+            .buffer()
+            .ok_or_else(|| NvrError::GStreamer("thumbnail sample has no buffer".into()))?;
+        let map = buffer
+            .map_readable()
+            .map_err(|e| NvrError::GStreamer(format!("map thumbnail buffer: {e}")))?;
+        Ok(map.as_slice().to_vec())
+    })();
+
+    let _ = pipeline.set_state(gst::State::Null);
+    result
+}
@@ -0,0 +1,605 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Cold archive — long-term copies of segments rotated out of the ring.
+//!
+//! With `[storage.archive]` configured, the global writer copies a pool's
+//! segments into the archive right before a rotation evicts them from the
+//! index. Only the listed cameras' main streams are archived (all cameras,
+//! if none are listed), and with `min_interval_secs` at most one segment
+//! per camera per interval, so the archive can keep a thinned-out record of
+//! many months in little space. Segments are archived whole: they are self-contained fMP4 files,
+//! so every archived segment stays playable on its own.
+//!
+//! One append-only file per camera and (UTC) day:
+//!
+//! ```text
+//! archive/
+//!   cam1/2026-10-15.arc
+//!   cam1/2026-10-16.arc
+//!   door/2026-10-16.arc
+//! ```
+//!
+//! Each file is a sequence of entries:
+//!
+//! ```text
+//! [magic      : 4 bytes  "NARC"]
+//! [version    : u8]
+//! [flags      : u8]             bit 0 = payload encrypted
+//! [reserved   : 2 bytes]
+//! [segment_id : u64     (LE)]   ID the segment had in the ring
+//! [start_us   : i64     (LE)]   µs since UNIX epoch
+//! [end_us     : i64     (LE)]
+//! [data_len   : u64     (LE)]
+//! [checksum   : u32     (LE)]   CRC-32 of header + data, this field zeroed
+//! [reserved   : 4 bytes]
+//! [data       : data_len bytes]
+//! ```
+//!
+//! The archive keeps its own [`ArchiveIndex`] in memory, rebuilt on startup
+//! by walking the entry headers of every day file; an entry torn by a crash
+//! at the end of a file is cut off, and a damaged one anywhere else is
+//! skipped without touching the file. Checksums are verified on read.
+//! [`segments_in_range`] merges it with the ring's [`SegmentIndex`], so
+//! listing, HLS VOD and export see one timeline across both tiers.
+//!
+//! With an encryption key configured, payloads are sealed like pool records
+//! (see [`crypto`](crate::storage::crypto)). Day files older than
+//! `max_age_days` are deleted.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use chrono::{DateTime, NaiveDate, Utc};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+use tracing::{info, warn};
+
+use crate::config::ArchiveConfig;
+use crate::error::{NvrError, Result};
+use crate::storage::chunk_pool::{ChunkPool, Stream};
+use crate::storage::crypto::{RecordCipher, SEAL_OVERHEAD};
+use crate::storage::index::{SegmentIndex, SegmentMeta};
+
+const MAGIC: &[u8; 4] = b"NARC";
+const VERSION: u8 = 1;
+const FLAG_ENCRYPTED: u8 = 1;
+pub const ENTRY_HEADER_SIZE: u64 = 48;
+const CHECKSUM_OFFSET: usize = 40;
+const DAY_FILE_EXT: &str = "arc";
+
+/// One archived segment.
+#[derive(Debug, Clone)]
+pub struct ArchivedSegment {
+    pub segment_id: u64,
+    pub camera_id: String,
+    pub start_ts: DateTime<Utc>,
+    pub end_ts: DateTime<Utc>,
+    /// Day file holding the entry.
+    pub day: NaiveDate,
+    /// Offset of the entry header in the day file.
+    pub offset: u64,
+    /// Stored payload length (sealed, if encrypted).
+    pub data_len: u64,
+}
+
+/// In-memory index of the archive, ordered like [`SegmentIndex`].
+#[derive(Default)]
+pub struct ArchiveIndex {
+    entries: BTreeMap<(String, DateTime<Utc>, u64), ArchivedSegment>,
+}
+
+impl ArchiveIndex {
+    fn insert(&mut self, seg: ArchivedSegment) {
+        let key = (seg.camera_id.clone(), seg.start_ts, seg.segment_id);
+        self.entries.insert(key, seg);
+    }
+
+    fn remove_day(&mut self, camera_id: &str, day: NaiveDate) {
+        self.entries.retain(|_, s| !(s.camera_id == camera_id && s.day == day));
+    }
+
+    fn camera<'a>(&'a self, camera_id: &str) -> impl Iterator<Item = &'a ArchivedSegment> + 'a {
+        let camera_id = camera_id.to_string();
+        let first = (camera_id.clone(), DateTime::<Utc>::MIN_UTC, 0);
+        self.entries
+            .range(first..)
+            .map(|(_, s)| s)
+            .take_while(move |s| s.camera_id == camera_id)
+    }
+
+    /// All archived segments of a camera in chronological order.
+    pub fn segments_for_camera(&self, camera_id: &str) -> Vec<&ArchivedSegment> {
+        self.camera(camera_id).collect()
+    }
+
+    /// Archived segments of `camera_id` overlapping `[from, to]`.
+    pub fn segments_in_range(
+        &self,
+        camera_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<&ArchivedSegment> {
+        self.camera(camera_id)
+            .filter(|s| s.start_ts < to && s.end_ts > from)
+            .collect()
+    }
+
+    pub fn get(&self, camera_id: &str, segment_id: u64) -> Option<&ArchivedSegment> {
+        self.camera(camera_id).find(|s| s.segment_id == segment_id)
+    }
+
+    /// The camera's most recent archived segment.
+    pub fn latest(&self, camera_id: &str) -> Option<&ArchivedSegment> {
+        self.camera(camera_id).last()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Stored bytes of all archived payloads.
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.values().map(|s| s.data_len).sum()
+    }
+}
+
+/// Handle to the archive directory and its index.
+pub struct Archive {
+    dir: PathBuf,
+    cameras: Vec<String>,
+    min_interval: chrono::Duration,
+    max_age_days: u32,
+    cipher: Option<RecordCipher>,
+    index: RwLock<ArchiveIndex>,
+    /// Serialises appends and purges.
+    write_lock: Mutex<()>,
+}
+
+pub type SharedArchive = Arc<Archive>;
+
+impl Archive {
+    /// Open (or create) the archive and index its day files.
+    pub fn open(config: &ArchiveConfig, cipher: Option<RecordCipher>) -> Result<Self> {
+        fs::create_dir_all(&config.path)
+            .map_err(|e| NvrError::Storage(format!("Cannot create archive path: {e}")))?;
+        let archive = Archive {
+            dir: config.path.clone(),
+            cameras: config.cameras.clone(),
+            min_interval: chrono::Duration::seconds(config.min_interval_secs as i64),
+            max_age_days: config.max_age_days,
+            cipher,
+            index: RwLock::new(ArchiveIndex::default()),
+            write_lock: Mutex::new(()),
+        };
+
+        let mut index = ArchiveIndex::default();
+        for (camera_id, day, path) in archive.day_files()? {
+            for seg in scan_day_file(&path, &camera_id, day)? {
+                index.insert(seg);
+            }
+        }
+        info!(path = ?archive.dir, segments = index.len(), "Archive opened");
+        *archive.index.write() = index;
+        Ok(archive)
+    }
+
+    pub fn index(&self) -> RwLockReadGuard<'_, ArchiveIndex> {
+        self.index.read()
+    }
+
+    /// Whether `seg` is to be archived: it is main stream, its camera is
+    /// selected, it isn't archived already, and it is at least `min_interval_secs` after the
+    /// camera's last archived segment.
+    pub fn wants(&self, seg: &SegmentMeta) -> bool {
+        if seg.location.stream != Stream::Main {
+            return false;
+        }
+        if !self.cameras.is_empty() && !self.cameras.contains(&seg.camera_id) {
+            return false;
+        }
+        let index = self.index.read();
+        if index.camera(&seg.camera_id).any(|s| s.start_ts == seg.start_ts) {
+            return false;
+        }
+        match index.latest(&seg.camera_id) {
+            Some(last) if self.min_interval > chrono::Duration::zero() => {
+                seg.start_ts >= last.start_ts + self.min_interval
+            }
+            _ => true,
+        }
+    }
+
+    /// Append a segment to its camera's day file and index it.
+    pub fn append(&self, seg: &SegmentMeta, data: &[u8]) -> Result<()> {
+        let _write = self.write_lock.lock();
+        let day = seg.start_ts.date_naive();
+        let path = self.day_path(&seg.camera_id, day);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let (flags, data_len) = match &self.cipher {
+            Some(_) => (FLAG_ENCRYPTED, data.len() as u64 + SEAL_OVERHEAD),
+            None => (0, data.len() as u64),
+        };
+        let mut entry = encode_header(flags, seg, data_len);
+        match &self.cipher {
+            Some(cipher) => {
+                let sealed = cipher.seal(&entry, data)?;
+                entry.extend_from_slice(&sealed);
+            }
+            None => entry.extend_from_slice(data),
+        }
+        let checksum = crc32fast::hash(&entry);
+        LittleEndian::write_u32(&mut entry[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4], checksum);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| NvrError::Storage(format!("open {path:?}: {e}")))?;
+        let offset = file.metadata()?.len();
+        file.write_all(&entry)?;
+        file.sync_data()?;
+
+        self.index.write().insert(ArchivedSegment {
+            segment_id: seg.segment_id,
+            camera_id: seg.camera_id.clone(),
+            start_ts: seg.start_ts,
+            end_ts: seg.end_ts,
+            day,
+            offset,
+            data_len,
+        });
+        Ok(())
+    }
+
+    /// Payload of an archived segment.
+    pub fn read_segment(&self, seg: &ArchivedSegment) -> Result<Vec<u8>> {
+        let path = self.day_path(&seg.camera_id, seg.day);
+        let mut file = File::open(&path)
+            .map_err(|e| NvrError::Storage(format!("open {path:?}: {e}")))?;
+        file.seek(SeekFrom::Start(seg.offset))?;
+        let mut entry = vec![0u8; (ENTRY_HEADER_SIZE + seg.data_len) as usize];
+        file.read_exact(&mut entry)?;
+        if &entry[0..4] != MAGIC {
+            return Err(NvrError::Storage(format!(
+                "No archive entry at {path:?} offset {}",
+                seg.offset
+            )));
+        }
+        let stored = LittleEndian::read_u32(&entry[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4]);
+        entry[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].fill(0);
+        if crc32fast::hash(&entry) != stored {
+            return Err(NvrError::Storage(format!(
+                "Archived segment {} of '{}' failed its checksum",
+                seg.segment_id, seg.camera_id
+            )));
+        }
+        let (header, data) = entry.split_at(ENTRY_HEADER_SIZE as usize);
+        if header[5] & FLAG_ENCRYPTED == 0 {
+            return Ok(data.to_vec());
+        }
+        match &self.cipher {
+            Some(cipher) => cipher.open(header, data),
+            None => Err(NvrError::Storage(format!(
+                "Archived segment {} of '{}' is encrypted and no key is configured",
+                seg.segment_id, seg.camera_id
+            ))),
+        }
+    }
+
+    /// Payload of the archived segment with the given ring ID, if any.
+    pub fn read_by_id(&self, camera_id: &str, segment_id: u64) -> Result<Option<Vec<u8>>> {
+        let seg = self.index.read().get(camera_id, segment_id).cloned();
+        seg.map(|s| self.read_segment(&s)).transpose()
+    }
+
+    /// Delete day files older than `max_age_days` (if set). Returns how
+    /// many were deleted.
+    pub fn purge_expired(&self, now: DateTime<Utc>) -> Result<usize> {
+        if self.max_age_days == 0 {
+            return Ok(0);
+        }
+        let _write = self.write_lock.lock();
+        let cutoff = now.date_naive() - chrono::Duration::days(self.max_age_days as i64);
+        let mut purged = 0;
+        for (camera_id, day, path) in self.day_files()? {
+            if day < cutoff {
+                // Index first, so nothing is served from a file being deleted.
+                self.index.write().remove_day(&camera_id, day);
+                fs::remove_file(&path)?;
+                info!(camera = camera_id, %day, "Archived day expired");
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    fn day_path(&self, camera_id: &str, day: NaiveDate) -> PathBuf {
+        self.dir
+            .join(camera_id)
+            .join(format!("{}.{DAY_FILE_EXT}", day.format("%Y-%m-%d")))
+    }
+
+    /// Every day file, as (camera, day, path).
+    fn day_files(&self) -> Result<Vec<(String, NaiveDate, PathBuf)>> {
+        let mut files = Vec::new();
+        for camera_dir in fs::read_dir(&self.dir)? {
+            let camera_dir = camera_dir?.path();
+            let Some(camera_id) = camera_dir.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if !camera_dir.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(&camera_dir)? {
+                let path = file?.path();
+                if path.extension().and_then(|e| e.to_str()) != Some(DAY_FILE_EXT) {
+                    continue;
+                }
+                let day = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+                if let Some(day) = day {
+                    files.push((camera_id.to_string(), day, path));
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Entry header with the checksum zeroed; the associated data of a sealed
+/// payload.
+fn encode_header(flags: u8, seg: &SegmentMeta, data_len: u64) -> Vec<u8> {
+    let mut h = Vec::with_capacity(ENTRY_HEADER_SIZE as usize);
+    h.extend_from_slice(MAGIC);
+    h.push(VERSION);
+    h.push(flags);
+    h.extend_from_slice(&[0u8; 2]);
+    h.write_u64::<LittleEndian>(seg.segment_id).unwrap();
+    h.write_i64::<LittleEndian>(seg.start_ts.timestamp_micros()).unwrap();
+    h.write_i64::<LittleEndian>(seg.end_ts.timestamp_micros()).unwrap();
+    h.write_u64::<LittleEndian>(data_len).unwrap();
+    h.extend_from_slice(&[0u8; 8]);
+    h
+}
+
+/// Fields of an entry header.
+struct EntryHeader {
+    segment_id: u64,
+    start_ts: DateTime<Utc>,
+    end_ts: DateTime<Utc>,
+    data_len: u64,
+}
+
+/// Parse an entry header; `None` if it isn't one.
+fn parse_header(header: &[u8; ENTRY_HEADER_SIZE as usize]) -> Option<EntryHeader> {
+    if &header[0..4] != MAGIC {
+        return None;
+    }
+    let timestamp = |at: usize| DateTime::from_timestamp_micros(LittleEndian::read_i64(&header[at..]));
+    Some(EntryHeader {
+        segment_id: LittleEndian::read_u64(&header[8..]),
+        start_ts: timestamp(16)?,
+        end_ts: timestamp(24)?,
+        data_len: LittleEndian::read_u64(&header[32..]),
+    })
+}
+
+/// Index the entries of one day file. An entry torn by a crash at the end
+/// of the file is cut off. A damaged entry anywhere else is skipped — the
+/// scan picks up again at the next intact entry — and the file is left as
+/// it is, so nothing after the damage is lost.
+fn scan_day_file(path: &Path, camera_id: &str, day: NaiveDate) -> Result<Vec<ArchivedSegment>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut segments = Vec::new();
+    let mut offset = 0u64;
+    let mut header = [0u8; ENTRY_HEADER_SIZE as usize];
+    while offset < len {
+        let entry = if offset + ENTRY_HEADER_SIZE <= len {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut header)?;
+            parse_header(&header).filter(|e| offset + ENTRY_HEADER_SIZE + e.data_len <= len)
+        } else {
+            None
+        };
+        if let Some(e) = entry {
+            segments.push(ArchivedSegment {
+                segment_id: e.segment_id,
+                camera_id: camera_id.to_string(),
+                start_ts: e.start_ts,
+                end_ts: e.end_ts,
+                day,
+                offset,
+                data_len: e.data_len,
+            });
+            offset += ENTRY_HEADER_SIZE + e.data_len;
+            continue;
+        }
+        match find_next_entry(&mut file, offset + 1, len)? {
+            Some(next) => {
+                warn!(file = ?path, offset, skipped = next - offset, "Damaged archive entry skipped");
+                offset = next;
+            }
+            None => break,
+        }
+    }
+    if offset < len {
+        if is_torn_tail(&mut file, offset, len)? {
+            warn!(file = ?path, offset, "Torn archive entry cut off");
+            OpenOptions::new().write(true).open(path)?.set_len(offset)?;
+        } else {
+            warn!(file = ?path, offset, "Unreadable data at end of archive file left in place");
+        }
+    }
+    Ok(segments)
+}
+
+/// Whether the bytes from `offset` to the end of the file are the start of
+/// an entry that was never finished: a header cut short, or a whole one
+/// whose payload runs past the end.
+fn is_torn_tail(file: &mut File, offset: u64, len: u64) -> Result<bool> {
+    let mut header = [0u8; ENTRY_HEADER_SIZE as usize];
+    let n = (len - offset).min(ENTRY_HEADER_SIZE) as usize;
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header[..n])?;
+    let m = n.min(MAGIC.len());
+    Ok(header[..m] == MAGIC[..m] && (n < ENTRY_HEADER_SIZE as usize || parse_header(&header).is_some()))
+}
+
+/// Offset of the first intact entry (header, length and checksum all
+/// check out) at or after `from`, if any.
+fn find_next_entry(file: &mut File, from: u64, len: u64) -> Result<Option<u64>> {
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut pos = from;
+    while pos + ENTRY_HEADER_SIZE <= len {
+        let n = (len - pos).min(buf.len() as u64) as usize;
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut buf[..n])?;
+        for i in 0..=n - MAGIC.len() {
+            if &buf[i..i + MAGIC.len()] == MAGIC && entry_is_intact(file, pos + i as u64, len)? {
+                return Ok(Some(pos + i as u64));
+            }
+        }
+        // Overlap, so a magic split across two reads is still found.
+        pos += (n - (MAGIC.len() - 1)) as u64;
+    }
+    Ok(None)
+}
+
+fn entry_is_intact(file: &mut File, offset: u64, len: u64) -> Result<bool> {
+    let mut header = [0u8; ENTRY_HEADER_SIZE as usize];
+    if offset + ENTRY_HEADER_SIZE > len {
+        return Ok(false);
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header)?;
+    let Some(e) = parse_header(&header) else {
+        return Ok(false);
+    };
+    if offset + ENTRY_HEADER_SIZE + e.data_len > len {
+        return Ok(false);
+    }
+    let mut entry = header.to_vec();
+    entry.resize((ENTRY_HEADER_SIZE + e.data_len) as usize, 0);
+    file.read_exact(&mut entry[ENTRY_HEADER_SIZE as usize..])?;
+    let stored = LittleEndian::read_u32(&entry[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4]);
+    entry[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].fill(0);
+    Ok(crc32fast::hash(&entry) == stored)
+}
+
+/// A segment in the ring or in the archive.
+#[derive(Debug, Clone, Copy)]
+pub enum TieredSegment<'a> {
+    Hot(&'a SegmentMeta),
+    Cold(&'a ArchivedSegment),
+}
+
+impl TieredSegment<'_> {
+    pub fn segment_id(&self) -> u64 {
+        match self {
+            TieredSegment::Hot(s) => s.segment_id,
+            TieredSegment::Cold(s) => s.segment_id,
+        }
+    }
+
+    pub fn camera_id(&self) -> &str {
+        match self {
+            TieredSegment::Hot(s) => &s.camera_id,
+            TieredSegment::Cold(s) => &s.camera_id,
+        }
+    }
+
+    pub fn start_ts(&self) -> DateTime<Utc> {
+        match self {
+            TieredSegment::Hot(s) => s.start_ts,
+            TieredSegment::Cold(s) => s.start_ts,
+        }
+    }
+
+    pub fn end_ts(&self) -> DateTime<Utc> {
+        match self {
+            TieredSegment::Hot(s) => s.end_ts,
+            TieredSegment::Cold(s) => s.end_ts,
+        }
+    }
+
+    /// Stored payload size.
+    pub fn data_len(&self) -> u64 {
+        match self {
+            TieredSegment::Hot(s) => s.location.data_len(),
+            TieredSegment::Cold(s) => s.data_len,
+        }
+    }
+
+    pub fn is_archived(&self) -> bool {
+        matches!(self, TieredSegment::Cold(_))
+    }
+}
+
+/// All segments of `camera_id` in the ring and the archive, by start time.
+pub fn segments_for_camera<'a>(
+    hot: &'a SegmentIndex,
+    cold: Option<&'a ArchiveIndex>,
+    camera_id: &str,
+) -> Vec<TieredSegment<'a>> {
+    merge(
+        hot.segments_for_camera(camera_id),
+        cold.map(|c| c.segments_for_camera(camera_id)).unwrap_or_default(),
+    )
+}
+
+/// Segments of `camera_id` overlapping `[from, to]` in the ring and the
+/// archive, by start time.
+pub fn segments_in_range<'a>(
+    hot: &'a SegmentIndex,
+    cold: Option<&'a ArchiveIndex>,
+    camera_id: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<TieredSegment<'a>> {
+    merge(
+        hot.segments_in_range(camera_id, from, to),
+        cold.map(|c| c.segments_in_range(camera_id, from, to)).unwrap_or_default(),
+    )
+}
+
+/// A segment both archived and still in the ring (relocated there by the
+/// retention policy) is listed once, from the ring.
+fn merge<'a>(hot: Vec<&'a SegmentMeta>, cold: Vec<&'a ArchivedSegment>) -> Vec<TieredSegment<'a>> {
+    let in_ring: HashSet<DateTime<Utc>> = hot.iter().map(|s| s.start_ts).collect();
+    let mut all: Vec<TieredSegment> = cold
+        .into_iter()
+        .filter(|s| !in_ring.contains(&s.start_ts))
+        .map(TieredSegment::Cold)
+        .chain(hot.into_iter().map(TieredSegment::Hot))
+        .collect();
+    all.sort_by_key(|s| s.start_ts());
+    all
+}
+
+/// Read a segment's payload from whichever tier holds it.
+pub fn read_tiered(pool: &ChunkPool, archive: Option<&Archive>, seg: &TieredSegment) -> Result<Vec<u8>> {
+    match (seg, archive) {
+        (TieredSegment::Hot(s), _) => pool.read_segment_data(&s.camera_id, &s.location),
+        (TieredSegment::Cold(s), Some(archive)) => archive.read_segment(s),
+        (TieredSegment::Cold(s), None) => Err(NvrError::Storage(format!(
+            "Segment {} of '{}' is archived and the archive is not open",
+            s.segment_id, s.camera_id
+        ))),
+    }
+}
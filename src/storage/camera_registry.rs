@@ -0,0 +1,158 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Camera registry — maps the numeric camera keys stored in record headers
+//! to full camera IDs.
+//!
+//! A record header only has room for a 16-byte camera ID prefix, so since
+//! record version 4 it also carries a `camera_key` assigned here on a
+//! camera's first write. The mapping lives in `base_path/cameras.registry`,
+//! an append-only file: an entry is synced to disk before any record using
+//! its key is written, and entries are never rewritten or reused, so every
+//! key in the pools resolves for as long as the file exists. If it is lost,
+//! records fall back to their 16-byte prefix.
+//!
+//! ## File Layout (all integers LE)
+//!
+//! ```text
+//! magic  : [u8;8] = b"NVRCAMS1"
+//! per entry:
+//!   key    : u32 (never 0)
+//!   id_len : u16, camera_id : [u8; id_len] (UTF-8)
+//!   crc32  : u32 over key + id_len + camera_id
+//! ```
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use tracing::{info, warn};
+
+use crate::error::{NvrError, Result};
+
+pub const REGISTRY_MAGIC: &[u8; 8] = b"NVRCAMS1";
+pub const REGISTRY_FILE: &str = "cameras.registry";
+
+/// In-memory copy of `cameras.registry`.
+#[derive(Debug, Default, Clone)]
+pub struct CameraRegistry {
+    path: PathBuf,
+    by_key: HashMap<u32, String>,
+    by_id: HashMap<String, u32>,
+    /// Length of the file up to the end of the last intact entry. Anything
+    /// after it (a torn append) is cut off before the next append.
+    valid_len: u64,
+}
+
+impl CameraRegistry {
+    /// Load the registry for `base_path`. A missing file is an empty
+    /// registry; nothing is created until the first [`key_for`](Self::key_for).
+    pub fn open(base_path: &Path) -> Result<Self> {
+        let mut reg = CameraRegistry {
+            path: base_path.join(REGISTRY_FILE),
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        match File::open(&reg.path) {
+            Ok(mut f) => f.read_to_end(&mut buf)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(reg),
+            Err(e) => return Err(e.into()),
+        };
+        if !REGISTRY_MAGIC.starts_with(&buf[..buf.len().min(REGISTRY_MAGIC.len())]) {
+            return Err(NvrError::Storage(format!(
+                "{:?} is not a camera registry",
+                reg.path
+            )));
+        }
+        if buf.len() < REGISTRY_MAGIC.len() {
+            return Ok(reg); // Torn first write; rewritten on the next append.
+        }
+
+        let mut pos = REGISTRY_MAGIC.len();
+        while let Some((key, id, len)) = decode_entry(&buf[pos..]) {
+            reg.by_id.insert(id.clone(), key);
+            reg.by_key.insert(key, id);
+            pos += len;
+        }
+        reg.valid_len = pos as u64;
+        if pos < buf.len() {
+            warn!(
+                path = ?reg.path,
+                offset = pos,
+                "Ignoring torn entry at end of camera registry"
+            );
+        }
+        Ok(reg)
+    }
+
+    /// Full camera ID for a key from a record header.
+    pub fn camera_id(&self, key: u32) -> Option<&str> {
+        self.by_key.get(&key).map(String::as_str)
+    }
+
+    /// Key of an already registered camera.
+    pub fn key(&self, camera_id: &str) -> Option<u32> {
+        self.by_id.get(camera_id).copied()
+    }
+
+    /// Key for `camera_id`, registering (and syncing) it first if needed.
+    pub fn key_for(&mut self, camera_id: &str) -> Result<u32> {
+        if let Some(key) = self.key(camera_id) {
+            return Ok(key);
+        }
+        let key = self.by_key.keys().max().map_or(1, |k| k + 1);
+        let id_len = u16::try_from(camera_id.len())
+            .map_err(|_| NvrError::Storage(format!("camera ID too long: {camera_id:?}")))?;
+
+        let mut entry = Vec::with_capacity(10 + camera_id.len());
+        entry.write_u32::<LittleEndian>(key)?;
+        entry.write_u16::<LittleEndian>(id_len)?;
+        entry.write_all(camera_id.as_bytes())?;
+        let crc = crc32fast::hash(&entry);
+        entry.write_u32::<LittleEndian>(crc)?;
+
+        let mut f = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.path)
+            .map_err(|e| NvrError::Storage(format!("open camera registry {:?}: {e}", self.path)))?;
+        if self.valid_len == 0 {
+            f.set_len(0)?;
+            f.write_all(REGISTRY_MAGIC)?;
+            self.valid_len = REGISTRY_MAGIC.len() as u64;
+        } else {
+            f.set_len(self.valid_len)?;
+        }
+        f.seek(SeekFrom::Start(self.valid_len))?;
+        f.write_all(&entry)?;
+        f.sync_all()?;
+
+        self.valid_len += entry.len() as u64;
+        self.by_id.insert(camera_id.to_string(), key);
+        self.by_key.insert(key, camera_id.to_string());
+        info!(camera = camera_id, key, "Camera registered in pool storage");
+        Ok(key)
+    }
+}
+
+/// Decode one entry from the front of `buf`: `(key, camera_id, entry_len)`,
+/// or `None` if it is incomplete or fails its checksum.
+fn decode_entry(buf: &[u8]) -> Option<(u32, String, usize)> {
+    let mut r = buf;
+    let key = r.read_u32::<LittleEndian>().ok()?;
+    let id_len = r.read_u16::<LittleEndian>().ok()? as usize;
+    let id = r.get(..id_len)?;
+    let crc = (&r[id_len..]).read_u32::<LittleEndian>().ok()?;
+    let len = 6 + id_len;
+    if key == 0 || crc32fast::hash(&buf[..len]) != crc {
+        return None;
+    }
+    let id = String::from_utf8(id.to_vec()).ok()?;
+    Some((key, id, len + 4))
+}
@@ -10,6 +10,18 @@
 //!
 //! All cameras write into the SAME sequential stream → zero seek overhead.
 //!
+//! With several storage volumes (one per disk), slot `i` lives on volume
+//! `i % volumes.len()`, so consecutive slots in ring order alternate disks
+//! and each disk still only ever sees one sequential writer. A volume that
+//! is missing at startup takes its slots offline: they are neither scanned
+//! nor written, and the ring skips them. Metadata (index snapshot, camera
+//! registry) always stays in `base_path`.
+//!
+//! Each slot also has a write [`SlotHealth`]. A failed write is retried on
+//! a fresh handle; a slot whose writes keep failing, or fail with `EIO`, is
+//! quarantined (`Failed`) and skipped by rotation like an offline one for
+//! as long as the pool stays open. A restart gives it another chance.
+//!
 //! ## File Layout
 //!
 //! ```text
@@ -17,39 +29,148 @@
 //!   magic      : [u8;8]  = b"NVRPOOL0"
 //!   pool_id    : u64     (LE) — monotonic ID, incremented on each rotation
 //!   created_at : i64     (unix seconds, LE)
-//!   reserved   : [u8;40]
+//!   key_id     : u64     (LE) — ID of the encryption key, 0 = none
+//!   first_seq  : u64     (LE) — sequence number of the first record written
+//!                                since the last rotation, 0 = unknown
+//!   reserved   : [u8;24]
 //!
-//! [RecordHeader: 32 bytes per record]
-//!   magic      : [u8;4]  = b"NREC"
+//! [RecordHeader: 64 bytes per record]
+//!   magic      : [u8;4]  = b"NRC2"
+//!   version    : u16     (LE) — RECORD_VERSION at write time
+//!   flags      : u16     (LE) — bit 0: payload encrypted
 //!   camera_id  : [u8;16] (UTF-8, zero-padded)
-//!   start_ts   : i64     (unix seconds, LE)
-//!   end_ts     : i64     (unix seconds, LE) — filled in by writer
+//!   start_ts   : i64     (unix microseconds, LE)
+//!   end_ts     : i64     (unix microseconds, LE) — filled in by writer
 //!   data_len   : u32     (LE)
+//!   checksum   : u32     (LE) — CRC-32 of header (this field zeroed) + data
+//!   sequence   : u64     (LE) — global record sequence number (segment ID)
+//!   camera_key : u32     (LE) — key in `cameras.registry`, 0 = none
+//!   stream     : u8      — 0 = main stream, 1 = substream
+//!   reserved   : [u8;3]
 //!
 //! [raw data    : data_len bytes]
 //! ```
+//!
+//! Pools written before the `NRC2` format may still contain legacy
+//! 40-byte `NREC` records (same fields, no version/flags/reserved, whole
+//! unix seconds). `scan_records` reads both, so old recordings keep
+//! playing until the ring overwrites them. Neither legacy `NREC` nor
+//! version 1 `NRC2` records carry a checksum, so they are trusted as-is.
+//!
+//! Every record written since version 3 carries a sequence number, assigned
+//! by the pool in write order and resumed after the highest one found on
+//! `open`. It is the segment's ID, so IDs survive restarts and rotations.
+//! Older records have none; the index numbers them below the first real
+//! sequence number at load time. A pool header names the first sequence
+//! number of its ring cycle, so a scan never takes records left over from
+//! the previous cycle for new ones, even where the new chain is torn.
+//!
+//! `camera_id` only holds the first 16 bytes of the ID. Since version 4 the
+//! full ID is recovered through `camera_key` (see
+//! [`camera_registry`](crate::storage::camera_registry)); older records
+//! come back with the truncated ID.
+//!
+//! Since version 5 a record also says which of its camera's [`Stream`]s it
+//! holds; older records are all main stream.
+//!
+//! Payloads of records with [`FLAG_ENCRYPTED`] are sealed with the key in
+//! `encryption_key_file` (see [`crypto`](crate::storage::crypto)) and
+//! opened again by `read_segment_data`.
+//!
+//! ## Durability
+//!
+//! Records reach disk according to the pool's
+//! [`DurabilityConfig`](crate::config::DurabilityConfig): `fdatasync` never
+//! (the default, left to the OS), after every write, or at most every
+//! `sync_interval_ms`. Several records can go out in one write through
+//! [`ChunkPool::append_batch`]. A pool shared behind a lock is written in
+//! three steps instead — [`ChunkPool::begin_append`] reserves the space,
+//! [`PendingAppend::write`] writes it without the lock, and
+//! [`ChunkPool::finish_append`] makes the records count — so readers never
+//! wait on the disk write. Syncs due then are made by the write step too,
+//! and idle syncs go through [`ChunkPool::begin_sync`] the same way. With `direct_io` the pool being written is
+//! opened with `O_DIRECT`: writes are widened to whole
+//! [`DIRECT_IO_ALIGN`]-byte blocks, the partial block in front taken from
+//! the previous write and the one behind zero-padded.
 
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use chrono::{DateTime, TimeZone, Utc};
-use tracing::{debug, info, warn};
+use serde::Deserialize;
+use tracing::{debug, error, info, warn};
 
+use crate::config::{DurabilityConfig, DurabilityMode};
 use crate::error::{NvrError, Result};
+use crate::storage::camera_registry::CameraRegistry;
+use crate::storage::crypto::{RecordCipher, SEAL_OVERHEAD};
+use crate::storage::snapshot::{snapshot_path, IndexSnapshot, PoolSnapshot};
 
 // ─────────────────────────────── constants ───────────────────────────────────
 
 pub const POOL_MAGIC: &[u8; 8] = b"NVRPOOL0";
-pub const RECORD_MAGIC: &[u8; 4] = b"NREC";
+pub const RECORD_MAGIC: &[u8; 4] = b"NRC2";
+pub const LEGACY_RECORD_MAGIC: &[u8; 4] = b"NREC";
 pub const POOL_HEADER_SIZE: u64 = 64;
-pub const RECORD_HEADER_SIZE: u64 = 4 + 16 + 8 + 8 + 4; // 40 bytes
+pub const RECORD_HEADER_SIZE: u64 = 64;
+pub const LEGACY_RECORD_HEADER_SIZE: u64 = 4 + 16 + 8 + 8 + 4; // 40 bytes
+/// Version written into every new `NRC2` record header.
+/// 1 = microsecond timestamps, 2 = + CRC-32 checksum, 3 = + sequence number,
+/// 4 = + camera key, 5 = + stream tag.
+pub const RECORD_VERSION: u16 = 5;
+/// Byte offset of the checksum field within an `NRC2` header.
+const CHECKSUM_OFFSET: usize = 44;
+/// Record flag: the payload is sealed with the pool's encryption key.
+pub const FLAG_ENCRYPTED: u16 = 1;
+/// Byte offset of the key ID within the pool header.
+const KEY_ID_OFFSET: u64 = 24;
+/// Byte offset of the first sequence number within the pool header.
+const FIRST_SEQUENCE_OFFSET: u64 = 32;
+/// Offset, length and memory alignment required of `O_DIRECT` writes.
+pub const DIRECT_IO_ALIGN: u64 = 4096;
+/// Attempts at a write before its slot is quarantined.
+const WRITE_ATTEMPTS: u32 = 3;
 
 // ─────────────────────────────── types ───────────────────────────────────────
 
+/// Which of a camera's renditions a record holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    /// The camera's `url`: full resolution, the recording of record.
+    #[default]
+    Main,
+    /// The camera's `substream_url`: low resolution, for live viewing.
+    Sub,
+}
+
+impl Stream {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stream::Main => "main",
+            Stream::Sub => "sub",
+        }
+    }
+
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            Stream::Main => 0,
+            Stream::Sub => 1,
+        }
+    }
+
+    /// Tags this version doesn't know are read as a substream, so they never
+    /// end up in a camera's main timeline.
+    pub(crate) fn from_tag(tag: u8) -> Self {
+        if tag == 0 { Stream::Main } else { Stream::Sub }
+    }
+}
+
 /// Identifies the physical location of a segment in the pool.
 #[derive(Debug, Clone)]
 pub struct SegmentLocation {
@@ -57,10 +178,216 @@ pub struct SegmentLocation {
     pub pool_idx: usize,
     /// Monotonic ID written in the pool header at rotation time.
     pub pool_id: u64,
-    /// Byte offset of the `NREC` magic within the pool file.
+    /// Byte offset of the record magic within the pool file.
     pub record_offset: u64,
     /// Total byte length of the record (header + data).
     pub record_size: u64,
+    /// Byte length of the record header (64 for `NRC2`, 40 for legacy `NREC`).
+    pub header_size: u64,
+    /// Sequence number from the record header; `None` for records written
+    /// before version 3.
+    pub sequence: Option<u64>,
+    /// Stream tag from the record header.
+    pub stream: Stream,
+}
+
+impl SegmentLocation {
+    /// Length of the segment payload, excluding the record header.
+    pub fn data_len(&self) -> u64 {
+        self.record_size - self.header_size
+    }
+}
+
+/// Decoded record header, in either the current `NRC2` or legacy `NREC`
+/// format.
+#[derive(Debug, Clone)]
+pub struct RecordHeader {
+    /// `RECORD_VERSION` for `NRC2` records, 0 for legacy `NREC` records.
+    pub version: u16,
+    /// `FLAG_*` bits; always 0 for legacy `NREC` records.
+    pub flags: u16,
+    pub camera_id: String,
+    pub start_ts: DateTime<Utc>,
+    pub end_ts: DateTime<Utc>,
+    pub data_len: u32,
+    /// CRC-32 over header + payload; `None` for records written before
+    /// version 2, which carry no checksum.
+    pub checksum: Option<u32>,
+    /// Global sequence number; `None` for records written before version 3.
+    pub sequence: Option<u64>,
+    /// Key of the full camera ID in the camera registry; `None` for records
+    /// written before version 4.
+    pub camera_key: Option<u32>,
+    /// `Stream::Main` for records written before version 5.
+    pub stream: Stream,
+}
+
+impl RecordHeader {
+    /// On-disk size of this header.
+    pub fn header_size(&self) -> u64 {
+        if self.version == 0 {
+            LEGACY_RECORD_HEADER_SIZE
+        } else {
+            RECORD_HEADER_SIZE
+        }
+    }
+
+    /// Serialize as a current-format `NRC2` header for a record carrying
+    /// `data`, with the checksum filled in.
+    fn encode(&self, data: &[u8]) -> [u8; RECORD_HEADER_SIZE as usize] {
+        let mut buf = self.encode_fields();
+        let crc = record_checksum(&buf, data);
+        buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].copy_from_slice(&crc.to_le_bytes());
+        buf
+    }
+
+    /// Serialize as a current-format `NRC2` header with the checksum left
+    /// zero.
+    fn encode_fields(&self) -> [u8; RECORD_HEADER_SIZE as usize] {
+        let mut buf = [0u8; RECORD_HEADER_SIZE as usize];
+        let mut w = &mut buf[..];
+        // Writes into a fixed-size slice can't fail.
+        w.write_all(RECORD_MAGIC).unwrap();
+        w.write_u16::<LittleEndian>(RECORD_VERSION).unwrap();
+        w.write_u16::<LittleEndian>(self.flags).unwrap();
+
+        // camera_id: 16 bytes, zero-padded.
+        let mut cam_bytes = [0u8; 16];
+        let stored = stored_camera_id(&self.camera_id);
+        cam_bytes[..stored.len()].copy_from_slice(stored.as_bytes());
+        w.write_all(&cam_bytes).unwrap();
+
+        w.write_i64::<LittleEndian>(self.start_ts.timestamp_micros()).unwrap();
+        w.write_i64::<LittleEndian>(self.end_ts.timestamp_micros()).unwrap();
+        w.write_u32::<LittleEndian>(self.data_len).unwrap();
+        w.write_u32::<LittleEndian>(0).unwrap(); // checksum
+        w.write_u64::<LittleEndian>(self.sequence.unwrap_or(0)).unwrap();
+        w.write_u32::<LittleEndian>(self.camera_key.unwrap_or(0)).unwrap();
+        w.write_u8(self.stream.tag()).unwrap();
+        buf
+    }
+
+    /// Read the raw bytes of one record header (magic included) starting
+    /// at the reader's current position. Returns `Ok(None)` if no record
+    /// magic is found there (zero-fill, garbage, or end of file).
+    pub fn read_raw<R: Read>(r: &mut R) -> Result<Option<Vec<u8>>> {
+        let mut magic = [0u8; 4];
+        if r.read_exact(&mut magic).is_err() {
+            return Ok(None);
+        }
+        let size = if &magic == RECORD_MAGIC {
+            RECORD_HEADER_SIZE
+        } else if &magic == LEGACY_RECORD_MAGIC {
+            LEGACY_RECORD_HEADER_SIZE
+        } else {
+            return Ok(None);
+        };
+        let mut raw = vec![0u8; size as usize];
+        raw[..4].copy_from_slice(&magic);
+        if r.read_exact(&mut raw[4..]).is_err() {
+            return Ok(None); // Header cut off by end of file.
+        }
+        Ok(Some(raw))
+    }
+
+    /// Decode a header from its raw bytes (as returned by `read_raw`).
+    pub fn decode(raw: &[u8]) -> Result<Self> {
+        let mut r = raw;
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        let (version, flags) = if &magic == RECORD_MAGIC {
+            (r.read_u16::<LittleEndian>()?, r.read_u16::<LittleEndian>()?)
+        } else if &magic == LEGACY_RECORD_MAGIC {
+            (0, 0)
+        } else {
+            return Err(NvrError::Storage("bad record magic".into()));
+        };
+
+        let mut cam_bytes = [0u8; 16];
+        r.read_exact(&mut cam_bytes)?;
+        let camera_id = std::str::from_utf8(&cam_bytes)
+            .unwrap_or("")
+            .trim_end_matches('\0')
+            .to_string();
+
+        let start_raw = r.read_i64::<LittleEndian>()?;
+        let end_raw = r.read_i64::<LittleEndian>()?;
+        let data_len = r.read_u32::<LittleEndian>()?;
+
+        let (start_ts, end_ts) = if version == 0 {
+            (
+                Utc.timestamp_opt(start_raw, 0).single(),
+                Utc.timestamp_opt(end_raw, 0).single(),
+            )
+        } else {
+            (
+                DateTime::from_timestamp_micros(start_raw),
+                DateTime::from_timestamp_micros(end_raw),
+            )
+        };
+
+        let checksum = if version >= 2 {
+            Some(r.read_u32::<LittleEndian>()?)
+        } else {
+            None
+        };
+        let sequence = if version >= 3 {
+            Some(r.read_u64::<LittleEndian>()?)
+        } else {
+            None
+        };
+        let camera_key = if version >= 4 {
+            Some(r.read_u32::<LittleEndian>()?).filter(|&k| k != 0)
+        } else {
+            None
+        };
+        let stream = if version >= 5 {
+            Stream::from_tag(r.read_u8()?)
+        } else {
+            Stream::Main
+        };
+
+        Ok(RecordHeader {
+            version,
+            flags,
+            camera_id,
+            start_ts: start_ts.unwrap_or_else(Utc::now),
+            end_ts: end_ts.unwrap_or_else(Utc::now),
+            data_len,
+            checksum,
+            sequence,
+            camera_key,
+            stream,
+        })
+    }
+
+    /// Replace the truncated on-disk `camera_id` with the full ID from the
+    /// registry, if the record has a key the registry knows.
+    pub fn resolve_camera(&mut self, cameras: &CameraRegistry) {
+        if let Some(id) = self.camera_key.and_then(|k| cameras.camera_id(k)) {
+            self.camera_id = id.to_string();
+        }
+    }
+
+    /// Check the stored checksum against the raw header bytes and payload.
+    /// Records without a checksum always pass.
+    pub fn verify(&self, raw: &[u8], data: &[u8]) -> bool {
+        match self.checksum {
+            Some(crc) => record_checksum(raw, data) == crc,
+            None => true,
+        }
+    }
+}
+
+/// CRC-32 over a raw `NRC2` header (with its checksum field treated as
+/// zero) followed by the payload.
+fn record_checksum(raw_header: &[u8], data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&raw_header[..CHECKSUM_OFFSET]);
+    hasher.update(&[0u8; 4]);
+    hasher.update(&raw_header[CHECKSUM_OFFSET + 4..]);
+    hasher.update(data);
+    hasher.finalize()
 }
 
 /// A record recovered from scanning a pool file on startup.
@@ -73,22 +400,135 @@ pub struct ScannedRecord {
     pub pool_id: u64,
     pub record_offset: u64,
     pub record_size: u64,
+    pub header_size: u64,
+    pub sequence: Option<u64>,
+    pub stream: Stream,
+}
+
+/// A record found during a scan whose checksum did not match its contents
+/// (typically a torn write from a power cut mid-`append`). Never indexed.
+#[derive(Debug, Clone)]
+pub struct CorruptRecord {
+    pub pool_idx: usize,
+    pub record_offset: u64,
+    pub record_size: u64,
+    /// Camera ID as read from the (possibly damaged) header.
+    pub camera_id: String,
+}
+
+/// Result of sequentially scanning one pool file.
+#[derive(Debug, Clone, Default)]
+pub struct PoolScan {
+    /// Records whose checksum (if any) verified.
+    pub records: Vec<ScannedRecord>,
+    /// Records skipped because their checksum failed.
+    pub corrupt: Vec<CorruptRecord>,
+    /// Bytes used after POOL_HEADER_SIZE, up to the end of the last good
+    /// record. A torn record at the tail lies beyond this and gets
+    /// overwritten by the next append.
+    pub bytes_used: u64,
+}
+
+/// One record to write with [`ChunkPool::append_batch`].
+#[derive(Debug, Clone, Copy)]
+pub struct NewRecord<'a> {
+    pub camera_id: &'a str,
+    pub stream: Stream,
+    pub start_ts: DateTime<Utc>,
+    pub end_ts: DateTime<Utc>,
+    pub data: &'a [u8],
+}
+
+/// Write counters since the pool was opened.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteStats {
+    /// Records appended.
+    pub records: u64,
+    /// Bytes appended, record headers included.
+    pub bytes: u64,
+    /// Write calls issued; fewer than `records` when writes were coalesced.
+    pub writes: u64,
+    /// Writes that went out with `O_DIRECT`.
+    pub direct_writes: u64,
+    /// `fdatasync` calls.
+    pub syncs: u64,
+    /// Total time spent in `fdatasync`.
+    pub sync_time: Duration,
+}
+
+/// Write health of a pool slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotHealth {
+    Healthy,
+    /// Writes failed but succeeded on retry; cleared when the ring next
+    /// rotates into the slot.
+    Degraded,
+    /// Quarantined: writes kept failing, and rotation skips the slot.
+    Failed,
+}
+
+impl SlotHealth {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SlotHealth::Healthy => "healthy",
+            SlotHealth::Degraded => "degraded",
+            SlotHealth::Failed => "failed",
+        }
+    }
 }
 
 // ─────────────────────────────── ChunkPool ───────────────────────────────────
 
+#[derive(Clone)]
 struct PoolSlot {
     path: PathBuf,
     pool_id: u64,
     /// Bytes used after POOL_HEADER_SIZE.
     bytes_used: u64,
+    /// False if the slot's volume (or file) was unavailable at `open`.
+    online: bool,
+    health: SlotHealth,
+    /// Failed write attempts since the pool was opened.
+    write_errors: u64,
+    /// Key ID in the pool header, if known to be current.
+    key_id: Option<u64>,
+    /// First sequence number of the current ring cycle, as in the header.
+    first_sequence: u64,
+}
+
+impl PoolSlot {
+    fn new(path: PathBuf, pool_id: u64, bytes_used: u64, online: bool) -> Self {
+        PoolSlot {
+            path,
+            pool_id,
+            bytes_used,
+            online,
+            health: SlotHealth::Healthy,
+            write_errors: 0,
+            key_id: None,
+            first_sequence: 0,
+        }
+    }
+
+    fn writable(&self) -> bool {
+        self.online && self.health != SlotHealth::Failed
+    }
+}
+
+/// Result of [`ChunkPool::open_slot`].
+struct OpenedSlot {
+    pool_id: u64,
+    bytes_used: u64,
+    first_sequence: u64,
+    /// False for a slot the ring hasn't written yet.
+    has_header: bool,
 }
 
-/// Manages `max_pools` pre-allocated binary pool files under `base_path/`.
+/// Manages `max_pools` pre-allocated binary pool files under `base_path/`
+/// (or striped across storage volumes).
 /// **Not** thread-safe on its own; callers must hold a lock or use
 /// `GlobalChunkWriter` which is the single writer.
 pub struct ChunkPool {
-    #[allow(dead_code)]
     base_path: PathBuf,
     pool_capacity: u64, // bytes per pool excluding header
     slots: Vec<PoolSlot>,
@@ -96,6 +536,124 @@ pub struct ChunkPool {
     pub write_idx: usize,
     /// Shared per-pool reader counters.
     pub read_counters: Arc<PoolReadCounters>,
+    /// Verify record checksums in `read_segment_data` (off by default; the
+    /// startup scan always verifies).
+    verify_reads: bool,
+    /// Whether the writer should persist an index snapshot at each rotation.
+    save_snapshots: bool,
+    /// Records recovered by `open` (from the index snapshot and/or scanning),
+    /// handed over once via `take_recovered`.
+    recovered: Vec<ScannedRecord>,
+    /// Full camera IDs for the keys in record headers.
+    cameras: CameraRegistry,
+    /// Sequence number for the next appended record.
+    next_sequence: u64,
+    /// Open handle to the pool at `write_idx`, kept across appends and
+    /// dropped at rotation. Lent to the [`PendingAppend`] being written.
+    active_file: Option<ActiveFile>,
+    /// Between `begin_append` and `finish_append`.
+    append_in_flight: bool,
+    /// A [`read_view`](Self::read_view): appends and syncs fail.
+    read_only: bool,
+    durability: DurabilityConfig,
+    /// When the oldest write not yet synced was made.
+    unsynced_since: Option<Instant>,
+    stats: WriteStats,
+    /// Seals new record payloads and opens encrypted ones; `None` = write
+    /// in the clear.
+    cipher: Option<RecordCipher>,
+}
+
+/// Write handle to the pool being written.
+struct ActiveFile {
+    file: File,
+    /// Opened with `O_DIRECT`.
+    direct: bool,
+    /// Offset and contents of the partially written block at the end of the
+    /// last direct write, which the next one has to rewrite.
+    tail: Option<(u64, Vec<u8>)>,
+}
+
+/// Records placed by [`ChunkPool::begin_append`] and waiting to be
+/// written — and synced, if the durability mode says so by then. Neither
+/// needs anything from the pool, so both happen without the pool lock.
+pub struct PendingAppend {
+    pool_idx: usize,
+    path: PathBuf,
+    offset: u64,
+    file_len: u64,
+    /// The pool's write handle, if it had one open.
+    file: Option<ActiveFile>,
+    direct_io: bool,
+    buf: Vec<u8>,
+    locations: Vec<SegmentLocation>,
+    /// Failed attempts, oldest first.
+    errors: Vec<NvrError>,
+    /// Once written: whether it went out with `O_DIRECT`.
+    direct: Option<bool>,
+    /// `fdatasync` once written.
+    sync: bool,
+    /// Outcome and duration of that sync.
+    synced: Option<(std::io::Result<()>, Duration)>,
+}
+
+impl PendingAppend {
+    /// Write the records, then sync them if due. A failed write is retried
+    /// on a fresh handle, up to `WRITE_ATTEMPTS` times unless the medium
+    /// itself failed; [`ChunkPool::finish_append`] accounts for the
+    /// failures.
+    pub fn write(&mut self) {
+        if self.buf.is_empty() || self.direct.is_some() {
+            return;
+        }
+        loop {
+            match self.try_write() {
+                Ok(direct) => {
+                    self.direct = Some(direct);
+                    if let (true, Some(f)) = (self.sync, &self.file) {
+                        let started = Instant::now();
+                        self.synced = Some((f.file.sync_data(), started.elapsed()));
+                    }
+                    return;
+                }
+                Err(e) => {
+                    // Reopen next time rather than trust the handle's state.
+                    self.file = None;
+                    let give_up =
+                        is_media_error(&e) || self.errors.len() + 1 >= WRITE_ATTEMPTS as usize;
+                    self.errors.push(e);
+                    if give_up {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// One attempt. Returns whether it went out with `O_DIRECT`.
+    fn try_write(&mut self) -> Result<bool> {
+        let file = match &mut self.file {
+            Some(f) => f,
+            None => self.file.insert(ActiveFile::open(&self.path, self.direct_io)?),
+        };
+        file.write_at(&self.path, self.offset, &self.buf, self.file_len)
+    }
+}
+
+/// A sync started by [`ChunkPool::begin_sync`], on its own handle to the
+/// pool file.
+pub struct PendingSync {
+    pool_idx: usize,
+    file: File,
+    synced: Option<(std::io::Result<()>, Duration)>,
+}
+
+impl PendingSync {
+    /// `fdatasync` the pool file.
+    pub fn run(&mut self) {
+        let started = Instant::now();
+        self.synced = Some((self.file.sync_data(), started.elapsed()));
+    }
 }
 
 // ────────────── read safety ───────────────────────────────────────
@@ -159,61 +717,219 @@ impl ChunkPool {
     /// Open (or create + pre-allocate) all pool files.
     /// If pool files already exist, scans their headers to determine
     /// which pool was last written to and resumes from there.
+    ///
+    /// Record metadata comes from the index snapshot for every slot whose
+    /// pool_id still matches it, scanning only what was appended after the
+    /// snapshot; other slots are scanned in full. The result is available
+    /// through [`take_recovered`](Self::take_recovered).
     pub fn open(base_path: &Path, pool_size_bytes: u64, max_pools: usize) -> Result<Self> {
+        Self::open_volumes(base_path, &[], pool_size_bytes, max_pools)
+    }
+
+    /// Like [`open`](Self::open), but with pool files striped across
+    /// `volumes` (empty = all in `base_path`). A volume directory that does
+    /// not exist is never created — it is most likely an unmounted disk —
+    /// and its slots are marked offline, as are slots whose file can't be
+    /// opened. Fails only if no slot is usable.
+    pub fn open_volumes(
+        base_path: &Path,
+        volumes: &[PathBuf],
+        pool_size_bytes: u64,
+        max_pools: usize,
+    ) -> Result<Self> {
         std::fs::create_dir_all(base_path)
             .map_err(|e| NvrError::Storage(format!("Cannot create storage dir: {e}")))?;
 
+        let cameras = CameraRegistry::open(base_path)?;
+        let snapshot = IndexSnapshot::load(&snapshot_path(base_path))
+            .unwrap_or_else(|e| {
+                warn!(error = %e, "Cannot read index snapshot, scanning all pools");
+                None
+            })
+            .filter(|s| s.pool_capacity == pool_size_bytes);
+
+        let mut recovered = Vec::new();
         let mut slots = Vec::with_capacity(max_pools);
-        let mut best_idx: usize = 0;
+        let mut best_idx: Option<usize> = None;
         let mut best_pool_id: u64 = 0;
-        let mut any_existing = false;
 
         for i in 0..max_pools {
-            let path = base_path.join(format!("pool_{:03}.bin", i));
-            if !path.exists() {
-                let total = POOL_HEADER_SIZE + pool_size_bytes;
-                let f = File::create(&path)?;
-                f.set_len(total)
-                    .map_err(|e| NvrError::Storage(format!("preallocate {path:?}: {e}")))?;
-                info!(pool = i, path = ?path, size_mb = total / 1_048_576, "Pre-allocated pool file");
-                slots.push(PoolSlot { path, pool_id: i as u64, bytes_used: 0 });
-            } else {
-                any_existing = true;
-                // Read pool header to recover pool_id and detect latest.
-                let (pid, _created) = Self::read_pool_header(&path)?;
-                // Scan records to find bytes_used.
-                let records = Self::scan_records(&path, i, pid, pool_size_bytes)?;
-                let bytes_used: u64 = records.iter().map(|r| r.record_size).sum();
-                if pid >= best_pool_id {
-                    best_pool_id = pid;
-                    best_idx = i;
+            let path = pool_file_path(base_path, volumes, i);
+            let volume = path.parent().unwrap_or(base_path);
+            if !volume.is_dir() {
+                warn!(pool = i, volume = ?volume, "Storage volume missing, pool slot offline");
+                slots.push(PoolSlot::new(path, i as u64, 0, false));
+                continue;
+            }
+
+            let opened = Self::open_slot(
+                &path,
+                i,
+                pool_size_bytes,
+                snapshot.as_ref(),
+                &cameras,
+                &mut recovered,
+            );
+            match opened {
+                Ok(slot) => {
+                    if slot.has_header && (best_idx.is_none() || slot.pool_id >= best_pool_id) {
+                        best_pool_id = slot.pool_id;
+                        best_idx = Some(i);
+                    }
+                    slots.push(PoolSlot {
+                        first_sequence: slot.first_sequence,
+                        ..PoolSlot::new(path, slot.pool_id, slot.bytes_used, true)
+                    });
+                }
+                Err(e) => {
+                    warn!(pool = i, path = ?path, error = %e, "Cannot open pool file, slot offline");
+                    slots.push(PoolSlot::new(path, i as u64, 0, false));
                 }
-                info!(pool = i, pool_id = pid, records = records.len(), bytes_used, "Recovered pool file");
-                slots.push(PoolSlot { path, pool_id: pid, bytes_used });
             }
         }
 
-        let write_idx = if any_existing { best_idx } else { 0 };
+        let Some(first_online) = slots.iter().position(|s| s.online) else {
+            return Err(NvrError::Storage("No storage volume available for pool files".into()));
+        };
+        let write_idx = best_idx.unwrap_or(first_online);
 
         let read_counters = Arc::new(PoolReadCounters::new(max_pools));
+        // Sequence numbers recorded in the snapshot still count for slots
+        // that are offline now, so they aren't handed out twice.
+        let snapshot_next = snapshot
+            .iter()
+            .flat_map(|s| &s.pools)
+            .flat_map(|p| &p.records)
+            .filter_map(|r| r.sequence)
+            .max()
+            .map_or(0, |s| s + 1);
+        let next_sequence = next_sequence(&recovered).max(snapshot_next);
 
-        let pool = ChunkPool {
+        let mut pool = ChunkPool {
             base_path: base_path.to_path_buf(),
             pool_capacity: pool_size_bytes,
             slots,
             write_idx,
             read_counters,
+            verify_reads: false,
+            save_snapshots: false,
+            recovered,
+            next_sequence,
+            cameras,
+            active_file: None,
+            append_in_flight: false,
+            read_only: false,
+            durability: DurabilityConfig::default(),
+            unsynced_since: None,
+            stats: WriteStats::default(),
+            cipher: None,
         };
 
-        if !any_existing {
-            pool.write_pool_header(0)?;
+        if best_idx.is_none() {
+            pool.slots[write_idx].first_sequence = next_sequence;
+            pool.write_pool_header(write_idx)?;
         }
 
-        info!(write_idx, "ChunkPool opened");
+        let offline = pool.slots.iter().filter(|s| !s.online).count();
+        if offline > 0 {
+            warn!(offline, "ChunkPool running with offline pool slots");
+        }
+        info!(write_idx, next_sequence, "ChunkPool opened");
         Ok(pool)
     }
 
-    /// Append one segment record.  Returns the [`SegmentLocation`] written.
+    /// A read-only copy of this pool as it is now — slot table, camera
+    /// registry, key and read settings — for long reads (exports, holds)
+    /// that shouldn't hold the pool lock throughout. Opens no pool file
+    /// until a read does, and never writes one: appends on it fail. Reads
+    /// of segments rotated out since it was taken fail the stale-read check
+    /// like any other.
+    pub fn read_view(&self) -> ChunkPool {
+        ChunkPool {
+            base_path: self.base_path.clone(),
+            pool_capacity: self.pool_capacity,
+            slots: self.slots.clone(),
+            write_idx: self.write_idx,
+            read_counters: self.read_counters.clone(),
+            verify_reads: self.verify_reads,
+            save_snapshots: false,
+            recovered: Vec::new(),
+            cameras: self.cameras.clone(),
+            next_sequence: self.next_sequence,
+            active_file: None,
+            append_in_flight: false,
+            read_only: true,
+            durability: self.durability.clone(),
+            unsynced_since: None,
+            stats: WriteStats::default(),
+            cipher: self.cipher.clone(),
+        }
+    }
+
+    /// Create or recover one pool file. Recovered records go to `recovered`.
+    fn open_slot(
+        path: &Path,
+        i: usize,
+        pool_size_bytes: u64,
+        snapshot: Option<&IndexSnapshot>,
+        cameras: &CameraRegistry,
+        recovered: &mut Vec<ScannedRecord>,
+    ) -> Result<OpenedSlot> {
+        if !path.exists() {
+            let total = POOL_HEADER_SIZE + pool_size_bytes;
+            let f = File::create(path)?;
+            f.set_len(total)
+                .map_err(|e| NvrError::Storage(format!("preallocate {path:?}: {e}")))?;
+            info!(pool = i, path = ?path, size_mb = total / 1_048_576, "Pre-allocated pool file");
+            return Ok(OpenedSlot { pool_id: i as u64, bytes_used: 0, first_sequence: 0, has_header: false });
+        }
+
+        let Some((pid, _created)) = Self::read_pool_header(path)? else {
+            // Pre-allocated but never rotated into (no header yet):
+            // same as a freshly created file, and never the resume point.
+            return Ok(OpenedSlot { pool_id: i as u64, bytes_used: 0, first_sequence: 0, has_header: false });
+        };
+
+        // Scan records to find bytes_used — or just the tail written
+        // since the snapshot, if it is still valid for this slot.
+        let snap = snapshot
+            .and_then(|s| s.pool(i, pid))
+            .filter(|p| Self::snapshot_matches(path, p, cameras));
+        let (snap_records, scan_from) = match snap {
+            Some(p) => (p.records.clone(), POOL_HEADER_SIZE + p.bytes_used),
+            None => (Vec::new(), POOL_HEADER_SIZE),
+        };
+        let prev_sequence = snap_records.last().and_then(|r| r.sequence);
+        let scan = Self::scan_pool_from(
+            path,
+            i,
+            pid,
+            pool_size_bytes,
+            scan_from,
+            prev_sequence,
+            cameras,
+        )?;
+        info!(
+            pool = i,
+            pool_id = pid,
+            from_snapshot = snap_records.len(),
+            scanned = scan.records.len(),
+            corrupt = scan.corrupt.len(),
+            bytes_used = scan.bytes_used,
+            "Recovered pool file"
+        );
+        recovered.extend(snap_records);
+        recovered.extend(scan.records);
+        Ok(OpenedSlot {
+            pool_id: pid,
+            bytes_used: scan.bytes_used,
+            first_sequence: Self::read_pool_first_sequence(path)?,
+            has_header: true,
+        })
+    }
+
+    /// Append one main-stream segment record.  Returns the
+    /// [`SegmentLocation`] written.
     pub fn append(
         &mut self,
         camera_id: &str,
@@ -221,99 +937,337 @@ impl ChunkPool {
         end_ts: DateTime<Utc>,
         data: &[u8],
     ) -> Result<SegmentLocation> {
-        let record_size = RECORD_HEADER_SIZE + data.len() as u64;
+        let record = NewRecord { camera_id, stream: Stream::Main, start_ts, end_ts, data };
+        let mut locs = self.append_batch(&[record])?;
+        Ok(locs.remove(0))
+    }
 
-        if record_size > self.pool_capacity {
-            return Err(NvrError::Storage(format!(
-                "Segment ({record_size} bytes) > pool capacity ({} bytes)",
-                self.pool_capacity
-            )));
+    /// Append several records, in order, with as few writes as possible:
+    /// one per pool they end up in. Returns their locations. Nothing is
+    /// written if any record is larger than a pool.
+    pub fn append_batch(&mut self, records: &[NewRecord]) -> Result<Vec<SegmentLocation>> {
+        let mut locs = Vec::with_capacity(records.len());
+        while locs.len() < records.len() {
+            let mut pending = self.begin_append(&records[locs.len()..])?;
+            pending.write();
+            locs.extend(self.finish_append(pending)?);
+        }
+        Ok(locs)
+    }
+
+    /// First half of an append that leaves the pool free while the disk is
+    /// written: encode as many of `records` as fit in the current pool
+    /// (rotating first if it is full) and reserve their place at its end.
+    /// The caller runs [`PendingAppend::write`] without holding the pool
+    /// lock, then hands the result to [`finish_append`](Self::finish_append).
+    /// Until then the records don't count towards `bytes_used`, and no
+    /// other append can begin.
+    ///
+    /// Nothing is reserved if any record is larger than a pool.
+    pub fn begin_append(&mut self, records: &[NewRecord]) -> Result<PendingAppend> {
+        if self.read_only {
+            return Err(NvrError::Storage("Pool read view can't be written".into()));
+        }
+        if self.append_in_flight {
+            return Err(NvrError::Storage("Another pool append is still in flight".into()));
+        }
+        for rec in records {
+            let record_size = self.record_size(rec.data.len());
+            if record_size > self.pool_capacity {
+                return Err(NvrError::Storage(format!(
+                    "Segment ({record_size} bytes) > pool capacity ({} bytes)",
+                    self.pool_capacity
+                )));
+            }
         }
 
-        // Rotate to next pool if current one is full.
-        if self.slots[self.write_idx].bytes_used + record_size > self.pool_capacity {
-            self.rotate()?;
+        let mut locs = Vec::with_capacity(records.len());
+        let mut buf = Vec::new();
+        for rec in records {
+            let record_size = self.record_size(rec.data.len());
+
+            // Registered (and synced) before any record refers to the key.
+            let camera_key = self.cameras.key_for(rec.camera_id)?;
+
+            // Rotate to next pool if current one is full (or quarantined).
+            // Records behind a rotation are left for the next append.
+            let slot = &self.slots[self.write_idx];
+            if !slot.writable() || slot.bytes_used + buf.len() as u64 + record_size > self.pool_capacity {
+                if !buf.is_empty() {
+                    break;
+                }
+                self.rotate()?;
+            }
+
+            let slot = &self.slots[self.write_idx];
+            let mut header = RecordHeader {
+                version: RECORD_VERSION,
+                flags: 0,
+                camera_id: rec.camera_id.to_string(),
+                start_ts: rec.start_ts,
+                end_ts: rec.end_ts,
+                data_len: (record_size - RECORD_HEADER_SIZE) as u32,
+                checksum: None,
+                sequence: Some(self.next_sequence),
+                camera_key: Some(camera_key),
+                stream: rec.stream,
+            };
+            let sealed = match &self.cipher {
+                Some(cipher) => {
+                    header.flags |= FLAG_ENCRYPTED;
+                    Some(cipher.seal(&header.encode_fields(), rec.data)?)
+                }
+                None => None,
+            };
+            let data = sealed.as_deref().unwrap_or(rec.data);
+            locs.push(SegmentLocation {
+                pool_idx: self.write_idx,
+                pool_id: slot.pool_id,
+                record_offset: POOL_HEADER_SIZE + slot.bytes_used + buf.len() as u64,
+                record_size,
+                header_size: RECORD_HEADER_SIZE,
+                sequence: header.sequence,
+                stream: rec.stream,
+            });
+            buf.extend_from_slice(&header.encode(data));
+            buf.extend_from_slice(data);
+            self.next_sequence += 1;
         }
 
-        let slot = &mut self.slots[self.write_idx];
-        let record_offset = POOL_HEADER_SIZE + slot.bytes_used;
+        let idx = self.write_idx;
+        let sync = match self.durability.mode {
+            DurabilityMode::None => false,
+            DurabilityMode::PerRecord => true,
+            // Due if the oldest unsynced write is.
+            DurabilityMode::Interval => self.sync_deadline().is_some_and(|d| d <= Instant::now()),
+        };
+        // The header has to name the key these records are sealed with.
+        let key_id = self.key_id();
+        if !buf.is_empty() && self.slots[idx].key_id != Some(key_id) {
+            if let Err(e) = self.write_pool_header(idx) {
+                self.record_write_error(idx, &e, WRITE_ATTEMPTS);
+                return Err(e);
+            }
+            self.slots[idx].key_id = Some(key_id);
+        }
 
-        let mut file = BufWriter::new(
-            OpenOptions::new()
-                .write(true)
-                .open(&slot.path)
-                .map_err(|e| NvrError::Storage(format!("open pool {:?}: {e}", slot.path)))?,
+        self.append_in_flight = true;
+        let slot = &self.slots[idx];
+        Ok(PendingAppend {
+            pool_idx: idx,
+            path: slot.path.clone(),
+            offset: POOL_HEADER_SIZE + slot.bytes_used,
+            file_len: POOL_HEADER_SIZE + self.pool_capacity,
+            file: self.active_file.take(),
+            direct_io: self.durability.direct_io,
+            buf,
+            locations: locs,
+            errors: Vec::new(),
+            direct: None,
+            sync,
+            synced: None,
+        })
+    }
+
+    /// Second half of an append: account for how the write (and sync) of
+    /// `pending` went and count its records into the pool. Returns their
+    /// locations, or the error that made the write give up (the slot is
+    /// quarantined by then).
+    pub fn finish_append(&mut self, pending: PendingAppend) -> Result<Vec<SegmentLocation>> {
+        self.append_in_flight = false;
+        let idx = pending.pool_idx;
+        let attempts = pending.errors.len() + 1;
+        let mut last_error = None;
+        for (i, e) in pending.errors.into_iter().enumerate() {
+            self.record_write_error(idx, &e, i as u32 + 1);
+            last_error = Some(e);
+        }
+        if pending.buf.is_empty() {
+            return Ok(pending.locations);
+        }
+        let Some(direct) = pending.direct else {
+            return Err(last_error
+                .unwrap_or_else(|| NvrError::Storage("Pool append finished unwritten".into())));
+        };
+        if attempts > 1 {
+            info!(pool_idx = idx, attempts, "Pool write succeeded on retry");
+        }
+
+        self.active_file = pending.file;
+        self.slots[idx].bytes_used += pending.buf.len() as u64;
+        self.stats.records += pending.locations.len() as u64;
+        self.stats.bytes += pending.buf.len() as u64;
+        self.stats.writes += 1;
+        if direct {
+            self.stats.direct_writes += 1;
+        }
+
+        match pending.synced {
+            Some((res, elapsed)) => {
+                // Cleared even if the sync failed, as in `sync`.
+                self.unsynced_since = None;
+                self.account_sync(idx, res, elapsed)?;
+            }
+            None if self.durability.mode != DurabilityMode::None => {
+                self.unsynced_since.get_or_insert_with(Instant::now);
+            }
+            None => {}
+        }
+        Ok(pending.locations)
+    }
+
+    /// Account a failed write to slot `idx` (attempt number `attempt`).
+    /// Returns `true` if the slot is now quarantined and the write should
+    /// not be retried.
+    fn record_write_error(&mut self, idx: usize, err: &NvrError, attempt: u32) -> bool {
+        let slot = &mut self.slots[idx];
+        slot.write_errors += 1;
+        if attempt < WRITE_ATTEMPTS && !is_media_error(err) {
+            if slot.health == SlotHealth::Healthy {
+                slot.health = SlotHealth::Degraded;
+            }
+            warn!(pool_idx = idx, path = ?slot.path, attempt, error = %err, "Pool write failed, retrying");
+            return false;
+        }
+        slot.health = SlotHealth::Failed;
+        error!(
+            pool_idx = idx,
+            path = ?slot.path,
+            write_errors = slot.write_errors,
+            error = %err,
+            "Pool slot quarantined after write errors, rotation will skip it"
         );
-        file.seek(SeekFrom::Start(record_offset))?;
+        true
+    }
 
-        // Write RecordHeader.
-        file.write_all(RECORD_MAGIC)?;
+    /// `fdatasync` the pool being written, if it has writes not yet synced.
+    pub fn sync(&mut self) -> Result<()> {
+        match self.begin_sync()? {
+            Some(mut pending) => {
+                pending.run();
+                self.finish_sync(pending)
+            }
+            None => Ok(()),
+        }
+    }
 
-        // camera_id: 16 bytes, zero-padded.
-        let mut cam_bytes = [0u8; 16];
-        let src = camera_id.as_bytes();
-        cam_bytes[..src.len().min(16)].copy_from_slice(&src[..src.len().min(16)]);
-        file.write_all(&cam_bytes)?;
-
-        file.write_i64::<LittleEndian>(start_ts.timestamp())?;
-        file.write_i64::<LittleEndian>(end_ts.timestamp())?;
-        file.write_u32::<LittleEndian>(data.len() as u32)?;
-        file.write_all(data)?;
-        file.flush()?;
-
-        let loc = SegmentLocation {
-            pool_idx: self.write_idx,
-            pool_id: slot.pool_id,
-            record_offset,
-            record_size,
+    /// [`sync`](Self::sync) in the same three steps as an append, for a
+    /// pool shared behind a lock: take a handle to the pool being written
+    /// (`None` if nothing is waiting to be synced), run
+    /// [`PendingSync::run`] without the lock, then account for it with
+    /// [`finish_sync`](Self::finish_sync). The writer syncs this way when
+    /// an `interval` mode deadline passes while it is idle.
+    pub fn begin_sync(&mut self) -> Result<Option<PendingSync>> {
+        // Cleared even if the sync fails: the kernel may already have
+        // dropped the failed pages, so retrying would report success for
+        // data that never made it.
+        if self.read_only || self.unsynced_since.take().is_none() {
+            return Ok(None);
+        }
+        let idx = self.write_idx;
+        let file = match &self.active_file {
+            Some(f) => f.file.try_clone(),
+            None => File::open(&self.slots[idx].path),
         };
-        slot.bytes_used += record_size;
-        Ok(loc)
+        match file {
+            Ok(file) => Ok(Some(PendingSync { pool_idx: idx, file, synced: None })),
+            Err(e) => {
+                let e = NvrError::from(e);
+                self.record_write_error(idx, &e, WRITE_ATTEMPTS);
+                Err(e)
+            }
+        }
     }
 
-    /// Rotate to the next pool file (ring wrap-around).
-    /// If readers are active on the target pool, spins briefly (up to 5s)
-    /// before proceeding to avoid data corruption during reads.
-    fn rotate(&mut self) -> Result<()> {
-        self.write_idx = (self.write_idx + 1) % self.slots.len();
+    /// Account for a sync started with [`begin_sync`](Self::begin_sync).
+    pub fn finish_sync(&mut self, pending: PendingSync) -> Result<()> {
+        match pending.synced {
+            Some((res, elapsed)) => self.account_sync(pending.pool_idx, res, elapsed),
+            None => Err(NvrError::Storage("Pool sync finished without running".into())),
+        }
+    }
 
-        // Wait for any readers on the target pool to finish.
-        let mut waited = 0u32;
-        while self.read_counters.has_readers(self.write_idx) && waited < 50 {
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            waited += 1;
+    fn account_sync(&mut self, idx: usize, res: std::io::Result<()>, elapsed: Duration) -> Result<()> {
+        if let Err(e) = res {
+            // Nothing to retry: the written pages are gone either way.
+            let e = NvrError::from(e);
+            self.record_write_error(idx, &e, WRITE_ATTEMPTS);
+            return Err(e);
         }
-        if self.read_counters.has_readers(self.write_idx) {
-            warn!(
-                pool_idx = self.write_idx,
-                "Rotating despite active readers (timeout after 5s)"
-            );
+        self.stats.syncs += 1;
+        self.stats.sync_time += elapsed;
+        Ok(())
+    }
+
+    /// When pending writes are due to be synced: right away in
+    /// `per_record` mode, after `sync_interval_ms` in `interval` mode.
+    /// `None` if nothing is pending or the mode never syncs.
+    pub fn sync_deadline(&self) -> Option<Instant> {
+        let since = self.unsynced_since?;
+        match self.durability.mode {
+            DurabilityMode::Interval => {
+                Some(since + Duration::from_millis(self.durability.sync_interval_ms))
+            }
+            DurabilityMode::PerRecord => Some(since),
+            DurabilityMode::None => None,
         }
+    }
+
+    /// Rotate to the next pool file (ring wrap-around).
+    /// Never waits for readers of the target pool: that would block
+    /// everyone behind the pool lock. The global writer gives them time
+    /// before calling `append`; a read that still overlaps the rotation
+    /// gets [`NvrError::SegmentEvicted`] instead of the new data.
+    fn rotate(&mut self) -> Result<()> {
+        // Whatever is pending belongs to the pool being left. A failed sync
+        // quarantines it, which is all the more reason to move on.
+        let _ = self.sync();
+        let prev_id = self.slots[self.write_idx].pool_id;
+        let next_idx = self.next_write_idx();
+        if !self.slots[next_idx].writable() {
+            return Err(NvrError::Storage("No writable pool slot left".into()));
+        }
+        self.write_idx = next_idx;
+        self.active_file = None;
 
+        // Smallest ID above the pool just filled (the newest) that keeps
+        // `pool_id % slots == idx`. Unless offline slots were skipped, this
+        // is just the slot's old ID + slots.
         let num_slots = self.slots.len() as u64;
+        let mut pool_id = prev_id - prev_id % num_slots + self.write_idx as u64;
+        if pool_id <= prev_id {
+            pool_id += num_slots;
+        }
         let slot = &mut self.slots[self.write_idx];
-        slot.pool_id += num_slots;
+        slot.pool_id = pool_id;
         slot.bytes_used = 0;
+        // Whatever the old cycle left behind is older than this, which is how
+        // a scan tells it apart even where the new chain is torn.
+        slot.first_sequence = self.next_sequence;
+        // A degraded slot gets a clean start; a failed header write below
+        // quarantines it again.
+        slot.health = SlotHealth::Healthy;
         warn!(
             pool_idx = self.write_idx,
             pool_id = slot.pool_id,
             path = ?slot.path,
             "Pool rotated — oldest data will be overwritten"
         );
-        self.write_pool_header(self.write_idx)
+        if let Err(e) = self.write_pool_header(self.write_idx) {
+            self.record_write_error(self.write_idx, &e, WRITE_ATTEMPTS);
+            return Err(e);
+        }
+        self.slots[self.write_idx].key_id = Some(self.key_id());
+        // Synced along with the pool's first records.
+        if self.durability.mode != DurabilityMode::None {
+            self.unsynced_since.get_or_insert_with(Instant::now);
+        }
+        Ok(())
     }
 
     fn write_pool_header(&self, idx: usize) -> Result<()> {
         let slot = &self.slots[idx];
-        let mut f = OpenOptions::new().write(true).open(&slot.path)
-            .map_err(|e| NvrError::Storage(format!("header open {:?}: {e}", slot.path)))?;
-        f.seek(SeekFrom::Start(0))?;
-        f.write_all(POOL_MAGIC)?;
-        f.write_u64::<LittleEndian>(slot.pool_id)?;
-        f.write_i64::<LittleEndian>(Utc::now().timestamp())?;
-        f.write_all(&[0u8; 40])?; // reserved
-        f.flush()?;
-        Ok(())
+        write_pool_header_at(&slot.path, slot.pool_id, self.key_id(), slot.first_sequence)
     }
 
     /// Return the current write pool index and approximate fill percentage.
@@ -323,29 +1277,215 @@ impl ChunkPool {
     }
 
     pub fn pool_count(&self) -> usize { self.slots.len() }
+    pub fn is_online(&self, idx: usize) -> bool { self.slots[idx].online }
+    pub fn slot_health(&self, idx: usize) -> SlotHealth { self.slots[idx].health }
+    pub fn write_errors(&self, idx: usize) -> u64 { self.slots[idx].write_errors }
+
+    /// Whether slot `idx` can be written: online and not quarantined.
+    pub fn is_writable(&self, idx: usize) -> bool { self.slots[idx].writable() }
+
+    /// Health of the storage as a whole: `Failed` if no slot is writable,
+    /// `Degraded` if any slot is offline or not healthy.
+    pub fn health(&self) -> SlotHealth {
+        if !self.slots.iter().any(|s| s.writable()) {
+            SlotHealth::Failed
+        } else if self.slots.iter().all(|s| s.online && s.health == SlotHealth::Healthy) {
+            SlotHealth::Healthy
+        } else {
+            SlotHealth::Degraded
+        }
+    }
+
+    /// The slot the next rotation will move to: the next writable slot in
+    /// ring order (the current one if there is no other).
+    pub fn next_write_idx(&self) -> usize {
+        let n = self.slots.len();
+        (1..=n)
+            .map(|step| (self.write_idx + step) % n)
+            .find(|&i| self.slots[i].writable())
+            .unwrap_or(self.write_idx)
+    }
     pub fn pool_path(&self, idx: usize) -> &Path { &self.slots[idx].path }
+    pub fn pool_id(&self, idx: usize) -> u64 { self.slots[idx].pool_id }
+    pub fn bytes_used(&self, idx: usize) -> u64 { self.slots[idx].bytes_used }
+    pub fn pool_capacity(&self) -> u64 { self.pool_capacity }
+    pub fn base_path(&self) -> &Path { &self.base_path }
+
+    /// Hand over the records recovered by `open`, sorted by pool_id
+    /// (chronological order across rotations). Empty on later calls.
+    pub fn take_recovered(&mut self) -> Vec<ScannedRecord> {
+        let mut all = std::mem::take(&mut self.recovered);
+        all.sort_by_key(|r| (r.pool_id, r.record_offset));
+        all
+    }
+
+    /// Enable or disable checksum verification in `read_segment_data`.
+    pub fn set_verify_reads(&mut self, verify: bool) {
+        self.verify_reads = verify;
+    }
+
+    /// Persist an index snapshot at every pool rotation (see
+    /// [`snapshot`](crate::storage::snapshot)).
+    pub fn set_save_snapshots(&mut self, save: bool) {
+        self.save_snapshots = save;
+    }
+
+    pub fn save_snapshots(&self) -> bool { self.save_snapshots }
+
+    /// Set when writes are synced, whether they bypass the page cache, and
+    /// how many queued records the writer may combine.
+    pub fn set_durability(&mut self, durability: DurabilityConfig) {
+        // Reopened with the new flags on the next write.
+        self.active_file = None;
+        self.durability = durability;
+    }
+
+    pub fn durability(&self) -> &DurabilityConfig { &self.durability }
+
+    /// Seal new records with `cipher` (`None` = write in the clear), and
+    /// open encrypted records with it. The key ID goes into the header of
+    /// each pool written from now on.
+    pub fn set_encryption(&mut self, cipher: Option<RecordCipher>) {
+        self.cipher = cipher;
+    }
+
+    /// ID of the key new records are sealed with, 0 if unencrypted.
+    pub fn key_id(&self) -> u64 {
+        self.cipher.as_ref().map_or(0, RecordCipher::key_id)
+    }
+
+    /// On-disk size of a record carrying `data_len` bytes of footage.
+    pub fn record_size(&self, data_len: usize) -> u64 {
+        let overhead = if self.cipher.is_some() { SEAL_OVERHEAD } else { 0 };
+        RECORD_HEADER_SIZE + data_len as u64 + overhead
+    }
+    pub fn write_stats(&self) -> WriteStats { self.stats }
 
     /// Read the raw MPEG-TS payload of a segment at the given location.
-    /// Returns only the data bytes (skips the 40-byte RecordHeader).
-    pub fn read_segment_data(&self, loc: &SegmentLocation) -> Result<Vec<u8>> {
+    /// Returns only the data bytes (skips the RecordHeader).
+    ///
+    /// The record header is checked against `camera_id` and `loc`, and the
+    /// on-disk pool header against `loc.pool_id`, so a location that has been
+    /// rotated out from under the caller (e.g. after `rotate` gave up waiting
+    /// for readers) returns [`NvrError::SegmentEvicted`] rather than whatever
+    /// another camera has since written there.
+    ///
+    /// With `verify_reads` on, the record's checksum is checked too; a
+    /// mismatch is returned as a storage error instead of serving corrupt
+    /// data.
+    pub fn read_segment_data(&self, camera_id: &str, loc: &SegmentLocation) -> Result<Vec<u8>> {
         let slot = &self.slots[loc.pool_idx];
-        let data_offset = loc.record_offset + RECORD_HEADER_SIZE;
-        let data_len = (loc.record_size - RECORD_HEADER_SIZE) as usize;
+        if !slot.online {
+            return Err(NvrError::Storage(format!(
+                "pool {} is offline (storage volume unavailable)",
+                loc.pool_idx
+            )));
+        }
+        let evicted = || NvrError::SegmentEvicted {
+            pool_idx: loc.pool_idx,
+            offset: loc.record_offset,
+        };
+        if slot.pool_id != loc.pool_id {
+            return Err(evicted());
+        }
 
         let mut f = BufReader::new(
             File::open(&slot.path)
                 .map_err(|e| NvrError::Storage(format!("open pool {:?}: {e}", slot.path)))?,
         );
-        f.seek(SeekFrom::Start(data_offset))?;
-        let mut buf = vec![0u8; data_len];
-        f.read_exact(&mut buf)?;
-        Ok(buf)
+
+        f.seek(SeekFrom::Start(loc.record_offset))?;
+        let mut record = vec![0u8; loc.record_size as usize];
+        // A short read means the pool was truncated or recreated smaller
+        // under us; any other failure is the disk's, not an eviction.
+        f.read_exact(&mut record).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => evicted(),
+            _ => NvrError::Io(e),
+        })?;
+
+        // Checked after the record is read: rotation rewrites the pool header
+        // before any new record, so if it still carries our pool_id nothing
+        // was overwritten while we were reading.
+        match Self::read_pool_header(&slot.path)? {
+            Some((pid, _)) if pid == loc.pool_id => {}
+            _ => return Err(evicted()),
+        }
+
+        let (raw, data) = record.split_at(loc.header_size as usize);
+        // Not a record header any more: something else was written here.
+        let mut header = RecordHeader::decode(raw).map_err(|_| evicted())?;
+        header.resolve_camera(&self.cameras);
+        if header.header_size() != loc.header_size
+            || header.data_len as u64 != loc.data_len()
+            // Records without a camera key only hold the stored prefix.
+            || (header.camera_id != camera_id && header.camera_id != stored_camera_id(camera_id))
+            || header.sequence != loc.sequence
+        {
+            warn!(
+                pool_idx = loc.pool_idx,
+                offset = loc.record_offset,
+                camera = camera_id,
+                "Record at segment location no longer matches, segment evicted"
+            );
+            return Err(evicted());
+        }
+
+        if self.verify_reads && !header.verify(raw, data) {
+            warn!(
+                pool_idx = loc.pool_idx,
+                offset = loc.record_offset,
+                "Record checksum mismatch on read, refusing to serve"
+            );
+            return Err(NvrError::Storage(format!(
+                "corrupt record at pool {} offset {}",
+                loc.pool_idx, loc.record_offset
+            )));
+        }
+        if header.flags & FLAG_ENCRYPTED != 0 {
+            return self.open_sealed(&slot.path, loc, raw, data);
+        }
+        Ok(data.to_vec())
+    }
+
+    /// Decrypt the payload of an encrypted record.
+    fn open_sealed(&self, path: &Path, loc: &SegmentLocation, raw: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+        let Some(cipher) = &self.cipher else {
+            return Err(NvrError::Storage(format!(
+                "record at pool {} offset {} is encrypted and no encryption key is configured",
+                loc.pool_idx, loc.record_offset
+            )));
+        };
+        let mut aad = raw.to_vec();
+        aad[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].fill(0);
+        cipher.open(&aad, sealed).map_err(|_| {
+            let written_with = Self::read_pool_key_id(path).unwrap_or(0);
+            warn!(
+                pool_idx = loc.pool_idx,
+                offset = loc.record_offset,
+                pool_key = format!("{written_with:016x}"),
+                key = format!("{:016x}", cipher.key_id()),
+                "Cannot decrypt record"
+            );
+            if written_with != 0 && written_with != cipher.key_id() {
+                NvrError::Storage(format!(
+                    "pool {} was written with encryption key {written_with:016x}, configured key is {:016x}",
+                    loc.pool_idx,
+                    cipher.key_id()
+                ))
+            } else {
+                NvrError::Storage(format!(
+                    "record at pool {} offset {} failed authentication (wrong key or tampered)",
+                    loc.pool_idx, loc.record_offset
+                ))
+            }
+        })
     }
 
     // ───────────────────── pool file scanning ─────────────────────────────
 
-    /// Read the 64-byte PoolHeader from a file. Returns `(pool_id, created_at)`.
-    fn read_pool_header(path: &Path) -> Result<(u64, i64)> {
+    /// Read the 64-byte PoolHeader from a file. Returns `(pool_id, created_at)`,
+    /// or `None` if the file has no valid header yet.
+    pub fn read_pool_header(path: &Path) -> Result<Option<(u64, i64)>> {
         let mut f = BufReader::new(
             File::open(path)
                 .map_err(|e| NvrError::Storage(format!("open {path:?}: {e}")))?,
@@ -354,11 +1494,28 @@ impl ChunkPool {
         f.read_exact(&mut magic)?;
         if &magic != POOL_MAGIC {
             // Fresh or corrupt — treat as empty.
-            return Ok((0, 0));
+            return Ok(None);
         }
         let pool_id = f.read_u64::<LittleEndian>()?;
         let created_at = f.read_i64::<LittleEndian>()?;
-        Ok((pool_id, created_at))
+        Ok(Some((pool_id, created_at)))
+    }
+
+    /// Key ID from a pool file's header (0 = unencrypted or unknown).
+    pub fn read_pool_key_id(path: &Path) -> Result<u64> {
+        let mut f = File::open(path)
+            .map_err(|e| NvrError::Storage(format!("open {path:?}: {e}")))?;
+        f.seek(SeekFrom::Start(KEY_ID_OFFSET))?;
+        Ok(f.read_u64::<LittleEndian>()?)
+    }
+
+    /// First sequence number of the pool's current ring cycle, from its
+    /// header (0 = unknown: written before it was recorded).
+    pub fn read_pool_first_sequence(path: &Path) -> Result<u64> {
+        let mut f = File::open(path)
+            .map_err(|e| NvrError::Storage(format!("open {path:?}: {e}")))?;
+        f.seek(SeekFrom::Start(FIRST_SEQUENCE_OFFSET))?;
+        Ok(f.read_u64::<LittleEndian>()?)
     }
 
     /// Sequentially scan all RecordHeaders in a pool file.
@@ -368,75 +1525,157 @@ impl ChunkPool {
         pool_idx: usize,
         pool_id: u64,
         pool_capacity: u64,
+        cameras: &CameraRegistry,
     ) -> Result<Vec<ScannedRecord>> {
+        Ok(Self::scan_pool(path, pool_idx, pool_id, pool_capacity, cameras)?.records)
+    }
+
+    /// Sequentially scan a pool file, verifying each record's checksum.
+    /// Records that fail verification are logged, reported in
+    /// [`PoolScan::corrupt`] and skipped; the scan continues past them.
+    /// Camera IDs are resolved through `cameras`.
+    pub fn scan_pool(
+        path: &Path,
+        pool_idx: usize,
+        pool_id: u64,
+        pool_capacity: u64,
+        cameras: &CameraRegistry,
+    ) -> Result<PoolScan> {
+        Self::scan_pool_from(path, pool_idx, pool_id, pool_capacity, POOL_HEADER_SIZE, None, cameras)
+    }
+
+    /// Cheap sanity check that a pool's snapshot entry still describes the
+    /// file: its last record's header must be on disk where the snapshot
+    /// says, for the same camera and size.
+    fn snapshot_matches(path: &Path, snap: &PoolSnapshot, cameras: &CameraRegistry) -> bool {
+        let Some(last) = snap.records.last() else {
+            return true;
+        };
+        let check = || -> Result<bool> {
+            let mut f = File::open(path)?;
+            f.seek(SeekFrom::Start(last.record_offset))?;
+            let Some(raw) = RecordHeader::read_raw(&mut f)? else {
+                return Ok(false);
+            };
+            let mut header = RecordHeader::decode(&raw)?;
+            header.resolve_camera(cameras);
+            Ok(header.camera_id == last.camera_id
+                && header.header_size() + header.data_len as u64 == last.record_size)
+        };
+        let ok = check().unwrap_or(false);
+        if !ok {
+            warn!(pool = snap.pool_idx, "Index snapshot does not match pool contents, rescanning");
+        }
+        ok
+    }
+
+    /// Like [`scan_pool`](Self::scan_pool), but starting at `start_offset`,
+    /// which must be a record boundary (or the end of the record chain).
+    /// `prev_sequence` is the sequence number of the record before it; with
+    /// none, the chain starts at the header's first sequence number.
+    fn scan_pool_from(
+        path: &Path,
+        pool_idx: usize,
+        pool_id: u64,
+        pool_capacity: u64,
+        start_offset: u64,
+        mut prev_sequence: Option<u64>,
+        cameras: &CameraRegistry,
+    ) -> Result<PoolScan> {
         let mut f = BufReader::new(
             File::open(path)
                 .map_err(|e| NvrError::Storage(format!("scan open {path:?}: {e}")))?,
         );
-        f.seek(SeekFrom::Start(POOL_HEADER_SIZE))?;
+        f.seek(SeekFrom::Start(start_offset))?;
+        if prev_sequence.is_none() {
+            prev_sequence = Self::read_pool_first_sequence(path)?.checked_sub(1);
+        }
 
-        let mut records = Vec::new();
-        let mut offset = POOL_HEADER_SIZE;
+        let mut scan = PoolScan {
+            bytes_used: start_offset - POOL_HEADER_SIZE,
+            ..PoolScan::default()
+        };
+        let mut offset = start_offset;
         let limit = POOL_HEADER_SIZE + pool_capacity;
+        let mut data = Vec::new();
 
-        while offset + RECORD_HEADER_SIZE <= limit {
-            // Try to read record magic.
-            let mut magic = [0u8; 4];
-            if f.read_exact(&mut magic).is_err() {
+        while offset + LEGACY_RECORD_HEADER_SIZE <= limit {
+            // No more valid records (hit zero-fill or garbage).
+            let Some(raw) = RecordHeader::read_raw(&mut f)? else {
                 break;
-            }
-            if &magic != RECORD_MAGIC {
-                // No more valid records (hit zero-fill or garbage).
-                break;
-            }
-
-            let mut cam_bytes = [0u8; 16];
-            f.read_exact(&mut cam_bytes)?;
-            let camera_id = std::str::from_utf8(&cam_bytes)
-                .unwrap_or("")
-                .trim_end_matches('\0')
-                .to_string();
+            };
+            let mut header = RecordHeader::decode(&raw)?;
+            header.resolve_camera(cameras);
 
-            let start_ts_unix = f.read_i64::<LittleEndian>()?;
-            let end_ts_unix = f.read_i64::<LittleEndian>()?;
-            let data_len = f.read_u32::<LittleEndian>()? as u64;
-
-            let record_size = RECORD_HEADER_SIZE + data_len;
+            let header_size = header.header_size();
+            let record_size = header_size + header.data_len as u64;
             if offset + record_size > limit {
                 break; // Partial record — don't trust.
             }
 
-            let start_ts = Utc.timestamp_opt(start_ts_unix, 0)
-                .single()
-                .unwrap_or_else(Utc::now);
-            let end_ts = Utc.timestamp_opt(end_ts_unix, 0)
-                .single()
-                .unwrap_or_else(Utc::now);
-
-            records.push(ScannedRecord {
-                camera_id,
-                start_ts,
-                end_ts,
+            if header.checksum.is_some() {
+                data.resize(header.data_len as usize, 0);
+                if f.read_exact(&mut data).is_err() {
+                    break; // Truncated file.
+                }
+                if !header.verify(&raw, &data) {
+                    warn!(
+                        path = ?path,
+                        offset,
+                        camera = header.camera_id,
+                        "Skipping record with bad checksum (torn or corrupt write)"
+                    );
+                    scan.corrupt.push(CorruptRecord {
+                        pool_idx,
+                        record_offset: offset,
+                        record_size,
+                        camera_id: header.camera_id,
+                    });
+                    offset += record_size;
+                    continue;
+                }
+            } else {
+                // Skip over the data payload.
+                f.seek(SeekFrom::Current(header.data_len as i64))?;
+            }
+
+            if !continues_chain(prev_sequence, header.sequence) {
+                debug!(path = ?path, offset, "Stale record from a previous ring cycle, end of chain");
+                break;
+            }
+            prev_sequence = header.sequence;
+
+            scan.records.push(ScannedRecord {
+                camera_id: header.camera_id,
+                start_ts: header.start_ts,
+                end_ts: header.end_ts,
                 pool_idx,
                 pool_id,
                 record_offset: offset,
                 record_size,
+                header_size,
+                sequence: header.sequence,
+                stream: header.stream,
             });
-
-            // Skip over the data payload.
-            f.seek(SeekFrom::Current(data_len as i64))?;
             offset += record_size;
+            scan.bytes_used = offset - POOL_HEADER_SIZE;
         }
 
-        debug!(path = ?path, records = records.len(), "Pool scan complete");
-        Ok(records)
+        debug!(
+            path = ?path,
+            records = scan.records.len(),
+            corrupt = scan.corrupt.len(),
+            "Pool scan complete"
+        );
+        Ok(scan)
     }
 
     /// Scan all pool files and return every recovered record, sorted by pool_id.
     pub fn scan_all_pools(&self) -> Result<Vec<ScannedRecord>> {
         let mut all = Vec::new();
-        for (i, slot) in self.slots.iter().enumerate() {
-            let recs = Self::scan_records(&slot.path, i, slot.pool_id, self.pool_capacity)?;
+        for (i, slot) in self.slots.iter().enumerate().filter(|(_, s)| s.online) {
+            let recs =
+                Self::scan_records(&slot.path, i, slot.pool_id, self.pool_capacity, &self.cameras)?;
             all.extend(recs);
         }
         // Sort by pool_id (chronological order across rotations).
@@ -444,3 +1683,163 @@ impl ChunkPool {
         Ok(all)
     }
 }
+
+impl ActiveFile {
+    fn open(path: &Path, direct_io: bool) -> Result<Self> {
+        if direct_io {
+            match open_direct(path) {
+                Ok(file) => return Ok(ActiveFile { file, direct: true, tail: None }),
+                Err(e) => warn!(path = ?path, error = %e, "O_DIRECT unavailable, using buffered writes"),
+            }
+        }
+        let file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map_err(|e| NvrError::Storage(format!("open pool {path:?}: {e}")))?;
+        Ok(ActiveFile { file, direct: false, tail: None })
+    }
+
+    /// Write `buf` at `offset` of the file at `path`, which is `file_len`
+    /// bytes long. Returns whether the write went out with `O_DIRECT`.
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    fn write_at(&mut self, path: &Path, offset: u64, buf: &[u8], file_len: u64) -> Result<bool> {
+        #[cfg(target_os = "linux")]
+        if self.direct {
+            return self.write_direct(path, offset, buf, file_len);
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(buf)?;
+        Ok(false)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn write_direct(&mut self, path: &Path, offset: u64, buf: &[u8], file_len: u64) -> Result<bool> {
+        use std::os::unix::fs::FileExt;
+
+        let start = offset - offset % DIRECT_IO_ALIGN;
+        let end = offset + buf.len() as u64;
+        let padded_end = end.next_multiple_of(DIRECT_IO_ALIGN);
+        if padded_end > file_len {
+            // The last block of a pool file is partial, and padding it would
+            // grow the file: write it through the page cache instead.
+            self.tail = None;
+            OpenOptions::new().write(true).open(path)?.write_all_at(buf, offset)?;
+            return Ok(false);
+        }
+
+        // The buffer has to be aligned in memory too: over-allocate and
+        // start at the first aligned byte.
+        let head = (offset - start) as usize;
+        let len = (padded_end - start) as usize;
+        let mut raw = vec![0u8; len + DIRECT_IO_ALIGN as usize];
+        let skip = raw.as_ptr().align_offset(DIRECT_IO_ALIGN as usize);
+        let block = &mut raw[skip..skip + len];
+        if head > 0 {
+            match &self.tail {
+                Some((at, data)) if *at == start => block[..head].copy_from_slice(&data[..head]),
+                _ => File::open(path)?.read_exact_at(&mut block[..head], start)?,
+            }
+        }
+        block[head..head + buf.len()].copy_from_slice(buf);
+        self.file.write_all_at(block, start)?;
+
+        let tail_start = end - end % DIRECT_IO_ALIGN;
+        self.tail = (tail_start < padded_end)
+            .then(|| (tail_start, block[(tail_start - start) as usize..].to_vec()));
+        Ok(true)
+    }
+}
+
+/// The part of `camera_id` a record header's 16-byte field holds: cut on a
+/// char boundary so the prefix stays valid UTF-8.
+fn stored_camera_id(camera_id: &str) -> &str {
+    let mut n = camera_id.len().min(16);
+    while !camera_id.is_char_boundary(n) {
+        n -= 1;
+    }
+    &camera_id[..n]
+}
+
+/// Whether a write error means the medium itself failed (`EIO`), which
+/// retrying won't fix.
+fn is_media_error(err: &NvrError) -> bool {
+    #[cfg(target_os = "linux")]
+    if let NvrError::Io(e) = err {
+        return e.raw_os_error() == Some(libc::EIO);
+    }
+    let _ = err;
+    false
+}
+
+#[cfg(target_os = "linux")]
+fn open_direct(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new().write(true).custom_flags(libc::O_DIRECT).open(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_direct(_path: &Path) -> std::io::Result<File> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "O_DIRECT is only supported on Linux",
+    ))
+}
+
+/// Path of pool slot `idx`: striped across `volumes` in ring order, or in
+/// `base_path` if there are none.
+pub fn pool_file_path(base_path: &Path, volumes: &[PathBuf], idx: usize) -> PathBuf {
+    let dir = if volumes.is_empty() {
+        base_path
+    } else {
+        &volumes[idx % volumes.len()]
+    };
+    dir.join(format!("pool_{:03}.bin", idx))
+}
+
+/// Slot index from a `pool_XXX.bin` file name.
+pub fn pool_file_index(path: &Path) -> Option<usize> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_prefix("pool_")?.strip_suffix(".bin")?.parse().ok()
+}
+
+/// Write a fresh PoolHeader for `pool_id` into an existing pool file.
+pub(crate) fn write_pool_header_at(
+    path: &Path,
+    pool_id: u64,
+    key_id: u64,
+    first_sequence: u64,
+) -> Result<()> {
+    let mut f = OpenOptions::new().write(true).open(path)
+        .map_err(|e| NvrError::Storage(format!("header open {path:?}: {e}")))?;
+    f.seek(SeekFrom::Start(0))?;
+    f.write_all(POOL_MAGIC)?;
+    f.write_u64::<LittleEndian>(pool_id)?;
+    f.write_i64::<LittleEndian>(Utc::now().timestamp())?;
+    f.write_u64::<LittleEndian>(key_id)?;
+    f.write_u64::<LittleEndian>(first_sequence)?;
+    f.write_all(&[0u8; 24])?; // reserved
+    f.flush()?;
+    Ok(())
+}
+
+/// Whether a record with sequence `next` can follow one with `prev` in the
+/// same pool. Sequence numbers only grow within a pool, so a record that
+/// breaks that is stale data from before the pool was last rotated into —
+/// left behind by a longer record chain that happened to end on the same
+/// boundary — and marks the end of the chain.
+pub fn continues_chain(prev: Option<u64>, next: Option<u64>) -> bool {
+    match (prev, next) {
+        (Some(p), Some(n)) => n > p,
+        (Some(_), None) => false,
+        (None, _) => true,
+    }
+}
+
+/// The sequence number to resume from after `records`: past the highest one
+/// stored, and past the IDs the index gives records that have none (see
+/// [`SegmentIndex::rebuild_from_scanned`](crate::storage::index::SegmentIndex::rebuild_from_scanned)).
+fn next_sequence(records: &[ScannedRecord]) -> u64 {
+    let unnumbered = records.iter().filter(|r| r.sequence.is_none()).count() as u64;
+    let next = records.iter().filter_map(|r| r.sequence).max().map_or(0, |s| s + 1);
+    next.max(unnumbered)
+}
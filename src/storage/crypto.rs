@@ -0,0 +1,129 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Encryption at rest for record payloads.
+//!
+//! With `encryption_key_file` set, every new record's payload is sealed with
+//! AES-256-GCM before it is written:
+//!
+//! ```text
+//! [nonce : 12 bytes, random per record]
+//! [ciphertext : as long as the footage]
+//! [tag   : 16 bytes]
+//! ```
+//!
+//! The record header itself stays in the clear (with bit 0 of `flags` set)
+//! and is the associated data, so a payload can't be moved under another
+//! camera, time or sequence number without failing authentication. The
+//! header checksum covers the sealed bytes, so pools are scanned, checked
+//! and migrated without the key, whether they hold encrypted records,
+//! plain ones, or both.
+//!
+//! Each pool header records the [key ID](RecordCipher::key_id) its records
+//! were written with (0 = none), so a read with the wrong key can say so.
+//!
+//! The key file holds the 32-byte key, raw or as 64 hex digits.
+
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+use crate::config::StorageConfig;
+use crate::error::{NvrError, Result};
+
+/// Bytes a sealed payload adds to the footage: nonce + tag.
+pub const SEAL_OVERHEAD: u64 = 12 + 16;
+const NONCE_SIZE: usize = 12;
+/// Associated data of the key check value, never used for a record.
+const KEY_CHECK_AAD: &[u8] = b"oasis-nvr key check";
+
+/// Seals and opens record payloads with one key.
+#[derive(Clone)]
+pub struct RecordCipher {
+    cipher: Aes256Gcm,
+    key_id: u64,
+}
+
+impl std::fmt::Debug for RecordCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordCipher").field("key_id", &format_args!("{:016x}", self.key_id)).finish()
+    }
+}
+
+impl RecordCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+        // Key check value: identifies the key without revealing it.
+        let check = cipher
+            .encrypt(Nonce::from_slice(&[0u8; NONCE_SIZE]), Payload { msg: &[], aad: KEY_CHECK_AAD })
+            .expect("empty message always encrypts");
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&check[..8]);
+        RecordCipher { cipher, key_id: u64::from_le_bytes(id).max(1) }
+    }
+
+    /// Load the key from `path`: 32 raw bytes or 64 hex digits.
+    pub fn from_key_file(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| NvrError::Config(format!("Cannot read encryption key {path:?}: {e}")))?;
+        let key = parse_key(&bytes).ok_or_else(|| {
+            NvrError::Config(format!(
+                "Encryption key {path:?} must be 32 bytes, or 64 hex digits"
+            ))
+        })?;
+        Ok(Self::new(&key))
+    }
+
+    /// The cipher for `storage.encryption_key_file`, if one is configured.
+    pub fn from_config(storage: &StorageConfig) -> Result<Option<Self>> {
+        storage.encryption_key_file.as_deref().map(Self::from_key_file).transpose()
+    }
+
+    /// Nonzero ID of the key, stored in pool headers.
+    pub fn key_id(&self) -> u64 {
+        self.key_id
+    }
+
+    /// Encrypt `data`, authenticating `aad` along with it.
+    pub fn seal(&self, aad: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let sealed = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: data, aad })
+            .map_err(|_| NvrError::Storage("payload encryption failed".into()))?;
+        let mut out = Vec::with_capacity(NONCE_SIZE + sealed.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    /// Decrypt a payload sealed by [`seal`](Self::seal) with the same `aad`.
+    /// Fails if it was sealed with another key or has been tampered with.
+    pub fn open(&self, aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < SEAL_OVERHEAD as usize {
+            return Err(NvrError::Storage("sealed payload too short".into()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|_| NvrError::Storage("payload authentication failed".into()))
+    }
+}
+
+fn parse_key(bytes: &[u8]) -> Option<[u8; 32]> {
+    if let Ok(key) = <[u8; 32]>::try_from(bytes) {
+        return Some(key);
+    }
+    let hex = std::str::from_utf8(bytes).ok()?.trim();
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(key)
+}
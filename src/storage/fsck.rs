@@ -0,0 +1,317 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Offline pool file checker (`oasis fsck`).
+//!
+//! Walks every `pool_XXX.bin` under the storage directory (or volumes) **without**
+//! opening a [`ChunkPool`](crate::storage::chunk_pool::ChunkPool) (which
+//! would pre-allocate missing files) and validates:
+//!
+//!   - the `NVRPOOL0` pool header,
+//!   - pool_id consistency across slots (each slot's ID must be congruent to
+//!     its index modulo the ring size, and unique),
+//!   - record boundaries (every record must fit inside the pool),
+//!   - record timestamps (`end_ts >= start_ts`, not in the far future),
+//!   - payload checksums, where the record format carries one.
+//!
+//! With `repair`, each pool is truncated at its first bad record by
+//! zero-filling the tail. The write pointer is derived from the record chain
+//! on the next `ChunkPool::open`, so this also puts it back right after the
+//! last good record. The index snapshot is deleted after a repair, since it
+//! may describe records that no longer exist. Must only be run while the
+//! recorder is stopped.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::error::{NvrError, Result};
+use crate::storage::chunk_pool::{
+    continues_chain, pool_file_index, pool_file_path, ChunkPool, RecordHeader, LEGACY_RECORD_HEADER_SIZE,
+    POOL_HEADER_SIZE,
+};
+use crate::storage::snapshot::snapshot_path;
+
+/// Zero-fill granularity for `--repair`.
+const ZERO_BLOCK: usize = 1024 * 1024;
+
+/// Findings for a single pool file.
+#[derive(Debug, Clone)]
+pub struct PoolReport {
+    pub pool_idx: usize,
+    pub path: PathBuf,
+    /// `None` if the file has no valid pool header (never written, or damaged).
+    pub pool_id: Option<u64>,
+    /// Records that passed every check.
+    pub good_records: usize,
+    /// Good records that carry no checksum (legacy / version 1).
+    pub unverified_records: usize,
+    /// Bytes used after the header, up to the end of the last good record.
+    pub bytes_used: u64,
+    /// Offset of the first bad record, if any. Everything from here on is
+    /// untrusted.
+    pub first_bad_offset: Option<u64>,
+    /// Human-readable problems, in the order found.
+    pub problems: Vec<String>,
+    /// A problem `repair` can't fix (bad pool header or pool_id).
+    pub unrepairable: bool,
+    /// Whether `repair` zero-filled this pool's tail.
+    pub repaired: bool,
+}
+
+impl PoolReport {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Findings for the whole pool set.
+#[derive(Debug, Clone, Default)]
+pub struct FsckReport {
+    pub pools: Vec<PoolReport>,
+    /// Problems that span slots (e.g. duplicate pool IDs).
+    pub problems: Vec<String>,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty() && self.pools.iter().all(|p| p.is_clean())
+    }
+
+    /// True if every problem that `repair` can fix has been fixed.
+    pub fn is_repaired(&self) -> bool {
+        self.problems.is_empty()
+            && self
+                .pools
+                .iter()
+                .all(|p| p.is_clean() || (p.repaired && !p.unrepairable))
+    }
+}
+
+/// Check (and with `repair`, fix) every `pool_XXX.bin` in `base_path`, or
+/// in each of `volumes` if pools are striped. `max_pools` is the configured
+/// ring size, used for the pool_id checks.
+pub fn check_pools(
+    base_path: &Path,
+    volumes: &[PathBuf],
+    max_pools: usize,
+    repair: bool,
+) -> Result<FsckReport> {
+    let mut report = FsckReport::default();
+    let dirs = if volumes.is_empty() {
+        vec![base_path.to_path_buf()]
+    } else {
+        volumes.to_vec()
+    };
+
+    let mut paths: Vec<(usize, PathBuf)> = Vec::new();
+    for dir in &dirs {
+        if !dir.is_dir() {
+            report.problems.push(format!("storage volume {} is missing", dir.display()));
+            continue;
+        }
+        for entry in std::fs::read_dir(dir)
+            .map_err(|e| NvrError::Storage(format!("read dir {dir:?}: {e}")))?
+        {
+            let path = entry?.path();
+            let Some(idx) = pool_file_index(&path) else { continue };
+            if pool_file_path(base_path, volumes, idx) != path {
+                report.problems.push(format!(
+                    "{} is on the wrong volume for slot {idx}",
+                    path.display()
+                ));
+            }
+            paths.push((idx, path));
+        }
+    }
+    paths.sort();
+
+    for (idx, path) in paths {
+        let mut pool = check_pool(&path, idx, max_pools)?;
+        if repair {
+            if let Some(bad) = pool.first_bad_offset {
+                zero_fill_tail(&path, bad)?;
+                pool.repaired = true;
+                info!(pool = idx, offset = bad, "Zero-filled pool tail from first bad record");
+            }
+        }
+        report.pools.push(pool);
+    }
+
+    if report.pools.iter().any(|p| p.repaired) {
+        match std::fs::remove_file(snapshot_path(base_path)) {
+            Ok(()) => info!("Removed index snapshot after repair"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let mut seen = std::collections::HashMap::new();
+    for pool in &report.pools {
+        if let Some(pid) = pool.pool_id {
+            if let Some(other) = seen.insert(pid, pool.pool_idx) {
+                report.problems.push(format!(
+                    "pool_{:03}.bin and pool_{:03}.bin share pool_id {pid}",
+                    other, pool.pool_idx
+                ));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Validate one pool file. Does not modify it.
+fn check_pool(path: &Path, pool_idx: usize, max_pools: usize) -> Result<PoolReport> {
+    let mut report = PoolReport {
+        pool_idx,
+        path: path.to_path_buf(),
+        pool_id: None,
+        good_records: 0,
+        unverified_records: 0,
+        bytes_used: 0,
+        first_bad_offset: None,
+        problems: Vec::new(),
+        unrepairable: false,
+        repaired: false,
+    };
+
+    let file_len = std::fs::metadata(path)?.len();
+    if file_len < POOL_HEADER_SIZE {
+        report.problems.push(format!("file is {file_len} bytes, shorter than the pool header"));
+        report.unrepairable = true;
+        return Ok(report);
+    }
+
+    match ChunkPool::read_pool_header(path)? {
+        Some((pid, _created)) => {
+            if max_pools > 0 && pid % max_pools as u64 != pool_idx as u64 % max_pools as u64 {
+                report.problems.push(format!(
+                    "pool_id {pid} does not belong to slot {pool_idx} of a {max_pools}-pool ring"
+                ));
+                report.unrepairable = true;
+            }
+            report.pool_id = Some(pid);
+        }
+        None => {
+            // A pre-allocated slot the ring has not reached yet is all
+            // zeros; anything else without a header is damage.
+            if !is_zero_from(path, 0)? {
+                report.problems.push("missing or damaged pool header".into());
+                report.unrepairable = true;
+            }
+            return Ok(report);
+        }
+    }
+
+    let capacity = file_len - POOL_HEADER_SIZE;
+    let limit = POOL_HEADER_SIZE + capacity;
+    let far_future = Utc::now() + chrono::Duration::days(1);
+
+    let mut f = BufReader::new(File::open(path)?);
+    f.seek(SeekFrom::Start(POOL_HEADER_SIZE))?;
+    let mut offset = POOL_HEADER_SIZE;
+    let mut data = Vec::new();
+    let mut prev_sequence = None;
+
+    while offset + LEGACY_RECORD_HEADER_SIZE <= limit {
+        // End of the record chain. Whatever follows is zero-fill or stale
+        // bytes from the previous trip around the ring, both expected.
+        let Some(raw) = RecordHeader::read_raw(&mut f)? else {
+            break;
+        };
+        let header = RecordHeader::decode(&raw)?;
+        let record_size = header.header_size() + header.data_len as u64;
+
+        let problem = if offset + record_size > limit {
+            Some(format!(
+                "record at offset {offset} ({record_size} bytes) runs past the end of the pool"
+            ))
+        } else if header.camera_id.is_empty() {
+            Some(format!("record at offset {offset} has an empty or non-UTF-8 camera ID"))
+        } else if header.end_ts < header.start_ts || header.start_ts > far_future {
+            Some(format!(
+                "record at offset {offset} has implausible timestamps ({} → {})",
+                header.start_ts, header.end_ts
+            ))
+        } else {
+            data.resize(header.data_len as usize, 0);
+            if f.read_exact(&mut data).is_err() {
+                Some(format!("record at offset {offset} is truncated"))
+            } else if !header.verify(&raw, &data) {
+                Some(format!("record at offset {offset} has a bad checksum"))
+            } else {
+                None
+            }
+        };
+
+        if let Some(problem) = problem {
+            warn!(pool = pool_idx, offset, "{problem}");
+            report.first_bad_offset = Some(offset);
+            report.problems.push(problem);
+            break;
+        }
+        if !continues_chain(prev_sequence, header.sequence) {
+            break; // Stale record from the previous trip around the ring.
+        }
+
+        if header.checksum.is_none() {
+            report.unverified_records += 1;
+        }
+        report.good_records += 1;
+        prev_sequence = header.sequence;
+        offset += record_size;
+        report.bytes_used = offset - POOL_HEADER_SIZE;
+    }
+
+    Ok(report)
+}
+
+/// True if the first block of `path` from `offset` is all zeros — i.e.
+/// nothing has been written there.
+fn is_zero_from(path: &Path, offset: u64) -> Result<bool> {
+    let mut f = File::open(path)?;
+    f.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0u8; ZERO_BLOCK];
+    let n = read_block(&mut f, &mut buf)?;
+    Ok(buf[..n].iter().all(|&b| b == 0))
+}
+
+/// Zero-fill `path` from `offset` onwards. Pool data is one contiguous run
+/// from the header, so filling stops at the first block that is already all
+/// zeros instead of rewriting the whole (possibly multi-GB) tail.
+fn zero_fill_tail(path: &Path, offset: u64) -> Result<()> {
+    let mut f = OpenOptions::new().read(true).write(true).open(path)?;
+    let zeros = vec![0u8; ZERO_BLOCK];
+    let mut buf = vec![0u8; ZERO_BLOCK];
+    let mut pos = offset;
+    loop {
+        f.seek(SeekFrom::Start(pos))?;
+        let n = read_block(&mut f, &mut buf)?;
+        if n == 0 || buf[..n].iter().all(|&b| b == 0) {
+            break;
+        }
+        f.seek(SeekFrom::Start(pos))?;
+        f.write_all(&zeros[..n])?;
+        pos += n as u64;
+    }
+    f.sync_all()?;
+    Ok(())
+}
+
+/// Fill `buf` as far as possible; returns bytes read (short only at EOF).
+fn read_block(f: &mut File, buf: &mut [u8]) -> Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match f.read(&mut buf[n..])? {
+            0 => break,
+            k => n += k,
+        }
+    }
+    Ok(n)
+}
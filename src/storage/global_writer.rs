@@ -3,13 +3,13 @@
 // If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
 // (c) 2026 Onur Tuna. All rights reserved.
 
-//! Global chunk writer — single async task that serialises all camera segment
-//! writes into one sequential I/O stream.
+//! Global chunk writer — a single dedicated I/O thread that serialises all
+//! camera segment writes into one sequential I/O stream.
 //!
 //! ```text
 //! cam1_worker ─┐
 //! cam2_worker ─┤       mpsc
-//! cam3_worker ─┼────→ channel ────→  GlobalChunkWriter task
+//! cam3_worker ─┼────→ channel ────→  GlobalChunkWriter thread
 //! ...          ─┘                         │
 //!                                         ▼
 //!                              pool_000.bin, pool_001.bin …
@@ -20,22 +20,50 @@
 //!
 //! Each camera worker sends a [`WriteRequest`] through a bounded `mpsc`
 //! channel. The writer drains the channel in order and appends records to the
-//! current pool file, rotating when full.
+//! current pool file, rotating when full. Before a pool is overwritten,
+//! segments protected by the [retention policy](crate::storage::retention)
+//! are read back and relocated into the new pool.
+//!
+//! Requests already queued behind the one being written are coalesced into
+//! a single write (up to `coalesce_max_records`, and never across a pool
+//! rotation), and in `interval` durability mode the writer syncs pending
+//! writes once their deadline passes, even if no new request arrives.
+//!
+//! With a [replica](crate::storage::replica) configured, every segment is
+//! handed on to the replica thread once it is written to the primary; with
+//! an [archive](crate::storage::archive), a pool's segments are copied into
+//! it before a rotation evicts them.
+//!
+//! The writer runs on its own OS thread rather than a Tokio task: pool
+//! writes, syncs and the wait for readers before a rotation are blocking,
+//! and must neither stall runtime threads nor hold the pool lock that API
+//! handlers read through. Camera workers still send asynchronously.
 
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
 use tokio::sync::mpsc;
-use tracing::{debug, error, info};
+use tokio::sync::mpsc::error::TryRecvError;
+use tracing::{debug, error, info, warn};
 
-use crate::storage::chunk_pool::ChunkPool;
-use crate::storage::index::SegmentIndex;
+use crate::error::Result;
+use crate::storage::archive::{Archive, SharedArchive};
+use crate::storage::chunk_pool::{
+    ChunkPool, NewRecord, PoolReadCounters, SegmentLocation, Stream,
+};
+use crate::storage::index::{SegmentIndex, SegmentMeta};
+use crate::storage::replica::Replicator;
+use crate::storage::retention::{SharedRetention, RELOCATION_BUDGET_DIVISOR};
+use crate::storage::snapshot::{snapshot_path, IndexSnapshot};
 
 /// Payload sent by camera workers to the global writer.
 #[derive(Debug)]
 pub struct WriteRequest {
     pub camera_id: String,
+    pub stream: Stream,
     pub start_ts: DateTime<Utc>,
     pub end_ts: DateTime<Utc>,
     pub data: Vec<u8>,
@@ -44,106 +72,455 @@ pub struct WriteRequest {
 /// Shared handle through which workers and the CLI can query the index.
 pub type SharedIndex = Arc<RwLock<SegmentIndex>>;
 
-/// Create the writer channel and spawn the writer task.
+/// Optional stores the writer feeds besides the primary ring.
+#[derive(Default)]
+pub struct WriterHooks {
+    /// Receives every segment once it is written.
+    pub replicator: Option<Replicator>,
+    /// Receives a pool's segments before a rotation evicts them.
+    pub archive: Option<SharedArchive>,
+}
+
+/// How long a rotation waits for readers of the pool it is about to
+/// overwrite before going ahead anyway.
+const READER_WAIT: Duration = Duration::from_secs(5);
+
+/// Longest the idle writer sleeps between checks for new requests while a
+/// sync is pending.
+const SYNC_POLL: Duration = Duration::from_millis(20);
+
+/// Create the writer channel and spawn the writer thread.
 ///
-/// On startup the pool files are scanned sequentially to rebuild the
-/// in-memory segment index from existing RecordHeaders. This makes
-/// the index persistent across restarts — no separate index file needed.
+/// On startup the in-memory segment index is rebuilt from the records
+/// `ChunkPool::open` recovered — from the index snapshot where it is still
+/// valid, otherwise by scanning RecordHeaders. If the pool has snapshots
+/// enabled, a fresh one is written at every pool rotation.
 ///
 /// Returns:
 ///   - `mpsc::Sender<WriteRequest>` — hand out clones to each camera worker.
 ///   - `SharedIndex` — read-only handle for status / listing.
-///   - `Arc<PoolReadCounters>` — shared pool reader counters for safe reads.
-///   - `JoinHandle` for the writer task.
+///   - `JoinHandle` for the writer thread, which exits once every sender
+///     has been dropped and the channel is drained.
 pub fn spawn_writer(
     pool: Arc<RwLock<ChunkPool>>,
+    retention: SharedRetention,
     channel_bound: usize,
-) -> (
-    mpsc::Sender<WriteRequest>,
-    SharedIndex,
-    tokio::task::JoinHandle<()>,
-) {
+) -> (mpsc::Sender<WriteRequest>, SharedIndex, JoinHandle<()>) {
+    spawn_writer_with(pool, retention, channel_bound, WriterHooks::default())
+}
+
+/// [`spawn_writer`], also feeding the replica and archive in `hooks`.
+pub fn spawn_writer_with(
+    pool: Arc<RwLock<ChunkPool>>,
+    retention: SharedRetention,
+    channel_bound: usize,
+    hooks: WriterHooks,
+) -> (mpsc::Sender<WriteRequest>, SharedIndex, JoinHandle<()>) {
     let (tx, rx) = mpsc::channel::<WriteRequest>(channel_bound);
     let index = Arc::new(RwLock::new(SegmentIndex::new()));
     let idx_clone = index.clone();
 
-    let handle = tokio::spawn(async move {
-        writer_loop(pool, rx, idx_clone).await;
-    });
+    let handle = std::thread::Builder::new()
+        .name("nvr-writer".into())
+        .spawn(move || writer_loop(pool, retention, rx, idx_clone, hooks))
+        .expect("spawn writer thread");
 
     (tx, index, handle)
 }
 
-async fn writer_loop(
+fn writer_loop(
     pool: Arc<RwLock<ChunkPool>>,
+    retention: SharedRetention,
     mut rx: mpsc::Receiver<WriteRequest>,
     index: SharedIndex,
+    hooks: WriterHooks,
 ) {
-    // Rebuild index from existing pool data (sequential scan, one-time).
-    let records_res = {
-        let p = pool.read();
-        p.scan_all_pools()
-    };
-    
-    match records_res {
-        Ok(records) => {
-            let count = records.len();
-            index.write().rebuild_from_scanned(records);
-            if count > 0 {
-                info!(recovered = count, "Index rebuilt from pool files");
-            }
-        }
-        Err(e) => {
-            error!(error = %e, "Failed to scan pool files, starting with empty index");
-        }
+    // Rebuild index from the records recovered when the pool was opened.
+    let records = pool.write().take_recovered();
+    let count = records.len();
+    index.write().rebuild_from_scanned(records);
+    if count > 0 {
+        info!(recovered = count, "Index rebuilt from pool files");
     }
 
     info!("GlobalChunkWriter started");
 
-    while let Some(req) = rx.recv().await {
-        let camera_id = req.camera_id.clone();
-        let data_len = req.data.len();
+    let read_counters = pool.read().read_counters.clone();
+
+    // A request taken off the queue that didn't fit the last batch.
+    let mut held_back: Option<WriteRequest> = None;
+
+    while let Some(req) = held_back.take().or_else(|| next_request(&pool, &mut rx)) {
 
-        // Check if rotation will happen and evict first.
-        let (cur_idx, used, cap) = {
+        // Check if rotation will happen (pool full, or its slot
+        // quarantined) and evict first.
+        let (cur_idx, used, cap, writable) = {
             let p = pool.read();
-            p.status()
+            let (idx, used, cap) = p.status();
+            (idx, used, cap, p.is_writable(idx))
         };
-        
-        let record_size = crate::storage::chunk_pool::RECORD_HEADER_SIZE + data_len as u64;
-        if used + record_size > cap {
-            // Next pool slot will be overwritten.
-            let next_idx = (cur_idx + 1) % pool.read().pool_count();
-            index.write().evict_pool(next_idx);
+
+        let record_size = pool.read().record_size(req.data.len());
+        let mut relocations = Vec::new();
+        let mut batch = vec![req];
+        if !writable || used + record_size > cap {
+            relocations = prepare_rotation(
+                &pool,
+                &index,
+                &retention,
+                &read_counters,
+                hooks.archive.as_deref(),
+                cap.saturating_sub(record_size),
+            );
+        } else {
+            // Coalesce whatever else is queued and still fits this pool.
+            let max = pool.read().durability().coalesce_max_records;
+            let mut room = cap - used - record_size;
+            while batch.len() < max {
+                let Ok(next) = rx.try_recv() else {
+                    break;
+                };
+                let size = pool.read().record_size(next.data.len());
+                if size > room {
+                    held_back = Some(next);
+                    break;
+                }
+                room -= size;
+                batch.push(next);
+            }
         }
 
-        let append_res = {
-            let mut p = pool.write();
-            p.append(&camera_id, req.start_ts, req.end_ts, &req.data)
+        let mut append_res = append_and_index(&pool, &index, &batch);
+        let quarantined = {
+            let p = pool.read();
+            !p.is_writable(p.write_idx)
         };
-        
+        if append_res.is_err() && quarantined {
+            // The slot failed under the write: move on to the next one, as
+            // at a rotation, and try once more there.
+            warn!(segments = batch.len(), "Retrying write in the next pool slot");
+            relocations.extend(prepare_rotation(
+                &pool,
+                &index,
+                &retention,
+                &read_counters,
+                hooks.archive.as_deref(),
+                cap.saturating_sub(record_size),
+            ));
+            append_res = append_and_index(&pool, &index, &batch);
+        }
+
         match append_res {
-            Ok(loc) => {
-                let seg_id = index.write().insert(
-                    &camera_id,
-                    req.start_ts,
-                    req.end_ts,
-                    loc.clone(),
-                );
-                debug!(
-                    camera = camera_id,
-                    segment_id = seg_id,
-                    pool_idx = loc.pool_idx,
-                    offset = loc.record_offset,
-                    bytes = data_len,
-                    "Segment written"
-                );
+            Ok(written) => {
+                if !relocations.is_empty() {
+                    relocate(&pool, &index, relocations);
+                }
+                if written.iter().any(|(loc, _)| loc.pool_idx != cur_idx) {
+                    save_snapshot(&pool, &index);
+                }
+                for ((loc, seg_id), r) in written.iter().zip(&batch) {
+                    debug!(
+                        camera = r.camera_id,
+                        segment_id = seg_id,
+                        pool_idx = loc.pool_idx,
+                        offset = loc.record_offset,
+                        bytes = r.data.len(),
+                        batch = batch.len(),
+                        "Segment written"
+                    );
+                }
+                // The replica is a second copy of the recording of record;
+                // substreams are only for live viewing.
+                if let Some(replicator) = &hooks.replicator {
+                    for r in batch.into_iter().filter(|r| r.stream == Stream::Main) {
+                        replicator.mirror(r);
+                    }
+                }
             }
             Err(e) => {
-                error!(camera = camera_id, error = %e, "Failed to write segment to pool");
+                for r in &batch {
+                    error!(camera = r.camera_id, error = %e, "Failed to write segment to pool");
+                }
             }
         }
     }
 
+    if let Err(e) = sync_pool(&pool) {
+        error!(error = %e, "Failed to sync pool on shutdown");
+    }
+    let p = pool.read();
+    let stats = p.write_stats();
+    info!(
+        records = stats.records,
+        bytes = stats.bytes,
+        writes = stats.writes,
+        direct_writes = stats.direct_writes,
+        syncs = stats.syncs,
+        sync_ms = stats.sync_time.as_millis() as u64,
+        "Pool write statistics"
+    );
+    drop(p);
     info!("GlobalChunkWriter shutting down (channel closed)");
 }
+
+/// Get ready to rotate into the next writable slot: give its readers time,
+/// read back the segments retention wants kept, archive what the archive
+/// wants, and evict the rest from the index. Returns the segments to
+/// relocate once the rotation is done.
+fn prepare_rotation(
+    pool: &Arc<RwLock<ChunkPool>>,
+    index: &SharedIndex,
+    retention: &SharedRetention,
+    read_counters: &PoolReadCounters,
+    archive: Option<&Archive>,
+    budget: u64,
+) -> Vec<(SegmentMeta, Vec<u8>)> {
+    // Next pool slot will be overwritten.
+    let next_idx = {
+        let p = pool.read();
+        let next_idx = p.next_write_idx();
+        if !p.is_writable(next_idx) {
+            // Nowhere left to rotate to; the append will fail on its own.
+            return Vec::new();
+        }
+        next_idx
+    };
+    wait_for_readers(read_counters, next_idx);
+    let relocations = read_relocations(pool, index, retention, next_idx, budget);
+    if let Some(archive) = archive {
+        archive_pool(pool, index, archive, next_idx);
+    }
+    index.write().evict_pool(next_idx);
+    relocations
+}
+
+/// Append a batch and index its records.
+fn append_and_index(
+    pool: &Arc<RwLock<ChunkPool>>,
+    index: &SharedIndex,
+    batch: &[WriteRequest],
+) -> Result<Vec<(SegmentLocation, u64)>> {
+    let records: Vec<NewRecord> = batch
+        .iter()
+        .map(|r| NewRecord {
+            camera_id: &r.camera_id,
+            stream: r.stream,
+            start_ts: r.start_ts,
+            end_ts: r.end_ts,
+            data: &r.data,
+        })
+        .collect();
+    write_records(pool, index, &records)
+}
+
+/// Append `records` to the pool and index them. The pool lock is held to
+/// reserve their place and, once they are on disk, to count them in and
+/// index them — never across the write itself, so API reads go on while
+/// the disk is busy. Indexed under the pool lock, so a snapshot (which
+/// reads both under that lock) never sees a record the index is missing.
+fn write_records(
+    pool: &Arc<RwLock<ChunkPool>>,
+    index: &SharedIndex,
+    records: &[NewRecord],
+) -> Result<Vec<(SegmentLocation, u64)>> {
+    let mut written = Vec::with_capacity(records.len());
+    while written.len() < records.len() {
+        let rest = &records[written.len()..];
+        let mut pending = pool.write().begin_append(rest)?;
+        pending.write();
+        let mut p = pool.write();
+        let locs = p.finish_append(pending)?;
+        let mut index = index.write();
+        for (loc, r) in locs.into_iter().zip(rest) {
+            let seg_id = index.insert(r.camera_id, r.start_ts, r.end_ts, loc.clone());
+            written.push((loc, seg_id));
+        }
+    }
+    Ok(written)
+}
+
+/// Sync whatever the pool has written since the last sync. The pool lock
+/// is held only to pick up a handle and to account for the outcome, not
+/// across the `fdatasync`.
+pub fn sync_pool(pool: &RwLock<ChunkPool>) -> Result<()> {
+    let Some(mut pending) = pool.write().begin_sync()? else {
+        return Ok(());
+    };
+    pending.run();
+    pool.write().finish_sync(pending)
+}
+
+/// Wait for the next request. While the pool has writes waiting to be
+/// synced, polls the channel instead of blocking on it, and syncs once they
+/// are due. `None` once the channel is closed and drained.
+fn next_request(
+    pool: &Arc<RwLock<ChunkPool>>,
+    rx: &mut mpsc::Receiver<WriteRequest>,
+) -> Option<WriteRequest> {
+    loop {
+        let Some(deadline) = pool.read().sync_deadline() else {
+            return rx.blocking_recv();
+        };
+        match rx.try_recv() {
+            Ok(req) => return Some(req),
+            Err(TryRecvError::Disconnected) => return None,
+            Err(TryRecvError::Empty) => {}
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            if let Err(e) = sync_pool(pool) {
+                error!(error = %e, "Failed to sync pool");
+            }
+        } else {
+            std::thread::sleep((deadline - now).min(SYNC_POLL));
+        }
+    }
+}
+
+/// Give readers of `pool_idx` up to [`READER_WAIT`] to finish before it is
+/// overwritten. Runs without holding the pool lock, so reads carry on.
+fn wait_for_readers(counters: &PoolReadCounters, pool_idx: usize) {
+    let step = Duration::from_millis(100);
+    let mut waited = Duration::ZERO;
+    while counters.has_readers(pool_idx) && waited < READER_WAIT {
+        std::thread::sleep(step);
+        waited += step;
+    }
+    if counters.has_readers(pool_idx) {
+        warn!(pool_idx, "Rotating despite active readers (timeout after 5s)");
+    }
+}
+
+/// Read the segments in `pool_idx` that the retention policy wants kept,
+/// before the pool is overwritten. `budget` is the room left in the new pool.
+fn read_relocations(
+    pool: &Arc<RwLock<ChunkPool>>,
+    index: &SharedIndex,
+    retention: &SharedRetention,
+    pool_idx: usize,
+    budget: u64,
+) -> Vec<(SegmentMeta, Vec<u8>)> {
+    let p = pool.read();
+    let ring_capacity = p.pool_capacity() * p.pool_count() as u64;
+    let budget = budget.min(p.pool_capacity() / RELOCATION_BUDGET_DIVISOR);
+    let selected = retention.read().select_relocations(
+        &index.read(),
+        pool_idx,
+        ring_capacity,
+        budget,
+        Utc::now(),
+    );
+
+    selected
+        .into_iter()
+        .filter_map(|seg| match p.read_segment_data(&seg.camera_id, &seg.location) {
+            Ok(data) => Some((seg, data)),
+            Err(e) => {
+                warn!(camera = seg.camera_id, error = %e, "Cannot read segment for relocation");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Copy the segments of `pool_idx` the archive wants into it, before the
+/// pool is overwritten, then drop archived days that have expired.
+fn archive_pool(
+    pool: &Arc<RwLock<ChunkPool>>,
+    index: &SharedIndex,
+    archive: &Archive,
+    pool_idx: usize,
+) {
+    let segments: Vec<SegmentMeta> =
+        index.read().segments_in_pool(pool_idx).into_iter().cloned().collect();
+    let mut count = 0usize;
+    let mut bytes = 0u64;
+    for seg in segments.iter().filter(|s| archive.wants(s)) {
+        let data = match pool.read().read_segment_data(&seg.camera_id, &seg.location) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!(camera = seg.camera_id, error = %e, "Cannot read segment for archiving");
+                continue;
+            }
+        };
+        match archive.append(seg, &data) {
+            Ok(()) => {
+                count += 1;
+                bytes += data.len() as u64;
+            }
+            Err(e) => {
+                error!(camera = seg.camera_id, error = %e, "Failed to archive segment");
+            }
+        }
+    }
+    if count > 0 {
+        info!(pool_idx, segments = count, bytes, "Archived segments ahead of pool overwrite");
+    }
+    if let Err(e) = archive.purge_expired(Utc::now()) {
+        warn!(error = %e, "Failed to purge expired archive days");
+    }
+}
+
+/// Append relocated segments to the current pool and re-index them.
+fn relocate(
+    pool: &Arc<RwLock<ChunkPool>>,
+    index: &SharedIndex,
+    relocations: Vec<(SegmentMeta, Vec<u8>)>,
+) {
+    // Never let a relocation itself trigger another rotation.
+    let fitting = {
+        let p = pool.read();
+        let (_, used, cap) = p.status();
+        let mut room = cap.saturating_sub(used);
+        relocations
+            .iter()
+            .take_while(|(_, data)| {
+                let size = p.record_size(data.len());
+                let fits = size <= room;
+                if fits {
+                    room -= size;
+                }
+                fits
+            })
+            .count()
+    };
+    for (seg, _) in &relocations[fitting..] {
+        warn!(camera = seg.camera_id, "No room left to relocate protected segment");
+    }
+
+    let records: Vec<NewRecord> = relocations[..fitting]
+        .iter()
+        .map(|(seg, data)| NewRecord {
+            camera_id: &seg.camera_id,
+            stream: seg.location.stream,
+            start_ts: seg.start_ts,
+            end_ts: seg.end_ts,
+            data,
+        })
+        .collect();
+    match write_records(pool, index, &records) {
+        Ok(_) => {
+            let bytes: u64 = records.iter().map(|r| r.data.len() as u64).sum();
+            info!(segments = fitting, bytes, "Relocated protected segments ahead of pool overwrite");
+        }
+        Err(e) => {
+            error!(segments = fitting, error = %e, "Failed to relocate protected segments");
+        }
+    }
+}
+
+/// Persist an index snapshot if the pool has snapshots enabled. Failures are
+/// logged only — the next startup just falls back to scanning.
+pub fn save_snapshot(pool: &Arc<RwLock<ChunkPool>>, index: &SharedIndex) {
+    let (snap, path) = {
+        let p = pool.read();
+        if !p.save_snapshots() {
+            return;
+        }
+        let snap = IndexSnapshot::capture(&p, &index.read());
+        (snap, snapshot_path(p.base_path()))
+    };
+    if let Err(e) = snap.save(&path) {
+        warn!(error = %e, "Failed to write index snapshot");
+    }
+}
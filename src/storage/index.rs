@@ -7,14 +7,36 @@
 //!
 //! The index lives in memory during a recording session but is **persistent**:
 //! on startup, pool files are scanned sequentially and the index is rebuilt
-//! from the RecordHeaders already embedded in the data stream. No separate
-//! index file is written, so recording I/O remains purely sequential.
+//! from the RecordHeaders already embedded in the data stream (or from the
+//! index snapshot, see [`snapshot`](crate::storage::snapshot)), so recording
+//! I/O remains purely sequential.
+//!
+//! A segment's ID is its record's sequence number, so it is stable across
+//! restarts and rotations (HLS media sequence numbers, bookmarked segment
+//! URLs). Records written before sequence numbers existed are numbered
+//! from 0 in on-disk order at load time; the pool never hands out
+//! sequence numbers in that range.
+//!
+//! Entries are ordered by camera first, so per-camera queries are range
+//! scans over that camera's entries only: O(log n + k), however many other
+//! cameras are recorded.
+//!
+//! A camera with a substream has two timelines, one per [`Stream`]. Queries
+//! without a stream are about the main stream: the recording of record
+//! (listing, coverage, export, archive, holds). The substream is only
+//! looked up for live viewing.
 
 use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
 
 use chrono::{DateTime, Utc};
 
-use crate::storage::chunk_pool::SegmentLocation;
+use crate::storage::chunk_pool::{SegmentLocation, Stream};
+
+/// Consecutive segments further apart than this are not continuous
+/// footage: HLS playlists mark a discontinuity there, and coverage reports
+/// a gap.
+pub const CONTINUITY_GAP: chrono::Duration = chrono::Duration::milliseconds(1500);
 
 /// Metadata about a single recorded segment, stored in the index.
 #[derive(Debug, Clone)]
@@ -26,10 +48,11 @@ pub struct SegmentMeta {
     pub location: SegmentLocation,
 }
 
-/// Key for the ordered index: (camera_id, start_ts).
+/// Key for the ordered index: (camera_id, stream, start_ts).
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct IndexKey {
     camera_id: String,
+    stream: Stream,
     start_ts: DateTime<Utc>,
     /// tiebreak on segment_id
     segment_id: u64,
@@ -39,7 +62,11 @@ struct IndexKey {
 #[derive(Default)]
 pub struct SegmentIndex {
     entries: BTreeMap<IndexKey, SegmentMeta>,
-    segment_counter: u64,
+    /// Next ID for segments whose record has no sequence number.
+    unnumbered_counter: u64,
+    /// Longest `end_ts - start_ts` ever inserted; bounds how far before a
+    /// range a segment overlapping it can start.
+    max_span: chrono::Duration,
 }
 
 impl SegmentIndex {
@@ -47,7 +74,8 @@ impl SegmentIndex {
         Self::default()
     }
 
-    /// Insert a new segment into the index.
+    /// Insert a new segment into the index. Returns its segment ID: the
+    /// location's sequence number, or the next legacy ID if it has none.
     pub fn insert(
         &mut self,
         camera_id: &str,
@@ -55,10 +83,15 @@ impl SegmentIndex {
         end_ts: DateTime<Utc>,
         location: SegmentLocation,
     ) -> u64 {
-        let id = self.segment_counter;
-        self.segment_counter += 1;
+        let id = location.sequence.unwrap_or_else(|| {
+            let id = self.unnumbered_counter;
+            self.unnumbered_counter += 1;
+            id
+        });
+        self.max_span = self.max_span.max(end_ts - start_ts);
         let key = IndexKey {
             camera_id: camera_id.to_string(),
+            stream: location.stream,
             start_ts,
             segment_id: id,
         };
@@ -81,28 +114,102 @@ impl SegmentIndex {
         self.entries.retain(|_, v| v.location.pool_idx != pool_idx);
     }
 
-    /// Return all segments for a given camera in chronological order.
+    /// Return all main-stream segments for a given camera in chronological
+    /// order.
     pub fn segments_for_camera(
         &self,
         camera_id: &str,
     ) -> Vec<&SegmentMeta> {
-        self.entries.values().filter(move |m| m.camera_id == camera_id).collect()
+        self.entries
+            .range(camera_keys(camera_id, Stream::Main, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC))
+            .map(|(_, m)| m)
+            .collect()
+    }
+
+    /// A camera's segment by ID, from either stream.
+    pub fn segment(&self, camera_id: &str, segment_id: u64) -> Option<&SegmentMeta> {
+        let (first, _) = camera_keys(camera_id, Stream::Main, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC)
+            .into_inner();
+        let (_, last) = camera_keys(camera_id, Stream::Sub, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC)
+            .into_inner();
+        self.entries
+            .range(first..=last)
+            .map(|(_, m)| m)
+            .find(|m| m.segment_id == segment_id)
+    }
+
+    /// The camera's `count` most recent main-stream segments, oldest first
+    /// (the live playlist window).
+    pub fn latest_for_camera(&self, camera_id: &str, count: usize) -> Vec<&SegmentMeta> {
+        self.latest_for_stream(camera_id, Stream::Main, count)
+    }
+
+    /// Like [`latest_for_camera`](Self::latest_for_camera), for either stream.
+    pub fn latest_for_stream(&self, camera_id: &str, stream: Stream, count: usize) -> Vec<&SegmentMeta> {
+        let mut latest: Vec<&SegmentMeta> = self
+            .entries
+            .range(camera_keys(camera_id, stream, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC))
+            .rev()
+            .take(count)
+            .map(|(_, m)| m)
+            .collect();
+        latest.reverse();
+        latest
+    }
+
+    /// Return all segments stored in pool slot `pool_idx`.
+    pub fn segments_in_pool(&self, pool_idx: usize) -> Vec<&SegmentMeta> {
+        self.entries.values().filter(|m| m.location.pool_idx == pool_idx).collect()
     }
 
-    /// Return segments for `camera_id` that overlap the time range `[from, to]`.
-    /// A segment overlaps if `segment.start_ts < to && segment.end_ts > from`.
+    /// Return main-stream segments for `camera_id` that overlap the time
+    /// range `[from, to]`. A segment overlaps if
+    /// `segment.start_ts < to && segment.end_ts > from`.
     pub fn segments_in_range(
         &self,
         camera_id: &str,
         from: DateTime<Utc>,
         to: DateTime<Utc>,
     ) -> Vec<&SegmentMeta> {
+        self.stream_in_range(camera_id, Stream::Main, from, to)
+    }
+
+    /// Like [`segments_in_range`](Self::segments_in_range), for either
+    /// stream.
+    pub fn stream_in_range(
+        &self,
+        camera_id: &str,
+        stream: Stream,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<&SegmentMeta> {
+        // Nothing starting earlier than the longest segment before `from`
+        // can still be running at `from`.
+        let earliest = from
+            .checked_sub_signed(self.max_span)
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        if earliest > to {
+            return Vec::new();
+        }
         self.entries
-            .values()
-            .filter(|m| m.camera_id == camera_id && m.start_ts < to && m.end_ts > from)
+            .range(camera_keys(camera_id, stream, earliest, to))
+            .map(|(_, m)| m)
+            .filter(|m| m.start_ts < to && m.end_ts > from)
             .collect()
     }
 
+    /// Continuous recording spans and gaps of `camera_id` within
+    /// `[from, to]`.
+    pub fn coverage(&self, camera_id: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Coverage {
+        Coverage::from_segments(
+            self.segments_in_range(camera_id, from, to)
+                .into_iter()
+                .map(|m| (m.start_ts, m.end_ts)),
+            from,
+            to,
+        )
+    }
+
     /// Return all segments across all cameras in insertion order.
     pub fn all_segments(&self) -> impl Iterator<Item = &SegmentMeta> {
         self.entries.values()
@@ -126,18 +233,158 @@ impl SegmentIndex {
     }
 
     /// Rebuild the index from records recovered by scanning pool files.
-    /// Called once on startup; zero disk I/O of its own.
+    /// Called once on startup; zero disk I/O of its own. `records` must be in
+    /// on-disk order (pool_id, offset), as `take_recovered` and
+    /// `scan_all_pools` return them.
     pub fn rebuild_from_scanned(&mut self, records: Vec<crate::storage::chunk_pool::ScannedRecord>) {
         self.entries.clear();
-        self.segment_counter = 0;
+        self.unnumbered_counter = 0;
+        self.max_span = chrono::Duration::zero();
         for r in records {
             let loc = crate::storage::chunk_pool::SegmentLocation {
                 pool_idx: r.pool_idx,
                 pool_id: r.pool_id,
                 record_offset: r.record_offset,
                 record_size: r.record_size,
+                header_size: r.header_size,
+                sequence: r.sequence,
+                stream: r.stream,
             };
             self.insert(&r.camera_id, r.start_ts, r.end_ts, loc);
         }
     }
 }
+
+/// Keys of `camera_id`'s `stream` segments starting in `[from, to]`.
+fn camera_keys(
+    camera_id: &str,
+    stream: Stream,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> RangeInclusive<IndexKey> {
+    let key = |start_ts, segment_id| IndexKey {
+        camera_id: camera_id.to_string(),
+        stream,
+        start_ts,
+        segment_id,
+    };
+    key(from, 0)..=key(to, u64::MAX)
+}
+
+/// A time interval `[start, end)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSpan {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl TimeSpan {
+    pub fn duration(&self) -> chrono::Duration {
+        self.end - self.start
+    }
+}
+
+/// Where a camera has footage within a time range.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Continuous recording, clipped to the range. Segments no more than
+    /// [`CONTINUITY_GAP`] apart are merged into one span.
+    pub spans: Vec<TimeSpan>,
+    /// Everything in the range that isn't covered by a span, except
+    /// stretches of up to [`CONTINUITY_GAP`] at either end.
+    pub gaps: Vec<TimeSpan>,
+}
+
+/// Recorded time within one bucket of a [`Coverage`] summary.
+#[derive(Debug, Clone, Copy)]
+pub struct CoverageBucket {
+    /// The bucket, clipped to the coverage range.
+    pub span: TimeSpan,
+    pub recorded: chrono::Duration,
+}
+
+impl CoverageBucket {
+    /// Recorded fraction of the bucket, 0.0 to 1.0.
+    pub fn ratio(&self) -> f64 {
+        let len = self.span.duration().num_milliseconds();
+        if len <= 0 {
+            return 0.0;
+        }
+        self.recorded.num_milliseconds() as f64 / len as f64
+    }
+}
+
+impl Coverage {
+    /// Build the coverage of `[from, to]` from `(start, end)` segment times
+    /// in chronological order of start.
+    pub fn from_segments(
+        segments: impl IntoIterator<Item = (DateTime<Utc>, DateTime<Utc>)>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Self {
+        let mut spans: Vec<TimeSpan> = Vec::new();
+        for (start, end) in segments {
+            let (start, end) = (start.max(from), end.min(to));
+            if end <= start {
+                continue;
+            }
+            match spans.last_mut() {
+                Some(last) if start - last.end <= CONTINUITY_GAP => last.end = last.end.max(end),
+                _ => spans.push(TimeSpan { start, end }),
+            }
+        }
+
+        let mut gaps = Vec::new();
+        let mut cursor = from;
+        for span in &spans {
+            if span.start - cursor > CONTINUITY_GAP {
+                gaps.push(TimeSpan { start: cursor, end: span.start });
+            }
+            cursor = span.end;
+        }
+        if to - cursor > CONTINUITY_GAP {
+            gaps.push(TimeSpan { start: cursor, end: to });
+        }
+
+        Coverage { from, to, spans, gaps }
+    }
+
+    /// Total recorded time in the range.
+    pub fn recorded(&self) -> chrono::Duration {
+        self.spans.iter().map(TimeSpan::duration).sum()
+    }
+
+    /// Recorded time per `bucket` (e.g. an hour or a day), with buckets
+    /// aligned to multiples of `bucket` since the Unix epoch (UTC).
+    pub fn buckets(&self, bucket: chrono::Duration) -> Vec<CoverageBucket> {
+        let secs = bucket.num_seconds();
+        if secs <= 0 || self.to <= self.from {
+            return Vec::new();
+        }
+        let first = self.from.timestamp().div_euclid(secs) * secs;
+        let mut start = DateTime::<Utc>::from_timestamp(first, 0).unwrap_or(self.from);
+
+        let mut buckets = Vec::new();
+        let mut spans = self.spans.iter().peekable();
+        while start < self.to {
+            let end = start + bucket;
+            let clipped = TimeSpan { start: start.max(self.from), end: end.min(self.to) };
+            let mut recorded = chrono::Duration::zero();
+            while let Some(span) = spans.peek() {
+                let overlap = span.end.min(clipped.end) - span.start.max(clipped.start);
+                if overlap > chrono::Duration::zero() {
+                    recorded += overlap;
+                }
+                if span.end > clipped.end {
+                    break;
+                }
+                spans.next();
+            }
+            buckets.push(CoverageBucket { span: clipped, recorded });
+            start = end;
+        }
+        buckets
+    }
+}
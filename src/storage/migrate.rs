@@ -0,0 +1,392 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Pool layout migration (`oasis migrate-pools`).
+//!
+//! The ring's layout is baked into the pool files: the pool capacity
+//! (`chunk_size_mb`) by each file's length, the slot count (`max_pools`) by
+//! the `pool_id % slots == idx` invariant, and the volume striping by where
+//! each file lives. Opening pools with a different layout would misread or
+//! ignore them, so a layout change copies the surviving records into a new
+//! set of pool files instead:
+//!
+//!   1. the current layout is detected from the files themselves;
+//!   2. every intact record is collected in chronological order, and the
+//!      newest ones that fit the new ring are packed into slots `0..m`
+//!      (pool_id = slot index), copied byte for byte so sequence numbers,
+//!      camera keys and checksums carry over. If the ring has room to
+//!      spare, slot `m` gets an empty pool with the highest pool_id, so
+//!      writing resumes there and reaches slot 0 only after the rest of the
+//!      ring; the slots after it are left without a header;
+//!   3. the new files are written next to their final path as
+//!      `pool_XXX.bin.migrate` and synced;
+//!   4. `migrate.commit` is written — from here on the migration rolls
+//!      forward;
+//!   5. old pool files are replaced or removed, then the commit marker and
+//!      the index snapshot are deleted.
+//!
+//! [`recover`] finishes (after step 4) or discards (before it) a migration
+//! interrupted by a crash, and must run before any pool is opened.
+//! Needs free space for a copy of the surviving records, and must only run
+//! while the recorder is stopped. It is never started automatically: a
+//! smaller ring drops footage, so `RecordingManager::new` refuses to start
+//! when the configured layout differs from the one on disk and leaves the
+//! migration to the operator.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use tracing::{info, warn};
+
+use crate::error::{NvrError, Result};
+use crate::storage::camera_registry::CameraRegistry;
+use crate::storage::chunk_pool::{
+    continues_chain, pool_file_index, pool_file_path, write_pool_header_at, ChunkPool,
+    ScannedRecord, POOL_HEADER_SIZE,
+};
+use crate::storage::snapshot::snapshot_path;
+
+pub const COMMIT_FILE: &str = "migrate.commit";
+const MIGRATE_SUFFIX: &str = "migrate";
+
+/// Pool capacity and slot count of a ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolLayout {
+    /// Payload bytes per pool, excluding the pool header.
+    pub pool_capacity: u64,
+    pub pool_count: usize,
+}
+
+/// Outcome of a migration (or, with `dry_run`, of its plan).
+#[derive(Debug, Clone)]
+pub struct MigrationReport {
+    pub from: PoolLayout,
+    pub to: PoolLayout,
+    /// Records copied into the new layout.
+    pub records_kept: usize,
+    /// Oldest records that did not fit the new ring.
+    pub records_dropped: usize,
+    pub bytes_copied: u64,
+    /// Pools in use after the migration.
+    pub pools_used: usize,
+}
+
+/// Pool files currently on disk, as `(slot, path)`, from `base_path` and
+/// every volume.
+fn find_pool_files(base_path: &Path, volumes: &[PathBuf]) -> Result<Vec<(usize, PathBuf)>> {
+    let mut files = Vec::new();
+    for dir in search_dirs(base_path, volumes) {
+        for entry in fs::read_dir(&dir)
+            .map_err(|e| NvrError::Storage(format!("read dir {dir:?}: {e}")))?
+        {
+            let path = entry?.path();
+            if let Some(idx) = pool_file_index(&path) {
+                files.push((idx, path));
+            }
+        }
+    }
+    files.sort();
+    if let Some(w) = files.windows(2).find(|w| w[0].0 == w[1].0) {
+        return Err(NvrError::Storage(format!(
+            "slot {} has two pool files: {} and {}",
+            w[0].0,
+            w[0].1.display(),
+            w[1].1.display()
+        )));
+    }
+    Ok(files)
+}
+
+/// `base_path` plus the volumes, without duplicates or missing directories.
+fn search_dirs(base_path: &Path, volumes: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs = vec![base_path.to_path_buf()];
+    for v in volumes {
+        if !dirs.contains(v) {
+            dirs.push(v.clone());
+        }
+    }
+    dirs.retain(|d| d.is_dir());
+    dirs
+}
+
+/// Layout of the pool files on disk, or `None` if there are none.
+pub fn detect_layout(base_path: &Path, volumes: &[PathBuf]) -> Result<Option<PoolLayout>> {
+    let files = find_pool_files(base_path, volumes)?;
+    layout_of(&files)
+}
+
+fn layout_of(files: &[(usize, PathBuf)]) -> Result<Option<PoolLayout>> {
+    let Some(&(last_idx, _)) = files.last() else {
+        return Ok(None);
+    };
+    let mut capacity = None;
+    for (_, path) in files {
+        let len = fs::metadata(path)?.len();
+        let cap = len.saturating_sub(POOL_HEADER_SIZE);
+        if *capacity.get_or_insert(cap) != cap {
+            return Err(NvrError::Storage(format!(
+                "pool files have different sizes ({} is {len} bytes)",
+                path.display()
+            )));
+        }
+    }
+    Ok(Some(PoolLayout {
+        pool_capacity: capacity.unwrap_or(0),
+        pool_count: last_idx + 1,
+    }))
+}
+
+/// Whether the pool files on disk differ from the configured layout (in
+/// size, slot count, or placement across volumes).
+pub fn needs_migration(
+    base_path: &Path,
+    volumes: &[PathBuf],
+    layout: PoolLayout,
+) -> Result<bool> {
+    let files = find_pool_files(base_path, volumes)?;
+    let Some(current) = layout_of(&files)? else {
+        return Ok(false);
+    };
+    Ok(current != layout
+        || files.iter().any(|(idx, path)| *path != pool_file_path(base_path, volumes, *idx)))
+}
+
+/// Move the ring to `layout`, keeping as much of the newest footage as
+/// fits. Returns `None` if the pools already match. With `dry_run`, only
+/// reports what would happen.
+pub fn migrate_pools(
+    base_path: &Path,
+    volumes: &[PathBuf],
+    layout: PoolLayout,
+    dry_run: bool,
+) -> Result<Option<MigrationReport>> {
+    if let Some(v) = volumes.iter().find(|v| !v.is_dir()) {
+        return Err(NvrError::Storage(format!(
+            "storage volume {} is missing; mount it before migrating",
+            v.display()
+        )));
+    }
+    if layout.pool_count == 0 || layout.pool_capacity == 0 {
+        return Err(NvrError::Config("pool size and count must be non-zero".into()));
+    }
+    recover(base_path, volumes)?;
+    if !needs_migration(base_path, volumes, layout)? {
+        return Ok(None);
+    }
+
+    let files = find_pool_files(base_path, volumes)?;
+    let Some(from) = layout_of(&files)? else {
+        return Ok(None);
+    };
+    let records = collect_records(base_path, &files, from.pool_capacity)?;
+    let groups = pack(&records, layout);
+
+    let kept: usize = groups.iter().map(Vec::len).sum();
+    let report = MigrationReport {
+        from,
+        to: layout,
+        records_kept: kept,
+        records_dropped: records.len() - kept,
+        bytes_copied: groups.iter().flatten().map(|&i| records[i].0.record_size).sum(),
+        pools_used: groups.len(),
+    };
+    if dry_run {
+        return Ok(Some(report));
+    }
+
+    info!(
+        from_pools = from.pool_count,
+        from_capacity = from.pool_capacity,
+        to_pools = layout.pool_count,
+        to_capacity = layout.pool_capacity,
+        records = kept,
+        dropped = report.records_dropped,
+        "Migrating pool layout"
+    );
+
+    // Steps 3–4: stage the new pools, then commit.
+    let mut sources: HashMap<&Path, File> = HashMap::new();
+    // Every slot is staged, filled or not, so the ring on disk matches
+    // `layout` and the next start-up doesn't see a layout change.
+    let empty = Vec::new();
+    let next_sequence = groups
+        .iter()
+        .flatten()
+        .filter_map(|&i| records[i].0.sequence)
+        .max()
+        .map_or(0, |s| s + 1);
+    let mut new_paths = Vec::with_capacity(layout.pool_count);
+    for slot in 0..layout.pool_count {
+        let group = groups.get(slot).unwrap_or(&empty);
+        let path = pool_file_path(base_path, volumes, slot);
+        let staged = staged_path(&path);
+        let f = File::create(&staged)
+            .map_err(|e| NvrError::Storage(format!("create {staged:?}: {e}")))?;
+        f.set_len(POOL_HEADER_SIZE + layout.pool_capacity)?;
+        drop(f);
+        if let Some(&last) = group.last() {
+            // Records are copied sealed as they are; keep naming their key.
+            let key_id = ChunkPool::read_pool_key_id(&records[last].1)?;
+            let first_sequence =
+                group.iter().filter_map(|&i| records[i].0.sequence).min().unwrap_or(0);
+            write_pool_header_at(&staged, slot as u64, key_id, first_sequence)?;
+        } else if slot == groups.len() && slot > 0 {
+            // The newest pool, so the writer resumes here rather than
+            // wrapping onto the oldest migrated footage in slot 0.
+            write_pool_header_at(&staged, slot as u64, 0, next_sequence)?;
+        }
+
+        let mut out = BufWriter::new(fs::OpenOptions::new().write(true).open(&staged)?);
+        out.seek(SeekFrom::Start(POOL_HEADER_SIZE))?;
+        let mut buf = Vec::new();
+        for &i in group {
+            let (rec, src) = &records[i];
+            let src_file = match sources.entry(src.as_path()) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => e.insert(File::open(src)?),
+            };
+            buf.resize(rec.record_size as usize, 0);
+            src_file.seek(SeekFrom::Start(rec.record_offset))?;
+            src_file.read_exact(&mut buf)?;
+            out.write_all(&buf)?;
+        }
+        out.into_inner()
+            .map_err(|e| NvrError::Storage(format!("write {staged:?}: {e}")))?
+            .sync_all()?;
+        new_paths.push(path);
+    }
+    drop(sources);
+
+    let marker: String = new_paths.iter().map(|p| format!("{}\n", p.display())).collect();
+    let marker_path = base_path.join(COMMIT_FILE);
+    let tmp = marker_path.with_extension("tmp");
+    {
+        let mut f = File::create(&tmp)?;
+        f.write_all(marker.as_bytes())?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, &marker_path)?;
+
+    // Step 5.
+    roll_forward(base_path, volumes, &new_paths)?;
+    info!(pools = report.pools_used, records = kept, "Pool layout migration complete");
+    Ok(Some(report))
+}
+
+/// Finish or discard a migration interrupted by a crash. A no-op if none
+/// was in progress.
+pub fn recover(base_path: &Path, volumes: &[PathBuf]) -> Result<()> {
+    let marker_path = base_path.join(COMMIT_FILE);
+    match fs::read_to_string(&marker_path) {
+        Ok(marker) => {
+            warn!("Completing interrupted pool migration");
+            let new_paths: Vec<PathBuf> = marker.lines().map(PathBuf::from).collect();
+            roll_forward(base_path, volumes, &new_paths)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            for dir in search_dirs(base_path, volumes) {
+                for entry in fs::read_dir(&dir)? {
+                    let path = entry?.path();
+                    if is_staged(&path) {
+                        warn!(path = ?path, "Discarding pool file from interrupted migration");
+                        fs::remove_file(&path)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace the old pools with the staged ones. Idempotent, so a crash at
+/// any point is finished by running it again.
+fn roll_forward(base_path: &Path, volumes: &[PathBuf], new_paths: &[PathBuf]) -> Result<()> {
+    for (_, path) in find_pool_files(base_path, volumes)? {
+        if !new_paths.contains(&path) {
+            fs::remove_file(&path)?;
+        }
+    }
+    for path in new_paths {
+        let staged = staged_path(path);
+        if staged.exists() {
+            fs::rename(&staged, path)?;
+        }
+    }
+    match fs::remove_file(snapshot_path(base_path)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    fs::remove_file(base_path.join(COMMIT_FILE))?;
+    Ok(())
+}
+
+fn staged_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(MIGRATE_SUFFIX);
+    PathBuf::from(name)
+}
+
+fn is_staged(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == MIGRATE_SUFFIX)
+        && path.file_stem().is_some_and(|s| pool_file_index(Path::new(s)).is_some())
+}
+
+/// Every intact record in chronological order, with the file it lives in.
+fn collect_records(
+    base_path: &Path,
+    files: &[(usize, PathBuf)],
+    pool_capacity: u64,
+) -> Result<Vec<(ScannedRecord, PathBuf)>> {
+    let cameras = CameraRegistry::open(base_path)?;
+    let mut records = Vec::new();
+    for (idx, path) in files {
+        let Some((pid, _)) = ChunkPool::read_pool_header(path)? else {
+            continue;
+        };
+        let scan = ChunkPool::scan_pool(path, *idx, pid, pool_capacity, &cameras)?;
+        records.extend(scan.records.into_iter().map(|r| (r, path.clone())));
+    }
+    records.sort_by_key(|(r, _)| (r.pool_id, r.record_offset));
+    Ok(records)
+}
+
+/// Pack the newest records that fit into at most `layout.pool_count` pools.
+/// Returns record indices per new pool, oldest pool first.
+fn pack(records: &[(ScannedRecord, PathBuf)], layout: PoolLayout) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut used = 0u64;
+    for (i, (rec, _)) in records.iter().enumerate().rev() {
+        if rec.record_size > layout.pool_capacity {
+            warn!(sequence = ?rec.sequence, "Record larger than the new pool size, dropped");
+            continue;
+        }
+        // Records are added newest first, so `rec` goes in front of the
+        // group's current first record and must still chain onto it.
+        let fits = groups.last().is_some_and(|g| {
+            used + rec.record_size <= layout.pool_capacity
+                && continues_chain(rec.sequence, records[*g.last().unwrap()].0.sequence)
+        });
+        if !fits {
+            if groups.len() == layout.pool_count {
+                break;
+            }
+            groups.push(Vec::new());
+            used = 0;
+        }
+        groups.last_mut().unwrap().push(i);
+        used += rec.record_size;
+    }
+    groups.reverse();
+    for g in &mut groups {
+        g.reverse();
+    }
+    groups
+}
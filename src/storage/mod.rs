@@ -5,6 +5,15 @@
 
 //! Storage subsystem — global chunk pool + index + writer.
 
+pub mod archive;
+pub mod camera_registry;
 pub mod chunk_pool;
+pub mod crypto;
+pub mod fsck;
 pub mod global_writer;
 pub mod index;
+pub mod migrate;
+pub mod replica;
+pub mod retention;
+pub mod snapshot;
+pub mod vault;
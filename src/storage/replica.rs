@@ -0,0 +1,262 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Replication of recorded segments to a second disk.
+//!
+//! With `[storage.replica]` configured, every segment the global writer
+//! appends to the primary ring is also mirrored into a second, independent
+//! ring on another volume:
+//!
+//! ```text
+//! GlobalChunkWriter ──append──→ primary pools
+//!        │
+//!        └── mirror (try_send) ──→ nvr-replica thread ──→ replica pools
+//! ```
+//!
+//! Mirroring is asynchronous: the writer hands each written segment to the
+//! replica thread without waiting, and if the replica disk falls more than
+//! `queue_size` segments behind, segments are skipped (and counted) rather
+//! than slowing down the primary. The replica has its own ring size and
+//! rotates on its own; relocations done for the retention policy are not
+//! mirrored, since the replica already holds its own copy of the segment,
+//! and neither are substreams.
+//!
+//! The replica keeps its own in-memory index, rebuilt by scanning its pools
+//! on startup. Segment IDs are per ring, so a primary segment is found in
+//! the replica by camera and timestamps ([`Replica::read_segment`]); the
+//! API falls back to it when reading from the primary fails.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use parking_lot::RwLock;
+use tracing::{error, info, warn};
+
+use crate::config::{DurabilityConfig, ReplicaConfig};
+use crate::error::{NvrError, Result};
+use crate::storage::chunk_pool::{ChunkPool, NewRecord, SlotHealth};
+use crate::storage::crypto::RecordCipher;
+use crate::storage::global_writer::{sync_pool, SharedIndex, WriteRequest};
+use crate::storage::index::{SegmentIndex, SegmentMeta};
+use crate::storage::migrate::{self, PoolLayout};
+
+/// Replica counters since the recorder started.
+#[derive(Debug, Clone, Copy)]
+pub struct ReplicaStats {
+    /// Segments currently held by the replica ring.
+    pub segments: usize,
+    pub mirrored: u64,
+    /// Segments not mirrored because the replica queue was full.
+    pub skipped: u64,
+    /// Segments the replica failed to write.
+    pub failed: u64,
+    pub health: SlotHealth,
+}
+
+/// The replica ring and its index.
+pub struct Replica {
+    pool: Arc<RwLock<ChunkPool>>,
+    index: SharedIndex,
+    mirrored: AtomicU64,
+    skipped: AtomicU64,
+    failed: AtomicU64,
+}
+
+pub type SharedReplica = Arc<Replica>;
+
+impl Replica {
+    /// Open (or create) the replica ring described by `config` and rebuild
+    /// its index. Fails if its pool files have another layout; `oasis
+    /// migrate-pools` migrates the replica along with the primary ring.
+    pub fn open(
+        config: &ReplicaConfig,
+        durability: DurabilityConfig,
+        cipher: Option<RecordCipher>,
+    ) -> Result<Self> {
+        let base = &config.base_path;
+        std::fs::create_dir_all(base)
+            .map_err(|e| NvrError::Storage(format!("Cannot create replica base_path: {e}")))?;
+
+        let pool_bytes = config.chunk_size_mb * 1024 * 1024;
+        migrate::recover(base, &[])?;
+        let layout = PoolLayout {
+            pool_capacity: pool_bytes,
+            pool_count: config.max_pools,
+        };
+        if migrate::needs_migration(base, &[], layout)? {
+            return Err(NvrError::Config(
+                "Replica pool files don't match its chunk_size_mb / max_pools; \
+                 run `oasis migrate-pools` (see --dry-run) or restore the previous settings"
+                    .into(),
+            ));
+        }
+
+        let mut pool = ChunkPool::open(base, pool_bytes, config.max_pools)?;
+        pool.set_durability(durability);
+        pool.set_encryption(cipher);
+        let mut index = SegmentIndex::new();
+        index.rebuild_from_scanned(pool.take_recovered());
+        info!(path = ?base, segments = index.len(), "Replica opened");
+
+        Ok(Replica {
+            pool: Arc::new(RwLock::new(pool)),
+            index: Arc::new(RwLock::new(index)),
+            mirrored: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        })
+    }
+
+    pub fn pool(&self) -> &Arc<RwLock<ChunkPool>> {
+        &self.pool
+    }
+
+    pub fn index(&self) -> &SharedIndex {
+        &self.index
+    }
+
+    pub fn stats(&self) -> ReplicaStats {
+        ReplicaStats {
+            segments: self.index.read().len(),
+            mirrored: self.mirrored.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            health: self.pool.read().health(),
+        }
+    }
+
+    /// Read the replica's copy of a primary segment. `Ok(None)` if the
+    /// replica doesn't have it (never mirrored, or rotated out).
+    pub fn read_segment(&self, seg: &SegmentMeta) -> Result<Option<Vec<u8>>> {
+        let location = {
+            let index = self.index.read();
+            index
+                .segments_for_camera(&seg.camera_id)
+                .into_iter()
+                .find(|m| m.start_ts == seg.start_ts && m.end_ts == seg.end_ts)
+                .map(|m| m.location.clone())
+        };
+        let Some(location) = location else {
+            return Ok(None);
+        };
+        // Reads hold the pool lock, so the replica can't rotate under them;
+        // a rotation since the lookup is caught by the stale-read guard.
+        match self.pool.read().read_segment_data(&seg.camera_id, &location) {
+            Ok(data) => Ok(Some(data)),
+            Err(NvrError::SegmentEvicted { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Append one segment and index it, evicting the slot a rotation is
+    /// about to overwrite from the index first. The pool lock isn't held
+    /// while the record is written, so fallback reads don't wait on it.
+    fn append(&self, req: &WriteRequest) -> Result<u64> {
+        let record = NewRecord {
+            camera_id: &req.camera_id,
+            stream: req.stream,
+            start_ts: req.start_ts,
+            end_ts: req.end_ts,
+            data: &req.data,
+        };
+        let mut pending = {
+            let mut p = self.pool.write();
+            let (idx, used, cap) = p.status();
+            if !p.is_writable(idx) || used + p.record_size(req.data.len()) > cap {
+                self.index.write().evict_pool(p.next_write_idx());
+            }
+            p.begin_append(&[record])?
+        };
+        pending.write();
+        let mut p = self.pool.write();
+        let loc = p.finish_append(pending)?.remove(0);
+        Ok(self.index.write().insert(&req.camera_id, req.start_ts, req.end_ts, loc))
+    }
+}
+
+/// The global writer's handle for mirroring segments into the replica.
+pub struct Replicator {
+    tx: SyncSender<WriteRequest>,
+    replica: SharedReplica,
+}
+
+impl Replicator {
+    /// Queue a written segment for the replica without waiting. Skipped if
+    /// the replica has fallen `queue_size` segments behind.
+    pub fn mirror(&self, req: WriteRequest) {
+        match self.tx.try_send(req) {
+            Ok(()) => {}
+            Err(TrySendError::Full(req)) => {
+                self.replica.skipped.fetch_add(1, Ordering::Relaxed);
+                warn!(camera = req.camera_id, "Replica queue full, segment not mirrored");
+            }
+            Err(TrySendError::Disconnected(req)) => {
+                self.replica.failed.fetch_add(1, Ordering::Relaxed);
+                error!(camera = req.camera_id, "Replica writer gone, segment not mirrored");
+            }
+        }
+    }
+}
+
+/// Spawn the replica writer thread. It exits once the returned
+/// [`Replicator`] has been dropped and its queue is drained.
+pub fn spawn_replicator(replica: SharedReplica, queue_size: usize) -> (Replicator, JoinHandle<()>) {
+    let (tx, rx) = mpsc::sync_channel(queue_size);
+    let thread_replica = replica.clone();
+    let handle = std::thread::Builder::new()
+        .name("nvr-replica".into())
+        .spawn(move || replica_loop(thread_replica, rx))
+        .expect("spawn replica thread");
+    (Replicator { tx, replica }, handle)
+}
+
+fn replica_loop(replica: SharedReplica, rx: Receiver<WriteRequest>) {
+    info!("Replica writer started");
+    while let Some(req) = next_request(&replica.pool, &rx) {
+        match replica.append(&req) {
+            Ok(_) => {
+                replica.mirrored.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                replica.failed.fetch_add(1, Ordering::Relaxed);
+                error!(camera = req.camera_id, error = %e, "Failed to mirror segment to replica");
+            }
+        }
+    }
+    if let Err(e) = sync_pool(&replica.pool) {
+        error!(error = %e, "Failed to sync replica on shutdown");
+    }
+    info!(
+        mirrored = replica.mirrored.load(Ordering::Relaxed),
+        skipped = replica.skipped.load(Ordering::Relaxed),
+        failed = replica.failed.load(Ordering::Relaxed),
+        "Replica writer shutting down"
+    );
+}
+
+/// Wait for the next segment, syncing the replica when a sync falls due in
+/// the meantime. `None` once the channel is closed and drained.
+fn next_request(pool: &RwLock<ChunkPool>, rx: &Receiver<WriteRequest>) -> Option<WriteRequest> {
+    loop {
+        let Some(deadline) = pool.read().sync_deadline() else {
+            return rx.recv().ok();
+        };
+        let now = Instant::now();
+        if now >= deadline {
+            if let Err(e) = sync_pool(pool) {
+                error!(error = %e, "Failed to sync replica");
+            }
+            continue;
+        }
+        match rx.recv_timeout(deadline - now) {
+            Ok(req) => return Some(req),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
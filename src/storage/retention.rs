@@ -0,0 +1,145 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Per-camera retention within the shared ring.
+//!
+//! All cameras share one ring, so by default a camera's history is only as
+//! long as the ring lasts at the *total* bitrate — a noisy camera pushes
+//! everyone else's footage out. A camera with `min_retention_days` is
+//! protected: when the writer is about to overwrite a pool, that camera's
+//! segments in it that are still inside the retention window are read back
+//! and relocated to the head of the ring instead of being evicted.
+//!
+//! Relocation is bounded so the ring keeps moving:
+//!   - `max_share` caps how much of the ring a camera's footage may occupy
+//!     including relocated segments; beyond that its oldest protected
+//!     segments expire like everyone else's.
+//!   - At most [`RELOCATION_BUDGET_DIVISOR`]⁻¹ of a pool is relocated per
+//!     rotation, newest segments first.
+//!
+//! Relocated segments are ordinary new records, so they get a new segment
+//! ID. Only main streams are protected; substreams are for live viewing.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::RwLock;
+
+use crate::config::CameraConfig;
+use crate::storage::chunk_pool::{Stream, RECORD_HEADER_SIZE};
+use crate::storage::index::{SegmentIndex, SegmentMeta};
+
+/// Relocation per rotation is capped at `pool_capacity / this`, which bounds
+/// both the write amplification and the memory used to carry the data over.
+pub const RELOCATION_BUDGET_DIVISOR: u64 = 4;
+
+/// Retention settings for one camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraRetention {
+    /// Keep segments at least this long (zero = no guarantee).
+    pub min_retention: Duration,
+    /// Largest fraction of the ring the camera may occupy (0, 1].
+    pub max_share: f64,
+}
+
+/// Retention settings for all cameras. Cameras not listed have none.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    cameras: HashMap<String, CameraRetention>,
+}
+
+/// Shared handle so cameras added or removed at runtime take effect in the
+/// writer.
+pub type SharedRetention = Arc<RwLock<RetentionPolicy>>;
+
+impl RetentionPolicy {
+    pub fn from_cameras(cameras: &[CameraConfig]) -> Self {
+        let mut policy = Self::default();
+        for cam in cameras {
+            policy.set_camera(cam);
+        }
+        policy
+    }
+
+    /// Add or update a camera's settings.
+    pub fn set_camera(&mut self, cam: &CameraConfig) {
+        if cam.min_retention_days == 0 {
+            self.cameras.remove(&cam.id);
+            return;
+        }
+        self.cameras.insert(
+            cam.id.clone(),
+            CameraRetention {
+                min_retention: Duration::days(cam.min_retention_days as i64),
+                max_share: cam.max_share,
+            },
+        );
+    }
+
+    pub fn remove_camera(&mut self, camera_id: &str) {
+        self.cameras.remove(camera_id);
+    }
+
+    pub fn camera(&self, camera_id: &str) -> Option<&CameraRetention> {
+        self.cameras.get(camera_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cameras.is_empty()
+    }
+
+    /// Segments in `pool_idx` that must survive its overwrite, newest first,
+    /// within `budget` bytes of payload. `ring_capacity` is the total payload
+    /// capacity of all pools, used for `max_share`.
+    pub fn select_relocations(
+        &self,
+        index: &SegmentIndex,
+        pool_idx: usize,
+        ring_capacity: u64,
+        budget: u64,
+        now: DateTime<Utc>,
+    ) -> Vec<SegmentMeta> {
+        if self.cameras.is_empty() {
+            return Vec::new();
+        }
+
+        // Bytes each protected camera keeps outside the pool being recycled.
+        let mut used: HashMap<&str, u64> = HashMap::new();
+        for seg in index.all_segments().filter(|seg| seg.location.stream == Stream::Main) {
+            if seg.location.pool_idx != pool_idx && self.cameras.contains_key(&seg.camera_id) {
+                *used.entry(seg.camera_id.as_str()).or_default() += seg.location.record_size;
+            }
+        }
+
+        let mut candidates: Vec<&SegmentMeta> = index
+            .segments_in_pool(pool_idx)
+            .into_iter()
+            .filter(|seg| {
+                seg.location.stream == Stream::Main
+                    && self
+                        .camera(&seg.camera_id)
+                        .is_some_and(|r| seg.end_ts > now - r.min_retention)
+            })
+            .collect();
+        candidates.sort_by_key(|seg| std::cmp::Reverse(seg.start_ts));
+
+        let mut selected = Vec::new();
+        let mut total = 0u64;
+        for seg in candidates {
+            // Relocated records are rewritten with a current-format header.
+            let size = RECORD_HEADER_SIZE + seg.location.data_len();
+            let share = self.cameras[&seg.camera_id].max_share;
+            let cam_used = used.entry(seg.camera_id.as_str()).or_default();
+            if total + size > budget || (*cam_used + size) as f64 > share * ring_capacity as f64 {
+                continue;
+            }
+            *cam_used += size;
+            total += size;
+            selected.push(seg.clone());
+        }
+        selected
+    }
+}
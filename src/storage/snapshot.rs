@@ -0,0 +1,239 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Index snapshot — a compact copy of the segment index, so startup doesn't
+//! have to re-read every RecordHeader on large pool sets.
+//!
+//! Written to `base_path/index.snapshot` on clean shutdown and at every pool
+//! rotation. On `ChunkPool::open`, each slot's entry is only trusted if its
+//! `pool_id` still matches the slot's on-disk pool header: a pool's records
+//! only ever grow while its `pool_id` is unchanged, so the snapshot is a
+//! valid prefix and only records appended after it (at most the active
+//! pool's tail) need scanning. Slots whose `pool_id` moved on are scanned
+//! in full.
+//!
+//! ## File Layout (all integers LE)
+//!
+//! ```text
+//! magic         : [u8;8] = b"NVRSNAP3"
+//! pool_capacity : u64
+//! pool_count    : u32
+//! per pool:
+//!   pool_idx    : u32
+//!   pool_id     : u64
+//!   bytes_used  : u64
+//!   records     : u32
+//!   per record:
+//!     cam_len       : u16, camera_id : [u8; cam_len]
+//!     start_ts      : i64 (unix microseconds)
+//!     end_ts        : i64 (unix microseconds)
+//!     record_offset : u64
+//!     record_size   : u64
+//!     header_size   : u16
+//!     sequence      : u64 (u64::MAX = none)
+//!     stream        : u8  (0 = main, 1 = substream)
+//! crc32         : u32 over everything above
+//! ```
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{DateTime, Utc};
+use tracing::{debug, warn};
+
+use crate::error::{NvrError, Result};
+use crate::storage::chunk_pool::{ChunkPool, ScannedRecord, Stream};
+use crate::storage::index::SegmentIndex;
+
+/// Bumped whenever the layout changes; older snapshots are ignored (and
+/// replaced at the next rotation) rather than migrated.
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"NVRSNAP3";
+/// Stored in place of a missing record sequence number.
+const NO_SEQUENCE: u64 = u64::MAX;
+pub const SNAPSHOT_FILE: &str = "index.snapshot";
+
+/// Location of the snapshot file for a pool directory.
+pub fn snapshot_path(base_path: &Path) -> PathBuf {
+    base_path.join(SNAPSHOT_FILE)
+}
+
+/// Snapshot of one pool slot.
+#[derive(Debug, Clone)]
+pub struct PoolSnapshot {
+    pub pool_idx: usize,
+    pub pool_id: u64,
+    /// Bytes used after POOL_HEADER_SIZE when the snapshot was taken.
+    pub bytes_used: u64,
+    /// Indexed records in this pool, in on-disk order.
+    pub records: Vec<ScannedRecord>,
+}
+
+/// Snapshot of the whole index plus the pool state it was taken against.
+#[derive(Debug, Clone, Default)]
+pub struct IndexSnapshot {
+    pub pool_capacity: u64,
+    pub pools: Vec<PoolSnapshot>,
+}
+
+impl IndexSnapshot {
+    /// Capture the current index and pool slot states.
+    pub fn capture(pool: &ChunkPool, index: &SegmentIndex) -> Self {
+        let mut pools: Vec<PoolSnapshot> = (0..pool.pool_count())
+            .map(|i| PoolSnapshot {
+                pool_idx: i,
+                pool_id: pool.pool_id(i),
+                bytes_used: pool.bytes_used(i),
+                records: Vec::new(),
+            })
+            .collect();
+
+        for seg in index.all_segments() {
+            let loc = &seg.location;
+            let Some(p) = pools.get_mut(loc.pool_idx) else { continue };
+            if p.pool_id != loc.pool_id {
+                continue;
+            }
+            p.records.push(ScannedRecord {
+                camera_id: seg.camera_id.clone(),
+                start_ts: seg.start_ts,
+                end_ts: seg.end_ts,
+                pool_idx: loc.pool_idx,
+                pool_id: loc.pool_id,
+                record_offset: loc.record_offset,
+                record_size: loc.record_size,
+                header_size: loc.header_size,
+                sequence: loc.sequence,
+                stream: loc.stream,
+            });
+        }
+        for p in &mut pools {
+            p.records.sort_by_key(|r| r.record_offset);
+        }
+
+        IndexSnapshot {
+            pool_capacity: pool.pool_capacity(),
+            pools,
+        }
+    }
+
+    /// The snapshot entry for `pool_idx`, if it was taken at `pool_id`.
+    pub fn pool(&self, pool_idx: usize, pool_id: u64) -> Option<&PoolSnapshot> {
+        self.pools
+            .iter()
+            .find(|p| p.pool_idx == pool_idx && p.pool_id == pool_id)
+    }
+
+    /// Write the snapshot atomically (temp file + rename).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut buf = Vec::new();
+        buf.write_all(SNAPSHOT_MAGIC)?;
+        buf.write_u64::<LittleEndian>(self.pool_capacity)?;
+        buf.write_u32::<LittleEndian>(self.pools.len() as u32)?;
+        for p in &self.pools {
+            buf.write_u32::<LittleEndian>(p.pool_idx as u32)?;
+            buf.write_u64::<LittleEndian>(p.pool_id)?;
+            buf.write_u64::<LittleEndian>(p.bytes_used)?;
+            buf.write_u32::<LittleEndian>(p.records.len() as u32)?;
+            for r in &p.records {
+                let cam = r.camera_id.as_bytes();
+                buf.write_u16::<LittleEndian>(cam.len() as u16)?;
+                buf.write_all(cam)?;
+                buf.write_i64::<LittleEndian>(r.start_ts.timestamp_micros())?;
+                buf.write_i64::<LittleEndian>(r.end_ts.timestamp_micros())?;
+                buf.write_u64::<LittleEndian>(r.record_offset)?;
+                buf.write_u64::<LittleEndian>(r.record_size)?;
+                buf.write_u16::<LittleEndian>(r.header_size as u16)?;
+                buf.write_u64::<LittleEndian>(r.sequence.unwrap_or(NO_SEQUENCE))?;
+                buf.write_u8(r.stream.tag())?;
+            }
+        }
+        let crc = crc32fast::hash(&buf);
+        buf.write_u32::<LittleEndian>(crc)?;
+
+        let tmp = path.with_extension("tmp");
+        {
+            let mut f = BufWriter::new(
+                File::create(&tmp)
+                    .map_err(|e| NvrError::Storage(format!("create snapshot {tmp:?}: {e}")))?,
+            );
+            f.write_all(&buf)?;
+            f.into_inner()
+                .map_err(|e| NvrError::Storage(format!("flush snapshot: {e}")))?
+                .sync_all()?;
+        }
+        std::fs::rename(&tmp, path)
+            .map_err(|e| NvrError::Storage(format!("rename snapshot into place: {e}")))?;
+
+        debug!(path = ?path, bytes = buf.len(), "Index snapshot written");
+        Ok(())
+    }
+
+    /// Load a snapshot. Returns `Ok(None)` if there is none, or if it is
+    /// damaged (logged) — callers then fall back to a full scan.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let mut buf = Vec::new();
+        match File::open(path) {
+            Ok(mut f) => f.read_to_end(&mut buf)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if buf.len() < SNAPSHOT_MAGIC.len() + 4 || &buf[..8] != SNAPSHOT_MAGIC {
+            warn!(path = ?path, "Ignoring index snapshot with bad magic");
+            return Ok(None);
+        }
+        let (body, crc_bytes) = buf.split_at(buf.len() - 4);
+        let crc = u32::from_le_bytes(crc_bytes.try_into().unwrap());
+        if crc32fast::hash(body) != crc {
+            warn!(path = ?path, "Ignoring index snapshot with bad checksum");
+            return Ok(None);
+        }
+
+        match Self::decode(&body[8..]) {
+            Ok(snap) => Ok(Some(snap)),
+            Err(e) => {
+                warn!(path = ?path, error = %e, "Ignoring unreadable index snapshot");
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode(mut r: &[u8]) -> std::io::Result<Self> {
+        let pool_capacity = r.read_u64::<LittleEndian>()?;
+        let pool_count = r.read_u32::<LittleEndian>()?;
+        let mut pools = Vec::with_capacity(pool_count as usize);
+        for _ in 0..pool_count {
+            let pool_idx = r.read_u32::<LittleEndian>()? as usize;
+            let pool_id = r.read_u64::<LittleEndian>()?;
+            let bytes_used = r.read_u64::<LittleEndian>()?;
+            let count = r.read_u32::<LittleEndian>()?;
+            let mut records = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let cam_len = r.read_u16::<LittleEndian>()? as usize;
+                let mut cam = vec![0u8; cam_len];
+                r.read_exact(&mut cam)?;
+                let start_us = r.read_i64::<LittleEndian>()?;
+                let end_us = r.read_i64::<LittleEndian>()?;
+                records.push(ScannedRecord {
+                    camera_id: String::from_utf8_lossy(&cam).into_owned(),
+                    start_ts: DateTime::from_timestamp_micros(start_us).unwrap_or_else(Utc::now),
+                    end_ts: DateTime::from_timestamp_micros(end_us).unwrap_or_else(Utc::now),
+                    pool_idx,
+                    pool_id,
+                    record_offset: r.read_u64::<LittleEndian>()?,
+                    record_size: r.read_u64::<LittleEndian>()?,
+                    header_size: r.read_u16::<LittleEndian>()? as u64,
+                    sequence: Some(r.read_u64::<LittleEndian>()?)
+                        .filter(|&s| s != NO_SEQUENCE),
+                    stream: Stream::from_tag(r.read_u8()?),
+                });
+            }
+            pools.push(PoolSnapshot { pool_idx, pool_id, bytes_used, records });
+        }
+        Ok(IndexSnapshot { pool_capacity, pools })
+    }
+}
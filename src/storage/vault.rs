@@ -0,0 +1,352 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Evidence vault — segments placed on hold, outside the ring.
+//!
+//! Rotation overwrites every pool eventually, so footage that has to be kept
+//! (an incident, a legal request) is copied out of the pools into
+//! `base_path/vault/`, which the writer never touches. Each hold is one
+//! directory:
+//!
+//! ```text
+//! vault/hold_000001/
+//!   000000000123.mp4   one file per held segment (the record payload)
+//!   ...
+//!   hold.json          manifest: camera, range, expiry, segment list
+//! ```
+//!
+//! Segment files are synced before the manifest is written (tmp + rename),
+//! so after a crash a hold is either complete or has no manifest. Holds
+//! without a manifest are ignored and cleaned up by
+//! [`Vault::remove_incomplete`] at recorder startup.
+//!
+//! With encryption at rest enabled, held copies are sealed with the same key
+//! as the pools (the segment's camera and ID are the associated data), so
+//! footage doesn't end up in the clear just by being put on hold. Copies
+//! made before encryption was enabled stay readable.
+//!
+//! The vault keeps no state in memory: every call reads the directory, so
+//! holds placed by the CLI while the recorder runs are visible to the API
+//! straight away.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::error::{NvrError, Result};
+use crate::storage::chunk_pool::ChunkPool;
+use crate::storage::crypto::RecordCipher;
+use crate::storage::index::SegmentMeta;
+
+pub const VAULT_DIR: &str = "vault";
+const MANIFEST_FILE: &str = "hold.json";
+
+/// A time range of one camera's footage copied into the vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hold {
+    pub id: u64,
+    pub camera_id: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    /// Released automatically after this; `None` = kept until released.
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub reason: String,
+    pub segments: Vec<HeldSegment>,
+}
+
+/// One segment copied into a hold. `segment_id` is the ID it had in the
+/// ring, so existing segment URLs keep resolving after it is overwritten.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldSegment {
+    pub segment_id: u64,
+    pub start_ts: DateTime<Utc>,
+    pub end_ts: DateTime<Utc>,
+    pub size_bytes: u64,
+    /// The copy is sealed with the encryption key.
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+impl Hold {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|t| t <= now)
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.segments.iter().map(|s| s.size_bytes).sum()
+    }
+}
+
+/// What to place on hold.
+#[derive(Debug, Clone)]
+pub struct HoldRequest {
+    pub camera_id: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub reason: String,
+}
+
+/// Handle to `base_path/vault/`.
+#[derive(Debug, Clone)]
+pub struct Vault {
+    dir: PathBuf,
+    /// Seals new copies and opens sealed ones; `None` = copies in the clear.
+    cipher: Option<RecordCipher>,
+}
+
+impl Vault {
+    pub fn new(base_path: &Path, cipher: Option<RecordCipher>) -> Self {
+        Vault {
+            dir: base_path.join(VAULT_DIR),
+            cipher,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Copy `segments` (those of `req.camera_id` overlapping the range, as
+    /// returned by `SegmentIndex::segments_in_range`) out of `pool` into a
+    /// new hold. Segments overwritten before they could be copied are
+    /// skipped; it is an error if none could be copied.
+    pub fn create_hold(
+        &self,
+        req: &HoldRequest,
+        segments: &[SegmentMeta],
+        pool: &ChunkPool,
+        now: DateTime<Utc>,
+    ) -> Result<Hold> {
+        if req.from >= req.to {
+            return Err(NvrError::Config("Hold range must end after it starts".into()));
+        }
+        if segments.is_empty() {
+            return Err(NvrError::Storage(format!(
+                "No segments found for camera '{}' in range",
+                req.camera_id
+            )));
+        }
+
+        let (id, dir) = self.reserve_id()?;
+        let mut held = Vec::with_capacity(segments.len());
+        for seg in segments {
+            let data = match pool.read_segment_data(&seg.camera_id, &seg.location) {
+                Ok(d) => d,
+                Err(e) => {
+                    warn!(hold = id, segment_id = seg.segment_id, error = %e, "Segment not held");
+                    continue;
+                }
+            };
+            let contents = match &self.cipher {
+                Some(cipher) => cipher.seal(&aad(&seg.camera_id, seg.segment_id), &data)?,
+                None => data.clone(),
+            };
+            let path = dir.join(segment_file_name(seg.segment_id));
+            let mut f = File::create(&path)
+                .map_err(|e| NvrError::Storage(format!("create {path:?}: {e}")))?;
+            f.write_all(&contents)?;
+            f.sync_all()?;
+            held.push(HeldSegment {
+                segment_id: seg.segment_id,
+                start_ts: seg.start_ts,
+                end_ts: seg.end_ts,
+                size_bytes: data.len() as u64,
+                encrypted: self.cipher.is_some(),
+            });
+        }
+        if held.is_empty() {
+            let _ = fs::remove_dir_all(&dir);
+            return Err(NvrError::Storage(format!(
+                "All segments for camera '{}' in range were overwritten before they could be held",
+                req.camera_id
+            )));
+        }
+
+        let hold = Hold {
+            id,
+            camera_id: req.camera_id.clone(),
+            from: req.from,
+            to: req.to,
+            created_at: now,
+            expires_at: req.expires_at,
+            reason: req.reason.clone(),
+            segments: held,
+        };
+        write_manifest(&dir, &hold)?;
+        info!(
+            hold = id,
+            camera = hold.camera_id,
+            segments = hold.segments.len(),
+            bytes = hold.total_bytes(),
+            "Footage placed on hold"
+        );
+        Ok(hold)
+    }
+
+    /// All complete holds, oldest first.
+    pub fn list(&self) -> Result<Vec<Hold>> {
+        let mut holds = Vec::new();
+        for (id, dir) in self.hold_dirs()? {
+            match read_manifest(&dir) {
+                Ok(Some(hold)) => holds.push(hold),
+                Ok(None) => {}
+                Err(e) => warn!(hold = id, error = %e, "Ignoring unreadable hold manifest"),
+            }
+        }
+        holds.sort_by_key(|h| h.id);
+        Ok(holds)
+    }
+
+    pub fn get(&self, id: u64) -> Result<Option<Hold>> {
+        read_manifest(&self.hold_dir(id))
+    }
+
+    /// Delete a hold and its copies. Returns `false` if it doesn't exist.
+    pub fn release(&self, id: u64) -> Result<bool> {
+        let dir = self.hold_dir(id);
+        if !dir.join(MANIFEST_FILE).exists() {
+            return Ok(false);
+        }
+        // Manifest first: an interrupted release leaves an incomplete hold,
+        // never a manifest pointing at missing files.
+        fs::remove_file(dir.join(MANIFEST_FILE))?;
+        fs::remove_dir_all(&dir)?;
+        info!(hold = id, "Hold released");
+        Ok(true)
+    }
+
+    /// Release every hold that has expired by `now`. Returns their IDs.
+    pub fn purge_expired(&self, now: DateTime<Utc>) -> Result<Vec<u64>> {
+        let mut purged = Vec::new();
+        for hold in self.list()? {
+            if hold.is_expired(now) && self.release(hold.id)? {
+                purged.push(hold.id);
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Remove hold directories left without a manifest by a crash. Only
+    /// safe while no hold is being created, i.e. at recorder startup.
+    pub fn remove_incomplete(&self) -> Result<()> {
+        for (id, dir) in self.hold_dirs()? {
+            if !dir.join(MANIFEST_FILE).exists() {
+                warn!(hold = id, "Removing incomplete hold");
+                fs::remove_dir_all(&dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Payload of a held segment, from any hold of `camera_id`.
+    pub fn read_segment(&self, camera_id: &str, segment_id: u64) -> Result<Option<Vec<u8>>> {
+        for hold in self.list()? {
+            if hold.camera_id != camera_id {
+                continue;
+            }
+            let Some(held) = hold.segments.iter().find(|s| s.segment_id == segment_id) else {
+                continue;
+            };
+            let data = fs::read(self.segment_path(hold.id, segment_id))?;
+            if !held.encrypted {
+                return Ok(Some(data));
+            }
+            return match &self.cipher {
+                Some(cipher) => cipher.open(&aad(camera_id, segment_id), &data).map(Some),
+                None => Err(NvrError::Storage(format!(
+                    "Held segment {segment_id} of '{camera_id}' is encrypted and no key is configured"
+                ))),
+            };
+        }
+        Ok(None)
+    }
+
+    /// Path of a held segment's file, sealed if the segment is `encrypted`.
+    pub fn segment_path(&self, hold_id: u64, segment_id: u64) -> PathBuf {
+        self.hold_dir(hold_id).join(segment_file_name(segment_id))
+    }
+
+    fn hold_dir(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("hold_{id:06}"))
+    }
+
+    /// Existing hold directories (complete or not) with their IDs.
+    fn hold_dirs(&self) -> Result<Vec<(u64, PathBuf)>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(e) => e,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut dirs = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let id = entry
+                .file_name()
+                .to_str()
+                .and_then(|n| n.strip_prefix("hold_"))
+                .and_then(|n| n.parse::<u64>().ok());
+            if let Some(id) = id {
+                dirs.push((id, entry.path()));
+            }
+        }
+        Ok(dirs)
+    }
+
+    /// Claim the next free hold ID by creating its directory, so two
+    /// processes (recorder API and CLI) never pick the same one.
+    fn reserve_id(&self) -> Result<(u64, PathBuf)> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| NvrError::Storage(format!("Cannot create vault: {e}")))?;
+        let mut id = self.hold_dirs()?.iter().map(|(id, _)| id + 1).max().unwrap_or(1);
+        loop {
+            let dir = self.hold_dir(id);
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok((id, dir)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => id += 1,
+                Err(e) => {
+                    return Err(NvrError::Storage(format!("create hold {dir:?}: {e}")));
+                }
+            }
+        }
+    }
+}
+
+/// Binds a sealed copy to its segment, so one can't be swapped for another's.
+fn aad(camera_id: &str, segment_id: u64) -> Vec<u8> {
+    format!("vault/{camera_id}/{segment_id}").into_bytes()
+}
+
+fn segment_file_name(segment_id: u64) -> String {
+    format!("{segment_id:012}.mp4")
+}
+
+fn read_manifest(dir: &Path) -> Result<Option<Hold>> {
+    let data = match fs::read(dir.join(MANIFEST_FILE)) {
+        Ok(d) => d,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    serde_json::from_slice(&data)
+        .map(Some)
+        .map_err(|e| NvrError::Storage(format!("bad hold manifest in {dir:?}: {e}")))
+}
+
+fn write_manifest(dir: &Path, hold: &Hold) -> Result<()> {
+    let json = serde_json::to_vec_pretty(hold)
+        .map_err(|e| NvrError::Storage(format!("encode hold manifest: {e}")))?;
+    let tmp = dir.join(format!("{MANIFEST_FILE}.tmp"));
+    let mut f = File::create(&tmp)?;
+    f.write_all(&json)?;
+    f.sync_all()?;
+    fs::rename(&tmp, dir.join(MANIFEST_FILE))?;
+    Ok(())
+}
@@ -0,0 +1,175 @@
+// This software is provided for non-commercial use only.
+// Commercial use is strictly prohibited.
+// If you use, modify, or redistribute this software, you must provide proper attribution to the original author.
+// (c) 2026 Onur Tuna. All rights reserved.
+
+//! Segment thumbnails for the recordings timeline.
+//!
+//! A segment's thumbnail is its first keyframe (every segment starts on
+//! one), decoded in software and scaled to a small JPEG by
+//! [`playback::keyframe_jpeg`](crate::playback::keyframe_jpeg). Decoding is
+//! done on first request only; the JPEG is then cached on disk, keyed by
+//! segment ID:
+//!
+//! ```text
+//! base_path/thumbnails/<camera_id>/<segment_id>.jpg
+//! ```
+//!
+//! A segment's ID never changes and is never reused for other footage, so
+//! a cached thumbnail never goes stale; it only becomes unused once its
+//! segment is gone from the ring, archive and vault, and is then removed by
+//! [`ThumbnailCache::prune`]. With encryption at rest enabled, cached
+//! thumbnails are sealed with the same key as the recordings.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::debug;
+
+use crate::error::Result;
+use crate::storage::crypto::RecordCipher;
+
+pub const THUMBNAIL_DIR: &str = "thumbnails";
+
+/// Width of generated thumbnails in pixels; height follows the aspect ratio.
+pub const THUMBNAIL_WIDTH: u32 = 320;
+
+/// Handle to `base_path/thumbnails/`.
+#[derive(Clone)]
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    cipher: Option<RecordCipher>,
+}
+
+impl ThumbnailCache {
+    pub fn new(base_path: &Path, cipher: Option<RecordCipher>) -> Self {
+        ThumbnailCache {
+            dir: base_path.join(THUMBNAIL_DIR),
+            cipher,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The cached thumbnail of a segment, if there is one.
+    pub fn get(&self, camera_id: &str, segment_id: u64) -> Result<Option<Vec<u8>>> {
+        let Some(path) = self.path(camera_id, segment_id) else {
+            return Ok(None);
+        };
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match &self.cipher {
+            // Sealed with another key: made again and replaced.
+            Some(cipher) => Ok(cipher.open(&aad(camera_id, segment_id), &data).ok()),
+            None => Ok(Some(data)),
+        }
+    }
+
+    /// The thumbnail of a segment: cached, or made by `render` and cached.
+    /// `render` returns `Ok(None)` if the segment doesn't exist.
+    pub fn get_or_create(
+        &self,
+        camera_id: &str,
+        segment_id: u64,
+        render: impl FnOnce() -> Result<Option<Vec<u8>>>,
+    ) -> Result<Option<Vec<u8>>> {
+        if let Some(jpeg) = self.get(camera_id, segment_id)? {
+            return Ok(Some(jpeg));
+        }
+        let Some(jpeg) = render()? else {
+            return Ok(None);
+        };
+        if let Some(path) = self.path(camera_id, segment_id) {
+            self.store(&path, camera_id, segment_id, &jpeg)?;
+            debug!(camera = camera_id, segment_id, bytes = jpeg.len(), "Thumbnail cached");
+        }
+        Ok(Some(jpeg))
+    }
+
+    /// Cameras with cached thumbnails.
+    pub fn cameras(&self) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut cameras = Vec::new();
+        for entry in entries {
+            cameras.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        Ok(cameras)
+    }
+
+    /// Delete cached thumbnails of segments for which `keep(camera_id,
+    /// segment_id)` is false. Returns the number deleted.
+    pub fn prune(&self, keep: impl Fn(&str, u64) -> bool) -> Result<usize> {
+        let cameras = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut removed = 0;
+        for camera in cameras {
+            let camera = camera?;
+            let camera_id = camera.file_name().to_string_lossy().into_owned();
+            for entry in fs::read_dir(camera.path())? {
+                let path = entry?.path();
+                let segment_id = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_suffix(".jpg"))
+                    .and_then(|n| n.parse::<u64>().ok());
+                // Not a thumbnail (e.g. a store in progress), or still used.
+                if segment_id.is_none_or(|id| keep(&camera_id, id)) {
+                    continue;
+                }
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// `None` for camera IDs that aren't a single path component (they
+    /// can't have recordings, and mustn't reach outside the cache).
+    fn path(&self, camera_id: &str, segment_id: u64) -> Option<PathBuf> {
+        if camera_id.is_empty()
+            || camera_id == "."
+            || camera_id == ".."
+            || camera_id.chars().any(|c| c == '/' || c == '\\' || c.is_control())
+        {
+            return None;
+        }
+        Some(self.dir.join(camera_id).join(format!("{segment_id}.jpg")))
+    }
+
+    /// Write via a temp file, so a concurrent reader never sees half a JPEG.
+    fn store(&self, path: &Path, camera_id: &str, segment_id: u64, jpeg: &[u8]) -> Result<()> {
+        let data = match &self.cipher {
+            Some(cipher) => cipher.seal(&aad(camera_id, segment_id), jpeg)?,
+            None => jpeg.to_vec(),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        static TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+        let seq = TMP_SEQ.fetch_add(1, Ordering::Relaxed);
+        let tmp = path.with_extension(format!("jpg.{}.{seq}.tmp", std::process::id()));
+        fs::write(&tmp, &data)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Binds a sealed thumbnail to its segment, so one can't be swapped for
+/// another's.
+fn aad(camera_id: &str, segment_id: u64) -> Vec<u8> {
+    format!("thumbnail/{camera_id}/{segment_id}").into_bytes()
+}
@@ -10,8 +10,12 @@
 use chrono::Utc;
 use tempfile::TempDir;
 
-use nvr::storage::chunk_pool::ChunkPool;
+use nvr::error::NvrError;
+use nvr::storage::camera_registry::CameraRegistry;
+use nvr::storage::chunk_pool::{ChunkPool, Stream};
 use nvr::storage::index::SegmentIndex;
+use nvr::storage::retention::RetentionPolicy;
+use nvr::storage::snapshot::{snapshot_path, IndexSnapshot};
 
 fn tmp_dir() -> TempDir {
     tempfile::tempdir().expect("create tempdir")
@@ -66,8 +70,8 @@ fn test_multiple_cameras_interleaved() {
 fn test_pool_rotation_and_eviction() {
     let dir = tmp_dir();
     // Small pools: 512 bytes each, 2 pool files
-    // RecordHeader = 40 bytes, so 100 bytes payload => 140 bytes per record
-    // 512 / 140 = 3 records per pool
+    // RecordHeader = 64 bytes, so 100 bytes payload => 164 bytes per record
+    // 512 / 164 = 3 records per pool
     let pool_size: u64 = 512;
     let max_pools = 2;
     let mut pool = ChunkPool::open(dir.path(), pool_size, max_pools).expect("open pool");
@@ -113,12 +117,18 @@ fn test_index_eviction() {
         pool_id: 0,
         record_offset: 64,
         record_size: 100,
+        header_size: 64,
+        sequence: None,
+        stream: Stream::Main,
     };
     let loc1 = nvr::storage::chunk_pool::SegmentLocation {
         pool_idx: 1,
         pool_id: 1,
         record_offset: 64,
         record_size: 100,
+        header_size: 64,
+        sequence: None,
+        stream: Stream::Main,
     };
 
     index.insert("cam1", now, now, loc0.clone());
@@ -139,14 +149,17 @@ fn test_index_eviction() {
 async fn test_global_writer_end_to_end() {
     let dir = tmp_dir();
     let pool = ChunkPool::open(dir.path(), 1024 * 1024, 3).expect("open pool");
+    let pool = std::sync::Arc::new(parking_lot::RwLock::new(pool));
 
-    let (tx, index, _read_counters, handle) = nvr::storage::global_writer::spawn_writer(pool, 64);
+    let retention = std::sync::Arc::new(parking_lot::RwLock::new(RetentionPolicy::default()));
+    let (tx, index, handle) = nvr::storage::global_writer::spawn_writer(pool, retention, 64);
 
     let now = Utc::now();
     // Send 5 write requests from different "cameras"
     for i in 0..5 {
         let req = nvr::storage::global_writer::WriteRequest {
             camera_id: format!("cam{}", i % 2),
+            stream: Stream::Main,
             start_ts: now,
             end_ts: now,
             data: vec![0xFFu8; 50],
@@ -156,7 +169,7 @@ async fn test_global_writer_end_to_end() {
 
     // Drop sender so writer loop exits
     drop(tx);
-    handle.await.expect("writer task");
+    handle.join().expect("writer thread");
 
     let idx = index.read();
     assert_eq!(idx.len(), 5);
@@ -164,6 +177,80 @@ async fn test_global_writer_end_to_end() {
     assert_eq!(idx.segments_for_camera("cam1").len(), 2);
 }
 
+#[tokio::test]
+async fn test_rotation_waits_for_readers_without_holding_pool_lock() {
+    use std::time::Duration;
+
+    let dir = tmp_dir();
+    let pool = ChunkPool::open(dir.path(), 1024, 2).expect("open pool");
+    let counters = pool.read_counters.clone();
+    let pool = std::sync::Arc::new(parking_lot::RwLock::new(pool));
+    let retention = std::sync::Arc::new(parking_lot::RwLock::new(RetentionPolicy::default()));
+    let (tx, index, handle) = nvr::storage::global_writer::spawn_writer(pool.clone(), retention, 64);
+
+    let reader = counters.acquire(1);
+    let now = Utc::now();
+    // 164-byte records, 6 per pool: the 7th has to rotate into pool 1.
+    for _ in 0..7 {
+        let req = nvr::storage::global_writer::WriteRequest {
+            camera_id: "cam1".to_string(),
+            stream: Stream::Main,
+            start_ts: now,
+            end_ts: now,
+            data: vec![0x11u8; 100],
+        };
+        tx.send(req).await.expect("send");
+    }
+    while index.read().len() < 6 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // The writer is waiting for the reader, but the pool stays readable.
+    assert_eq!(index.read().len(), 6);
+    assert!(pool.try_read_for(Duration::from_millis(100)).is_some());
+
+    drop(reader);
+    drop(tx);
+    handle.join().expect("writer thread");
+    assert_eq!(index.read().len(), 7);
+    assert_eq!(pool.read().status().0, 1);
+}
+
+#[test]
+fn test_append_writes_without_holding_pool_lock() {
+    use nvr::storage::chunk_pool::NewRecord;
+
+    let dir = tmp_dir();
+    let now = Utc::now();
+    let pool = ChunkPool::open(dir.path(), 1024 * 1024, 2).expect("open pool");
+    let pool = std::sync::Arc::new(parking_lot::RwLock::new(pool));
+    let old = pool.write().append("cam1", now, now, &[1u8; 100]).expect("append");
+
+    let record = NewRecord {
+        camera_id: "cam1",
+        stream: Stream::Main,
+        start_ts: now,
+        end_ts: now,
+        data: &[2u8; 100],
+    };
+    let mut pending = pool.write().begin_append(&[record]).expect("begin");
+    // Reserved, but not counted until finished, and no second append can
+    // take the same space meanwhile.
+    assert_eq!(pool.read().status().1, 164);
+    assert!(pool.write().begin_append(&[record]).is_err());
+
+    // The write needs no lock: readers carry on while it runs.
+    let reader = pool.read();
+    pending.write();
+    assert_eq!(reader.read_segment_data("cam1", &old).expect("read"), [1u8; 100]);
+    drop(reader);
+
+    let locs = pool.write().finish_append(pending).expect("finish");
+    assert_eq!(pool.read().status().1, 2 * 164);
+    assert_eq!(pool.read().read_segment_data("cam1", &locs[0]).expect("read"), [2u8; 100]);
+}
+
 #[test]
 fn test_restart_recovery() {
     // Simulate: write some data, "crash" (drop pool), reopen, verify index rebuilt.
@@ -196,6 +283,1161 @@ fn test_restart_recovery() {
     }
 }
 
+fn save_snapshot(pool: &ChunkPool) {
+    let mut index = SegmentIndex::new();
+    index.rebuild_from_scanned(pool.scan_all_pools().expect("scan"));
+    IndexSnapshot::capture(pool, &index)
+        .save(&snapshot_path(pool.base_path()))
+        .expect("save snapshot");
+}
+
+#[test]
+fn test_torn_first_record_after_rotation_hides_previous_cycle() {
+    let dir = tmp_dir();
+    let now = Utc::now();
+    // 164-byte records, 6 per pool: the 13th record rotates into pool 0.
+    let first = {
+        let mut pool = ChunkPool::open(dir.path(), 1024, 2).expect("open");
+        for i in 0..12u8 {
+            pool.append("cam1", now, now, &[i; 100]).expect("append");
+        }
+        pool.append("cam1", now, now, &[12u8; 100]).expect("append")
+    };
+    assert_eq!((first.pool_idx, first.sequence), (0, Some(12)));
+
+    // Torn: the old cycle's records behind it still have valid checksums.
+    corrupt_byte(&dir.path().join("pool_000.bin"), first.record_offset + first.header_size + 1);
+
+    let mut pool = ChunkPool::open(dir.path(), 1024, 2).expect("reopen");
+    let records = pool.take_recovered();
+    assert_eq!(
+        records.iter().map(|r| r.sequence).collect::<Vec<_>>(),
+        (6..12).map(Some).collect::<Vec<_>>(),
+        "only the previous pool's records survive"
+    );
+    // Writing resumes over the torn record, and the new chain is found again.
+    let loc = pool.append("cam1", now, now, &[13u8; 100]).expect("append");
+    assert_eq!(loc.record_offset, first.record_offset);
+    drop(pool);
+    let mut pool = ChunkPool::open(dir.path(), 1024, 2).expect("reopen");
+    assert_eq!(pool.take_recovered().len(), 7);
+}
+
+#[test]
+fn test_index_snapshot_used_on_reopen() {
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024 * 1024;
+    let now = Utc::now();
+
+    let first = {
+        let mut pool = ChunkPool::open(dir.path(), pool_size, 3).expect("open");
+        let first = pool.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+        pool.append("cam2", now, now, &[0x22u8; 100]).expect("append");
+        save_snapshot(&pool);
+        // Written after the snapshot: must be picked up by the tail scan.
+        pool.append("cam1", now, now, &[0x33u8; 100]).expect("append");
+        first
+    };
+
+    // Damage a snapshotted record's payload. A full scan would drop it; the
+    // snapshot is trusted for everything it covers, so it is still indexed.
+    corrupt_byte(&dir.path().join("pool_000.bin"), first.record_offset + first.header_size + 1);
+
+    let mut pool = ChunkPool::open(dir.path(), pool_size, 3).expect("reopen");
+    let records = pool.take_recovered();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].record_offset, first.record_offset);
+    assert!(pool.take_recovered().is_empty(), "recovered records are handed over once");
+
+    // Appends resume after the tail record, not after the snapshot.
+    let loc = pool.append("cam1", now, now, &[0x44u8; 10]).expect("append");
+    assert_eq!(loc.record_offset, records[2].record_offset + records[2].record_size);
+}
+
+#[test]
+fn test_stale_index_snapshot_falls_back_to_scan() {
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024;
+    let now = Utc::now();
+
+    {
+        let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("open");
+        pool.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+        save_snapshot(&pool);
+        // 164-byte records, 6 per pool: wrap around so slot 0 gets a new pool_id.
+        for _ in 0..12 {
+            pool.append("cam1", now, now, &[0x22u8; 100]).expect("append");
+        }
+        assert_eq!(pool.pool_id(0), 2);
+    }
+
+    let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("reopen");
+    let scanned = pool.scan_all_pools().expect("scan");
+    let recovered = pool.take_recovered();
+    assert_eq!(recovered.len(), scanned.len());
+    assert!(recovered.iter().all(|r| r.pool_id >= 1), "no records from the overwritten pool");
+}
+
+#[test]
+fn test_segment_ids_stable_across_restart_and_rotation() {
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024;
+    let now = Utc::now();
+
+    // 164-byte records, 6 per pool: 8 appends rotate into pool_001.
+    let ids: Vec<u64> = {
+        let mut pool = ChunkPool::open(dir.path(), pool_size, 3).expect("open");
+        let mut index = SegmentIndex::new();
+        (0..8)
+            .map(|_| {
+                let loc = pool.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+                index.insert("cam1", now, now, loc)
+            })
+            .collect()
+    };
+    assert_eq!(ids, (0..8).collect::<Vec<_>>());
+
+    let mut pool = ChunkPool::open(dir.path(), pool_size, 3).expect("reopen");
+    let mut index = SegmentIndex::new();
+    index.rebuild_from_scanned(pool.take_recovered());
+    let recovered: Vec<u64> = index.all_segments().map(|s| s.segment_id).collect();
+    assert_eq!(recovered, ids, "IDs must not be renumbered on restart");
+
+    // New records continue the sequence instead of restarting at 0.
+    let loc = pool.append("cam1", now, now, &[0x22u8; 100]).expect("append");
+    assert_eq!(index.insert("cam1", now, now, loc), 8);
+
+    // Overwriting the oldest pool drops its IDs but doesn't shift the rest.
+    for _ in 0..10 {
+        pool.append("cam1", now, now, &[0x33u8; 100]).expect("append");
+    }
+    let mut index = SegmentIndex::new();
+    index.rebuild_from_scanned(pool.scan_all_pools().expect("scan"));
+    let first = index.all_segments().map(|s| s.segment_id).min();
+    assert_eq!(first, Some(6));
+    assert_eq!(index.all_segments().map(|s| s.segment_id).max(), Some(18));
+}
+
+#[test]
+fn test_migrate_pools_grows_and_shrinks_ring() {
+    use nvr::storage::migrate::{self, PoolLayout};
+
+    let dir = tmp_dir();
+    let now = Utc::now();
+    let layout = |pool_capacity, pool_count| PoolLayout { pool_capacity, pool_count };
+    let ids = |dir: &std::path::Path, cap, count| -> Vec<u64> {
+        let mut pool = ChunkPool::open(dir, cap, count).expect("open");
+        let mut index = SegmentIndex::new();
+        index.rebuild_from_scanned(pool.take_recovered());
+        let mut ids: Vec<u64> = index.all_segments().map(|s| s.segment_id).collect();
+        ids.sort();
+        ids
+    };
+
+    // 164-byte records, 6 per 1 KB pool: 10 records over two pools.
+    {
+        let mut pool = ChunkPool::open(dir.path(), 1024, 3).expect("open");
+        for i in 0..10u8 {
+            pool.append(&format!("cam{}", i % 2), now, now, &[i; 100]).expect("append");
+        }
+        save_snapshot(&pool);
+    }
+    assert!(migrate::migrate_pools(dir.path(), &[], layout(1024, 3), false).expect("noop").is_none());
+
+    // Grow: 2 KB × 4 holds everything in one pool.
+    let report = migrate::migrate_pools(dir.path(), &[], layout(2048, 4), false)
+        .expect("grow")
+        .expect("migrated");
+    assert_eq!((report.records_kept, report.records_dropped, report.pools_used), (10, 0, 1));
+    assert!(!snapshot_path(dir.path()).exists());
+    assert_eq!(migrate::detect_layout(dir.path(), &[]).expect("detect"), Some(layout(2048, 4)));
+    assert_eq!(ids(dir.path(), 2048, 4), (0..10).collect::<Vec<_>>());
+
+    // Shrink: a single 1 KB pool keeps the newest 6, IDs unchanged.
+    let report = migrate::migrate_pools(dir.path(), &[], layout(1024, 1), false)
+        .expect("shrink")
+        .expect("migrated");
+    assert_eq!((report.records_kept, report.records_dropped), (6, 4));
+    assert_eq!(ids(dir.path(), 1024, 1), (4..10).collect::<Vec<_>>());
+
+    let mut pool = ChunkPool::open(dir.path(), 1024, 1).expect("open");
+    let seg = pool.take_recovered().pop().expect("record");
+    let loc = nvr::storage::chunk_pool::SegmentLocation {
+        pool_idx: seg.pool_idx,
+        pool_id: seg.pool_id,
+        record_offset: seg.record_offset,
+        record_size: seg.record_size,
+        header_size: seg.header_size,
+        sequence: seg.sequence,
+        stream: Stream::Main,
+    };
+    assert_eq!(pool.read_segment_data("cam1", &loc).expect("read"), [9u8; 100]);
+    assert_eq!(pool.append("cam1", now, now, &[0u8; 10]).expect("append").sequence, Some(10));
+    drop(pool);
+
+    // After a grow, writing goes on in the first new slot and reaches the
+    // migrated footage only once the new slots are full.
+    let dir = tmp_dir();
+    {
+        let mut pool = ChunkPool::open(dir.path(), 1024, 3).expect("open");
+        for i in 0..10u8 {
+            pool.append("cam1", now, now, &[i; 100]).expect("append");
+        }
+    }
+    let report = migrate::migrate_pools(dir.path(), &[], layout(2048, 4), false)
+        .expect("grow")
+        .expect("migrated");
+    assert_eq!(report.pools_used, 1);
+    let mut pool = ChunkPool::open(dir.path(), 2048, 4).expect("open");
+    let loc = pool.append("cam1", now, now, &[0xEEu8; 100]).expect("append");
+    assert_eq!((loc.pool_idx, loc.sequence), (report.pools_used, Some(10)));
+    // Three 2 KB pools hold 12 records each.
+    for _ in 1..3 * 12 {
+        let loc = pool.append("cam1", now, now, &[0xEEu8; 100]).expect("append");
+        assert_ne!(loc.pool_idx, 0, "migrated pool overwritten early");
+    }
+    let mut kept = 0;
+    for seg in pool.scan_all_pools().expect("scan") {
+        if seg.pool_idx != 0 {
+            continue;
+        }
+        let loc = nvr::storage::chunk_pool::SegmentLocation {
+            pool_idx: seg.pool_idx,
+            pool_id: seg.pool_id,
+            record_offset: seg.record_offset,
+            record_size: seg.record_size,
+            header_size: seg.header_size,
+            sequence: seg.sequence,
+            stream: Stream::Main,
+        };
+        let i = seg.sequence.expect("sequence") as u8;
+        assert_eq!(pool.read_segment_data("cam1", &loc).expect("read"), [i; 100]);
+        kept += 1;
+    }
+    assert_eq!(kept, 10);
+    // The next pool is the oldest: slot 0.
+    let loc = pool.append("cam1", now, now, &[0xEEu8; 100]).expect("append");
+    assert_eq!(loc.pool_idx, 0);
+}
+
+#[test]
+fn test_interrupted_migration_is_discarded_or_finished() {
+    use nvr::storage::migrate::{self, PoolLayout, COMMIT_FILE};
+
+    let dir = tmp_dir();
+    let now = Utc::now();
+    {
+        let mut pool = ChunkPool::open(dir.path(), 1024, 2).expect("open");
+        pool.append("cam1", now, now, &[1u8; 100]).expect("append");
+    }
+    let original = std::fs::read(dir.path().join("pool_000.bin")).expect("read");
+
+    // Crash before commit: the staged copy is thrown away.
+    let staged = dir.path().join("pool_000.bin.migrate");
+    std::fs::write(&staged, b"partial").expect("stage");
+    migrate::recover(dir.path(), &[]).expect("recover");
+    assert!(!staged.exists());
+    assert_eq!(std::fs::read(dir.path().join("pool_000.bin")).expect("read"), original);
+
+    // Crash after commit: the staged copy replaces the old pools.
+    let layout = PoolLayout { pool_capacity: 2048, pool_count: 1 };
+    migrate::migrate_pools(dir.path(), &[], layout, true).expect("dry run");
+    assert_eq!(std::fs::read(dir.path().join("pool_000.bin")).expect("read"), original);
+    let mut grown = original.clone();
+    grown.resize(64 + 2048, 0);
+    std::fs::write(&staged, &grown).expect("stage");
+    std::fs::write(
+        dir.path().join(COMMIT_FILE),
+        format!("{}\n", dir.path().join("pool_000.bin").display()),
+    )
+    .expect("commit");
+    migrate::recover(dir.path(), &[]).expect("recover");
+    assert!(!staged.exists() && !dir.path().join("pool_001.bin").exists());
+    assert!(!dir.path().join(COMMIT_FILE).exists());
+    assert_eq!(migrate::detect_layout(dir.path(), &[]).expect("detect"), Some(layout));
+}
+
+#[test]
+fn test_long_camera_ids_round_trip() {
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024 * 1024;
+    let now = Utc::now();
+    // Same first 16 bytes; the second one is cut mid-character there.
+    let cams = ["warehouse_dock_east", "warehouse_dock_eastside", "warehouse_dock_é"];
+
+    {
+        let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("open");
+        for cam in cams {
+            let loc = pool.append(cam, now, now, cam.as_bytes()).expect("append");
+            assert_eq!(pool.read_segment_data(cam, &loc).expect("read"), cam.as_bytes());
+            assert!(pool.read_segment_data("warehouse_dock_e", &loc).is_err());
+        }
+    }
+
+    let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("reopen");
+    let mut index = SegmentIndex::new();
+    index.rebuild_from_scanned(pool.take_recovered());
+    assert_eq!(index.cameras(), cams);
+    for cam in cams {
+        let segs = index.segments_for_camera(cam);
+        assert_eq!(segs.len(), 1);
+        let data = pool.read_segment_data(cam, &segs[0].location).expect("read");
+        assert_eq!(data, cam.as_bytes());
+    }
+
+    // Keys stay stable across restarts: no new registry entries.
+    let registry_len = std::fs::metadata(dir.path().join("cameras.registry")).unwrap().len();
+    pool.append(cams[1], now, now, b"again").expect("append");
+    assert_eq!(
+        std::fs::metadata(dir.path().join("cameras.registry")).unwrap().len(),
+        registry_len
+    );
+}
+
+#[test]
+fn test_camera_id_validation_rejects_collisions() {
+    use nvr::config::{validate_cameras, CameraConfig};
+
+    let cam = |id: &str| CameraConfig {
+        id: id.to_string(),
+        name: id.to_string(),
+        url: "rtsp://example".to_string(),
+        max_reconnect_attempts: 0,
+        min_retention_days: 0,
+        max_share: 1.0,
+        priority: 0,
+        record_audio: false,
+        substream_url: None,
+    };
+    assert!(validate_cameras(&[cam("warehouse_dock_east"), cam("warehouse_dock_eastside")]).is_ok());
+    assert!(validate_cameras(&[cam("cam1"), cam("cam1")]).is_err());
+    assert!(validate_cameras(&[cam("warehouse_dock_eastside"), cam("warehouse_dock_e")]).is_err());
+    assert!(validate_cameras(&[cam("a/b")]).is_err());
+    assert!(validate_cameras(&[cam("")]).is_err());
+    // Used as a directory name by the archive and thumbnail cache.
+    assert!(validate_cameras(&[cam("..")]).is_err());
+    assert!(validate_cameras(&[cam(".")]).is_err());
+    assert!(validate_cameras(&[cam("...")]).is_ok());
+}
+
+#[test]
+fn test_striped_volumes_and_offline_volume() {
+    let dir = tmp_dir();
+    let disks = [dir.path().join("disk0"), dir.path().join("disk1")];
+    for d in &disks {
+        std::fs::create_dir(d).unwrap();
+    }
+    let pool_size: u64 = 1024;
+    let now = Utc::now();
+
+    // 164-byte records, 6 per pool: 14 appends fill slots 0 and 1 and start 2.
+    {
+        let mut pool = ChunkPool::open_volumes(dir.path(), &disks, pool_size, 4).expect("open");
+        for _ in 0..14 {
+            pool.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+        }
+        assert_eq!(pool.status().0, 2);
+    }
+    assert!(disks[0].join("pool_000.bin").exists());
+    assert!(disks[1].join("pool_001.bin").exists());
+    assert!(disks[0].join("pool_002.bin").exists());
+    assert!(!dir.path().join("pool_000.bin").exists());
+
+    // disk1 disappears: startup still works, its slots are skipped.
+    let unplugged = dir.path().join("disk1.unplugged");
+    std::fs::rename(&disks[1], &unplugged).unwrap();
+    {
+        let mut pool = ChunkPool::open_volumes(dir.path(), &disks, pool_size, 4).expect("open");
+        assert!(!pool.is_online(1) && !pool.is_online(3));
+        assert!(!disks[1].exists(), "missing volumes are not created");
+        assert_eq!(pool.take_recovered().len(), 8);
+        assert_eq!(pool.next_write_idx(), 0);
+
+        // Fill slot 2 and rotate past the offline slot 3 into slot 0.
+        for _ in 0..6 {
+            pool.append("cam1", now, now, &[0x22u8; 100]).expect("append");
+        }
+        let (idx, _, _) = pool.status();
+        assert_eq!(idx, 0);
+        assert_eq!(pool.pool_id(0), 4);
+    }
+
+    // disk1 comes back: its (older) slot is recovered again and ordered
+    // before the newer pools.
+    std::fs::rename(&unplugged, &disks[1]).unwrap();
+    let mut pool = ChunkPool::open_volumes(dir.path(), &disks, pool_size, 4).expect("open");
+    assert!((0..4).all(|i| pool.is_online(i)));
+    let records = pool.take_recovered();
+    let pools: Vec<_> = records.iter().map(|r| r.pool_idx).collect();
+    assert_eq!(pools, [vec![1; 6], vec![2; 6], vec![0; 2]].concat());
+    assert_eq!(pool.status().0, 0);
+}
+
+#[test]
+fn test_subsecond_timestamps_survive_restart() {
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024 * 1024;
+
+    let start = chrono::DateTime::from_timestamp_micros(1_771_500_000_123_456).unwrap();
+    let end = start + chrono::Duration::milliseconds(750);
+
+    {
+        let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("open");
+        pool.append("cam1", start, end, b"short-segment").expect("append");
+    }
+
+    let pool = ChunkPool::open(dir.path(), pool_size, 2).expect("reopen");
+    let records = pool.scan_all_pools().expect("scan");
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].start_ts, start);
+    assert_eq!(records[0].end_ts, end);
+    assert_eq!((records[0].end_ts - records[0].start_ts).num_milliseconds(), 750);
+}
+
+#[test]
+fn test_legacy_nrec_records_still_scanned() {
+    use std::io::{Seek, SeekFrom, Write};
+    use nvr::storage::chunk_pool::{LEGACY_RECORD_HEADER_SIZE, POOL_HEADER_SIZE};
+
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024 * 1024;
+
+    // Lay down a legacy 40-byte `NREC` record by hand, as an older build would have.
+    {
+        let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("open");
+        let mut f = std::fs::OpenOptions::new()
+            .write(true)
+            .open(pool.pool_path(0))
+            .expect("open pool file");
+        f.seek(SeekFrom::Start(POOL_HEADER_SIZE)).unwrap();
+        f.write_all(b"NREC").unwrap();
+        let mut cam = [0u8; 16];
+        cam[..4].copy_from_slice(b"old1");
+        f.write_all(&cam).unwrap();
+        f.write_all(&1_771_500_000i64.to_le_bytes()).unwrap();
+        f.write_all(&1_771_500_060i64.to_le_bytes()).unwrap();
+        f.write_all(&5u32.to_le_bytes()).unwrap();
+        f.write_all(b"hello").unwrap();
+        // Longer IDs were cut to the 16 bytes the header holds.
+        f.write_all(b"NREC").unwrap();
+        f.write_all(b"parking-garage-l").unwrap();
+        f.write_all(&1_771_500_060i64.to_le_bytes()).unwrap();
+        f.write_all(&1_771_500_120i64.to_le_bytes()).unwrap();
+        f.write_all(&4u32.to_le_bytes()).unwrap();
+        f.write_all(b"long").unwrap();
+        drop(f);
+
+        // Reopen so the pool picks up the legacy record's bytes_used, then
+        // append a new-format record right after it.
+        drop(pool);
+        pool = ChunkPool::open(dir.path(), pool_size, 2).expect("reopen");
+        let now = Utc::now();
+        pool.append("new1", now, now, b"world!").expect("append");
+    }
+
+    let pool = ChunkPool::open(dir.path(), pool_size, 2).expect("reopen");
+    let records = pool.scan_all_pools().expect("scan");
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].camera_id, "old1");
+    assert_eq!(records[0].header_size, LEGACY_RECORD_HEADER_SIZE);
+    assert_eq!(records[0].start_ts.timestamp(), 1_771_500_000);
+    assert_eq!(records[0].end_ts.timestamp(), 1_771_500_060);
+    assert_eq!(records[1].camera_id, "parking-garage-l");
+    assert_eq!(records[2].camera_id, "new1");
+
+    let mut index = SegmentIndex::new();
+    index.rebuild_from_scanned(records);
+    let old = index.segments_for_camera("old1");
+    assert_eq!(pool.read_segment_data("old1", &old[0].location).expect("read"), b"hello");
+    // Read back under the camera's full ID, which the header only has a prefix of.
+    let long = index.segments_for_camera("parking-garage-l");
+    let data = pool.read_segment_data("parking-garage-level-2", &long[0].location).expect("read");
+    assert_eq!(data, b"long");
+    let new = index.segments_for_camera("new1");
+    assert_eq!(pool.read_segment_data("new1", &new[0].location).expect("read"), b"world!");
+}
+
+/// Flip one byte at `offset` in a pool file, simulating on-disk damage.
+fn corrupt_byte(path: &std::path::Path, offset: u64) {
+    use std::io::{Read, Seek, SeekFrom, Write};
+    let mut f = std::fs::OpenOptions::new().read(true).write(true).open(path).unwrap();
+    let mut b = [0u8; 1];
+    f.seek(SeekFrom::Start(offset)).unwrap();
+    f.read_exact(&mut b).unwrap();
+    f.seek(SeekFrom::Start(offset)).unwrap();
+    f.write_all(&[b[0] ^ 0xFF]).unwrap();
+}
+
+#[test]
+fn test_torn_tail_record_skipped_and_overwritten() {
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024 * 1024;
+    let now = Utc::now();
+
+    let torn_loc = {
+        let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("open");
+        pool.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+        pool.append("cam1", now, now, &[0x22u8; 100]).expect("append")
+    };
+    // Damage the tail of the last record's payload, as a power cut would.
+    corrupt_byte(&dir.path().join("pool_000.bin"), torn_loc.record_offset + torn_loc.record_size - 1);
+
+    let cameras = CameraRegistry::open(dir.path()).expect("registry");
+    let scan = ChunkPool::scan_pool(&dir.path().join("pool_000.bin"), 0, 0, pool_size, &cameras)
+        .expect("scan");
+    assert_eq!(scan.records.len(), 1);
+    assert_eq!(scan.corrupt.len(), 1);
+    assert_eq!(scan.corrupt[0].record_offset, torn_loc.record_offset);
+
+    // On reopen the writer resumes right where the torn record started.
+    let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("reopen");
+    let loc = pool.append("cam1", now, now, &[0x33u8; 10]).expect("append");
+    assert_eq!(loc.record_offset, torn_loc.record_offset);
+    assert_eq!(pool.scan_all_pools().expect("scan").len(), 2);
+}
+
+#[test]
+fn test_corrupt_middle_record_skipped() {
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024 * 1024;
+    let now = Utc::now();
+
+    let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("open");
+    pool.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+    let bad = pool.append("cam2", now, now, &[0x22u8; 100]).expect("append");
+    pool.append("cam3", now, now, &[0x33u8; 100]).expect("append");
+    corrupt_byte(pool.pool_path(0), bad.record_offset + bad.header_size + 10);
+
+    let records = pool.scan_all_pools().expect("scan");
+    let cams: Vec<_> = records.iter().map(|r| r.camera_id.as_str()).collect();
+    assert_eq!(cams, ["cam1", "cam3"]);
+
+    // Reads only catch the damage when verification is enabled.
+    assert!(pool.read_segment_data("cam2", &bad).is_ok());
+    pool.set_verify_reads(true);
+    assert!(pool.read_segment_data("cam2", &bad).is_err());
+}
+
+#[test]
+fn test_read_after_rotation_reports_evicted() {
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024;
+    let now = Utc::now();
+
+    let mut writer = ChunkPool::open(dir.path(), pool_size, 2).expect("open");
+    let loc = writer.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+    // A separately opened pool (like the API's export path) still has the
+    // old pool_id in memory, so only the on-disk check can catch it.
+    let reader = ChunkPool::open(dir.path(), pool_size, 2).expect("open reader");
+    assert_eq!(reader.read_segment_data("cam1", &loc).expect("read"), [0x11u8; 100]);
+    assert!(matches!(
+        reader.read_segment_data("cam2", &loc),
+        Err(NvrError::SegmentEvicted { .. })
+    ));
+
+    // 164-byte records, 6 per pool: wrap around and overwrite slot 0 with
+    // another camera's data at the same offset.
+    for _ in 0..12 {
+        writer.append("cam2", now, now, &[0x22u8; 100]).expect("append");
+    }
+    assert_eq!(writer.pool_id(0), 2);
+    for pool in [&writer, &reader] {
+        assert!(matches!(
+            pool.read_segment_data("cam1", &loc),
+            Err(NvrError::SegmentEvicted { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_coalesced_direct_writes_sync_and_read_back() {
+    use nvr::config::{DurabilityConfig, DurabilityMode};
+    use nvr::storage::chunk_pool::NewRecord;
+
+    let dir = tmp_dir();
+    let pool_size: u64 = 16 * 1024;
+    let now = Utc::now();
+    let mut pool = ChunkPool::open(dir.path(), pool_size, 4).expect("open");
+    // Falls back to buffered writes where the filesystem lacks O_DIRECT;
+    // the result must be the same either way.
+    pool.set_durability(DurabilityConfig {
+        mode: DurabilityMode::PerRecord,
+        direct_io: true,
+        ..DurabilityConfig::default()
+    });
+
+    // Odd sizes so records straddle 4 KiB blocks; two batches so the second
+    // starts mid-block, and enough data to rotate inside a batch.
+    let payloads: Vec<Vec<u8>> = (0..40).map(|i| vec![i as u8; 1000 + i * 7]).collect();
+    let mut locs = Vec::new();
+    for chunk in payloads.chunks(20) {
+        let records: Vec<NewRecord> = chunk
+            .iter()
+            .map(|data| NewRecord { camera_id: "cam1", stream: Stream::Main, start_ts: now, end_ts: now, data })
+            .collect();
+        locs.extend(pool.append_batch(&records).expect("append batch"));
+    }
+    assert!(locs.iter().any(|l| l.pool_idx != 0), "batch spans a rotation");
+    for (loc, data) in locs.iter().zip(&payloads) {
+        assert_eq!(&pool.read_segment_data("cam1", loc).expect("read"), data);
+    }
+
+    let stats = pool.write_stats();
+    assert_eq!(stats.records, 40);
+    assert!(stats.writes < stats.records, "records were coalesced");
+    assert!(stats.syncs >= stats.writes, "every write synced");
+    assert_eq!(pool.sync_deadline(), None);
+    for (i, slot) in (0..4).map(|i| (i, dir.path().join(format!("pool_{i:03}.bin")))) {
+        let len = std::fs::metadata(&slot).expect("pool file").len();
+        assert_eq!(len, 64 + pool_size, "pool {i} keeps its size");
+    }
+    drop(pool);
+
+    let mut reopened = ChunkPool::open(dir.path(), pool_size, 4).expect("reopen");
+    let recovered = reopened.take_recovered();
+    assert_eq!(recovered.len(), 40);
+    assert_eq!(
+        recovered.iter().map(|r| r.sequence).collect::<Vec<_>>(),
+        (0..40).map(Some).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_syncs_run_without_holding_pool_lock() {
+    use nvr::config::{DurabilityConfig, DurabilityMode};
+    use nvr::storage::chunk_pool::NewRecord;
+
+    let dir = tmp_dir();
+    let now = Utc::now();
+    let mut pool = ChunkPool::open(dir.path(), 1024 * 1024, 2).expect("open");
+    pool.set_durability(DurabilityConfig {
+        mode: DurabilityMode::PerRecord,
+        ..DurabilityConfig::default()
+    });
+    let pool = parking_lot::RwLock::new(pool);
+    let record =
+        NewRecord { camera_id: "cam1", stream: Stream::Main, start_ts: now, end_ts: now, data: &[7u8; 100] };
+
+    // `per_record`: the write step syncs, with readers still served.
+    let mut pending = pool.write().begin_append(&[record]).expect("begin");
+    let reader = pool.read();
+    pending.write();
+    drop(reader);
+    pool.write().finish_append(pending).expect("finish");
+    assert_eq!(pool.read().write_stats().syncs, 1);
+    assert_eq!(pool.read().sync_deadline(), None);
+
+    // An idle sync works on its own handle the same way.
+    pool.write().set_durability(DurabilityConfig {
+        mode: DurabilityMode::Interval,
+        sync_interval_ms: 60_000,
+        ..DurabilityConfig::default()
+    });
+    pool.write().append("cam1", now, now, &[8u8; 100]).expect("append");
+    assert_eq!(pool.read().write_stats().syncs, 1, "not due yet");
+    let mut pending = pool.write().begin_sync().expect("begin sync").expect("sync pending");
+    let reader = pool.read();
+    pending.run();
+    drop(reader);
+    pool.write().finish_sync(pending).expect("finish sync");
+    assert_eq!(pool.read().write_stats().syncs, 2);
+    assert!(pool.write().begin_sync().expect("begin sync").is_none());
+}
+
+#[tokio::test]
+async fn test_overload_spills_in_order_and_drops_by_priority() {
+    use nvr::config::{OverloadConfig, OverloadPolicy};
+    use nvr::overload::{CameraSpill, OverloadMonitor, Submitted};
+    use nvr::storage::global_writer::WriteRequest;
+
+    let monitor = std::sync::Arc::new(OverloadMonitor::new(&OverloadConfig {
+        policy: OverloadPolicy::DropByPriority,
+        spill_buffer_mb: 1,
+    }));
+    monitor.register("door", 10);
+    monitor.register("yard", 0);
+    let mut door = CameraSpill::new("door".into(), monitor.clone());
+    let mut yard = CameraSpill::new("yard".into(), monitor.clone());
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let now = Utc::now();
+    let req = |camera: &str, byte: u8| WriteRequest {
+        camera_id: camera.to_string(),
+        stream: Stream::Main,
+        start_ts: now,
+        end_ts: now,
+        data: vec![byte; 400 * 1024],
+    };
+
+    // The queue takes one segment; the rest spill, and the fourth pushes
+    // the oldest spilled one out of the 1 MB buffer.
+    assert_eq!(door.submit(&tx, req("door", 1)), Submitted::Queued);
+    assert_eq!(door.submit(&tx, req("door", 2)), Submitted::Spilled);
+    assert_eq!(door.submit(&tx, req("door", 3)), Submitted::Spilled);
+    assert_eq!(door.submit(&tx, req("door", 4)), Submitted::Spilled);
+    let load = monitor.camera("door").expect("door");
+    assert_eq!((load.spilled_segments, load.dropped_segments), (2, 1));
+
+    // A lower-priority camera gives way while "door" is spilling.
+    assert_eq!(yard.submit(&tx, req("yard", 9)), Submitted::Dropped);
+    assert_eq!(monitor.camera("yard").expect("yard").dropped_segments, 1);
+
+    // A substream spills on its own, adding to the camera's spill level.
+    let mut door_sub = CameraSpill::new("door".into(), monitor.clone()).with_stream(Stream::Sub);
+    let (sub_tx, mut sub_rx) = tokio::sync::mpsc::channel(1);
+    assert_eq!(door_sub.submit(&sub_tx, req("door", 5)), Submitted::Queued);
+    assert_eq!(door_sub.submit(&sub_tx, req("door", 6)), Submitted::Spilled);
+    assert_eq!(monitor.camera("door").expect("door").spilled_segments, 3);
+    sub_rx.recv().await.expect("queued");
+    assert!(door_sub.drain_one(&sub_tx).await);
+    assert_eq!(monitor.camera("door").expect("door").spilled_segments, 2);
+
+    let mut received = vec![rx.recv().await.expect("queued").data[0]];
+    while !door.is_empty() {
+        assert!(door.drain_one(&tx).await);
+        received.push(rx.recv().await.expect("drained").data[0]);
+    }
+    assert_eq!(received, [1, 3, 4], "spilled segments keep their order");
+    assert_eq!(monitor.camera("door").expect("door").spilled_bytes, 0);
+
+    // Backlog cleared: "yard" gets through again.
+    assert_eq!(yard.submit(&tx, req("yard", 9)), Submitted::Queued);
+}
+
+#[tokio::test]
+async fn test_failing_slot_is_quarantined_without_losing_segments() {
+    use nvr::storage::chunk_pool::SlotHealth;
+    use nvr::storage::global_writer::{spawn_writer, WriteRequest};
+
+    let dir = tmp_dir();
+    let pool = ChunkPool::open(dir.path(), 1024, 3).expect("open");
+    // Slot 1 can no longer be opened for writing once the ring gets there.
+    let bad = dir.path().join("pool_001.bin");
+    std::fs::remove_file(&bad).expect("remove");
+    std::fs::create_dir(&bad).expect("mkdir");
+
+    let pool = std::sync::Arc::new(parking_lot::RwLock::new(pool));
+    let retention = std::sync::Arc::new(parking_lot::RwLock::new(RetentionPolicy::default()));
+    let (tx, index, handle) = spawn_writer(pool.clone(), retention, 64);
+    let now = Utc::now();
+    // 164-byte records, 6 per pool: the 7th needs a rotation.
+    for i in 0..10u8 {
+        let req = WriteRequest {
+            camera_id: "cam1".to_string(),
+            stream: Stream::Main,
+            start_ts: now,
+            end_ts: now,
+            data: vec![i; 100],
+        };
+        tx.send(req).await.expect("send");
+    }
+    drop(tx);
+    handle.join().expect("writer thread");
+
+    let p = pool.read();
+    assert_eq!(p.slot_health(1), SlotHealth::Failed);
+    assert_eq!(p.health(), SlotHealth::Degraded);
+    assert_eq!(p.write_idx, 2);
+    assert_eq!(p.next_write_idx(), 0, "rotation skips the quarantined slot");
+    let idx = index.read();
+    let segs = idx.segments_for_camera("cam1");
+    assert_eq!(segs.len(), 10);
+    for (i, seg) in segs.iter().enumerate() {
+        assert_eq!(p.read_segment_data("cam1", &seg.location).expect("read"), [i as u8; 100]);
+    }
+}
+
+#[test]
+fn test_encrypted_records_round_trip_and_mixed_pools() {
+    use nvr::storage::crypto::RecordCipher;
+
+    let dir = tmp_dir();
+    let key = RecordCipher::new(&[7u8; 32]);
+    let now = Utc::now();
+    let mut pool = ChunkPool::open(dir.path(), 1024 * 1024, 3).expect("open");
+    let plain = pool.append("cam1", now, now, b"PLAINTEXT-FOOTAGE").expect("append");
+    pool.set_encryption(Some(key.clone()));
+    let sealed = pool.append("cam1", now, now, b"SECRET-FOOTAGE").expect("append sealed");
+    assert_eq!(pool.read_segment_data("cam1", &plain).expect("read plain"), b"PLAINTEXT-FOOTAGE");
+    assert_eq!(pool.read_segment_data("cam1", &sealed).expect("read sealed"), b"SECRET-FOOTAGE");
+    drop(pool);
+
+    let raw = std::fs::read(dir.path().join("pool_000.bin")).expect("raw pool");
+    assert!(!raw.windows(14).any(|w| w == b"SECRET-FOOTAGE"), "payload is not on disk in the clear");
+    assert_eq!(
+        ChunkPool::read_pool_key_id(&dir.path().join("pool_000.bin")).expect("key id"),
+        key.key_id()
+    );
+
+    // Scanning needs no key; reading sealed footage does, and the right one.
+    let mut keyless = ChunkPool::open(dir.path(), 1024 * 1024, 3).expect("reopen");
+    assert_eq!(keyless.take_recovered().len(), 2);
+    assert_eq!(keyless.read_segment_data("cam1", &plain).expect("read plain"), b"PLAINTEXT-FOOTAGE");
+    assert!(keyless.read_segment_data("cam1", &sealed).is_err());
+    keyless.set_encryption(Some(RecordCipher::new(&[8u8; 32])));
+    assert!(keyless.read_segment_data("cam1", &sealed).is_err(), "wrong key is rejected");
+    keyless.set_encryption(Some(key));
+    assert_eq!(keyless.read_segment_data("cam1", &sealed).expect("read sealed"), b"SECRET-FOOTAGE");
+}
+
+#[tokio::test]
+async fn test_replica_mirrors_segments_and_serves_lost_ones() {
+    use nvr::config::{DurabilityConfig, ReplicaConfig};
+    use nvr::storage::global_writer::{spawn_writer_with, WriteRequest, WriterHooks};
+    use nvr::storage::replica::{spawn_replicator, Replica};
+
+    let primary_dir = tmp_dir();
+    let replica_dir = tmp_dir();
+    let config = ReplicaConfig {
+        base_path: replica_dir.path().to_path_buf(),
+        chunk_size_mb: 1,
+        max_pools: 2,
+        queue_size: 64,
+    };
+    let replica = std::sync::Arc::new(
+        Replica::open(&config, DurabilityConfig::default(), None).expect("open replica"),
+    );
+    let (replicator, replica_handle) = spawn_replicator(replica.clone(), config.queue_size);
+
+    let pool = ChunkPool::open(primary_dir.path(), 1024 * 1024, 3).expect("open");
+    let pool = std::sync::Arc::new(parking_lot::RwLock::new(pool));
+    let retention = std::sync::Arc::new(parking_lot::RwLock::new(RetentionPolicy::default()));
+    let hooks = WriterHooks { replicator: Some(replicator), ..WriterHooks::default() };
+    let (tx, index, handle) = spawn_writer_with(pool.clone(), retention, 64, hooks);
+    let start = Utc::now();
+    for i in 0..5u8 {
+        let req = WriteRequest {
+            camera_id: "cam1".to_string(),
+            stream: Stream::Main,
+            start_ts: start + chrono::Duration::seconds(i as i64),
+            end_ts: start + chrono::Duration::seconds(i as i64 + 1),
+            data: vec![i; 1000],
+        };
+        tx.send(req).await.expect("send");
+    }
+    drop(tx);
+    handle.join().expect("writer thread");
+    replica_handle.join().expect("replica thread");
+
+    let stats = replica.stats();
+    assert_eq!((stats.segments, stats.mirrored, stats.skipped, stats.failed), (5, 5, 0, 0));
+
+    // Lose the primary disk: every segment is still served by the replica.
+    std::fs::remove_file(primary_dir.path().join("pool_000.bin")).expect("remove");
+    let segs: Vec<_> = index.read().segments_for_camera("cam1").into_iter().cloned().collect();
+    assert_eq!(segs.len(), 5);
+    for (i, seg) in segs.iter().enumerate() {
+        assert!(pool.read().read_segment_data("cam1", &seg.location).is_err());
+        assert_eq!(replica.read_segment(seg).expect("replica read"), Some(vec![i as u8; 1000]));
+    }
+    drop(replica);
+
+    let reopened = Replica::open(&config, DurabilityConfig::default(), None).expect("reopen replica");
+    assert_eq!(reopened.index().read().len(), 5, "replica index rebuilt from its pools");
+    drop(reopened);
+
+    // A layout change is never migrated implicitly: it may drop footage.
+    let config = ReplicaConfig { max_pools: 3, ..config };
+    assert!(matches!(
+        Replica::open(&config, DurabilityConfig::default(), None),
+        Err(NvrError::Config(_))
+    ));
+    let layout = nvr::storage::migrate::PoolLayout { pool_capacity: 1024 * 1024, pool_count: 3 };
+    nvr::storage::migrate::migrate_pools(&config.base_path, &[], layout, false).expect("migrate");
+    let migrated = Replica::open(&config, DurabilityConfig::default(), None).expect("open migrated");
+    assert_eq!(migrated.index().read().len(), 5);
+}
+
+#[tokio::test]
+async fn test_evicted_pools_are_archived_and_queried_with_the_ring() {
+    use chrono::TimeZone;
+    use nvr::config::ArchiveConfig;
+    use nvr::storage::archive::{self, Archive};
+    use nvr::storage::global_writer::{spawn_writer_with, WriteRequest, WriterHooks};
+
+    let dir = tmp_dir();
+    let archive_dir = tmp_dir();
+    let config = ArchiveConfig {
+        path: archive_dir.path().to_path_buf(),
+        cameras: vec!["door".to_string()],
+        min_interval_secs: 120,
+        max_age_days: 0,
+    };
+    let archive = std::sync::Arc::new(Archive::open(&config, None).expect("open archive"));
+
+    let pool = ChunkPool::open(dir.path(), 1024, 2).expect("open");
+    let pool = std::sync::Arc::new(parking_lot::RwLock::new(pool));
+    let retention = std::sync::Arc::new(parking_lot::RwLock::new(RetentionPolicy::default()));
+    let hooks = WriterHooks { archive: Some(archive.clone()), ..WriterHooks::default() };
+    let (tx, index, handle) = spawn_writer_with(pool.clone(), retention, 64, hooks);
+    let start = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+    // 164-byte records, 6 per pool: "door" and "yard" take turns, one
+    // minute each; the 13th record rotates pool 0 out.
+    for i in 0..16u8 {
+        let t = start + chrono::Duration::seconds(60 * (i as i64 / 2));
+        let req = WriteRequest {
+            camera_id: if i % 2 == 0 { "door" } else { "yard" }.to_string(),
+            stream: Stream::Main,
+            start_ts: t,
+            end_ts: t + chrono::Duration::seconds(60),
+            data: vec![i; 100],
+        };
+        tx.send(req).await.expect("send");
+    }
+    drop(tx);
+    handle.join().expect("writer thread");
+
+    // Only "door", and at most one segment per two minutes: 12:00, 12:02.
+    let cold: Vec<_> = archive.index().segments_for_camera("door").into_iter().cloned().collect();
+    assert_eq!(cold.iter().map(|s| s.segment_id).collect::<Vec<_>>(), [0, 4]);
+    assert!(archive.index().segments_for_camera("yard").is_empty());
+    assert!(archive_dir.path().join("door").join("2026-10-16.arc").is_file());
+
+    // Ring and archive answer as one timeline.
+    let hot = index.read();
+    let archive_index = archive.index();
+    let all = archive::segments_in_range(&hot, Some(&archive_index), "door", start, start + chrono::Duration::hours(1));
+    assert_eq!(all.len(), 7);
+    assert!(all[0].is_archived() && all[1].is_archived() && !all[2].is_archived());
+    assert!(all.windows(2).all(|w| w[0].start_ts() < w[1].start_ts()));
+    assert_eq!(archive::read_tiered(&pool.read(), Some(&archive), &all[1]).expect("read"), [4u8; 100]);
+    let playlist = nvr::hls::generate_vod_playlist(
+        &hot,
+        Some(&archive_index),
+        "door",
+        start,
+        start + chrono::Duration::hours(1),
+        60,
+    )
+    .expect("playlist");
+    assert!(playlist.contains("segment/mp4/0\n") && playlist.contains("segment/mp4/14\n"));
+    drop(archive_index);
+    drop(hot);
+
+    let reopened = Archive::open(&config, None).expect("reopen archive");
+    assert_eq!(reopened.index().len(), 2);
+    assert_eq!(reopened.read_by_id("door", 0).expect("read"), Some(vec![0u8; 100]));
+    drop(reopened);
+
+    // Damage mid-file costs only the damaged entry, and the file is kept.
+    let day_file = archive_dir.path().join("door").join("2026-10-16.arc");
+    let len = std::fs::metadata(&day_file).expect("day file").len();
+    corrupt_byte(&day_file, 0);
+    let reopened = Archive::open(&config, None).expect("reopen archive");
+    assert_eq!(reopened.index().len(), 1);
+    assert_eq!(reopened.read_by_id("door", 4).expect("read"), Some(vec![4u8; 100]));
+    assert_eq!(std::fs::metadata(&day_file).expect("day file").len(), len);
+    drop(reopened);
+
+    // A torn last entry is cut off.
+    {
+        use std::io::Write;
+        let mut f = std::fs::OpenOptions::new().append(true).open(&day_file).expect("open");
+        f.write_all(b"NARC\x01\0\0\0").expect("append");
+    }
+    let reopened = Archive::open(&config, None).expect("reopen archive");
+    assert_eq!(reopened.index().len(), 1);
+    assert_eq!(std::fs::metadata(&day_file).expect("day file").len(), len);
+}
+
+#[tokio::test]
+async fn test_protected_camera_survives_noisy_camera() {
+    use nvr::config::CameraConfig;
+    use nvr::storage::global_writer::{spawn_writer, WriteRequest};
+
+    let dir = tmp_dir();
+    let pool = ChunkPool::open(dir.path(), 1024, 3).expect("open");
+    let pool = std::sync::Arc::new(parking_lot::RwLock::new(pool));
+    let cam = |id: &str, min_retention_days, max_share| CameraConfig {
+        id: id.to_string(),
+        name: id.to_string(),
+        url: "rtsp://example".to_string(),
+        max_reconnect_attempts: 0,
+        min_retention_days,
+        max_share,
+        priority: 0,
+        record_audio: false,
+        substream_url: None,
+    };
+    // "lobby" is protected but may only hold 1% of the ring — less than
+    // one record — so it expires like an unprotected camera.
+    let policy = RetentionPolicy::from_cameras(&[
+        cam("door", 7, 1.0),
+        cam("lobby", 7, 0.01),
+        cam("yard", 0, 1.0),
+    ]);
+    let retention = std::sync::Arc::new(parking_lot::RwLock::new(policy));
+    let (tx, index, handle) = spawn_writer(pool.clone(), retention, 64);
+
+    let now = Utc::now();
+    let send = |camera: &str, byte: u8| WriteRequest {
+        camera_id: camera.to_string(),
+        stream: Stream::Main,
+        start_ts: now,
+        end_ts: now,
+        data: vec![byte; 100],
+    };
+    for (camera, byte) in [("door", 0xD0), ("lobby", 0x10), ("yard", 0x7A)] {
+        tx.send(send(camera, byte)).await.expect("send");
+    }
+    // 164-byte records, 6 per pool: the noisy camera laps the ring 3 times.
+    for _ in 0..54 {
+        tx.send(send("noisy", 0xEE)).await.expect("send");
+    }
+    drop(tx);
+    handle.join().expect("writer thread");
+
+    let idx = index.read();
+    assert!(idx.segments_for_camera("yard").is_empty());
+    assert!(idx.segments_for_camera("lobby").is_empty());
+    let door = idx.segments_for_camera("door");
+    assert_eq!(door.len(), 1);
+    assert_ne!(door[0].segment_id, 0, "relocated segments get a new ID");
+    let data = pool.read().read_segment_data("door", &door[0].location).expect("read");
+    assert_eq!(data, [0xD0u8; 100]);
+}
+
+#[test]
+fn test_hold_survives_rotation_and_restart() {
+    use nvr::storage::vault::{HoldRequest, Vault};
+
+    let dir = tmp_dir();
+    let mut pool = ChunkPool::open(dir.path(), 1024, 2).expect("open");
+    let mut index = SegmentIndex::new();
+    let t0 = Utc::now();
+    let at = |s: i64| t0 + chrono::Duration::seconds(s);
+    for i in 0..3u8 {
+        let (start, end) = (at(i as i64 * 10), at(i as i64 * 10 + 10));
+        let loc = pool.append("cam1", start, end, &[i; 100]).expect("append");
+        index.insert("cam1", start, end, loc);
+    }
+
+    let vault = Vault::new(dir.path(), None);
+    let req = HoldRequest {
+        camera_id: "cam1".to_string(),
+        from: at(10),
+        to: at(30),
+        expires_at: Some(at(3600)),
+        reason: "incident 42".to_string(),
+    };
+    let segments: Vec<_> =
+        index.segments_in_range("cam1", req.from, req.to).into_iter().cloned().collect();
+    let hold = vault.create_hold(&req, &segments, &pool, t0).expect("hold");
+    assert_eq!(hold.segments.iter().map(|s| s.segment_id).collect::<Vec<_>>(), [1, 2]);
+    assert!(vault.create_hold(&req, &[], &pool, t0).is_err());
+
+    // 164-byte records, 6 per pool: lap the ring so the originals are gone.
+    for _ in 0..12 {
+        pool.append("cam2", t0, t0, &[0xEEu8; 100]).expect("append");
+    }
+    let first = &index.segments_for_camera("cam1")[1];
+    assert!(pool.read_segment_data("cam1", &first.location).is_err());
+
+    // A fresh handle (as after a restart) still finds the copies.
+    let vault = Vault::new(dir.path(), None);
+    assert_eq!(vault.read_segment("cam1", 1).expect("read"), Some(vec![1u8; 100]));
+    assert_eq!(vault.read_segment("cam1", 0).expect("read"), None);
+    assert_eq!(vault.read_segment("cam2", 1).expect("read"), None);
+    let holds = vault.list().expect("list");
+    assert_eq!(holds.len(), 1);
+    assert_eq!(holds[0].reason, "incident 42");
+
+    // Not yet expired, then expired.
+    assert!(vault.purge_expired(at(60)).expect("purge").is_empty());
+    assert_eq!(vault.purge_expired(at(3600)).expect("purge"), [hold.id]);
+    assert!(vault.list().expect("list").is_empty());
+    assert!(!vault.release(hold.id).expect("release"));
+
+    // With encryption at rest, the copies are sealed too.
+    let cipher = nvr::storage::crypto::RecordCipher::new(&[7u8; 32]);
+    let loc = pool.append("cam1", at(40), at(50), &[0x42u8; 100]).expect("append");
+    let seg = index.insert("cam1", at(40), at(50), loc);
+    let segments: Vec<_> = index.segment("cam1", seg).into_iter().cloned().collect();
+    let req = HoldRequest { from: at(40), to: at(50), ..req };
+    let hold = Vault::new(dir.path(), Some(cipher.clone())).create_hold(&req, &segments, &pool, t0).expect("hold");
+    let copy = std::fs::read(vault.segment_path(hold.id, seg)).expect("copy");
+    assert!(hold.segments[0].encrypted);
+    assert!(!copy.windows(16).any(|w| w == [0x42u8; 16]), "copy is not in the clear");
+    let sealed = Vault::new(dir.path(), Some(cipher));
+    assert_eq!(sealed.read_segment("cam1", seg).expect("read"), Some(vec![0x42u8; 100]));
+    assert!(vault.read_segment("cam1", seg).is_err(), "no key, no footage");
+}
+
+#[test]
+fn test_read_errors_are_not_reported_as_evictions() {
+    let dir = tmp_dir();
+    let now = Utc::now();
+    let mut pool = ChunkPool::open(dir.path(), 1024 * 1024, 2).expect("open");
+    let loc = pool.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+    let path = dir.path().join("pool_000.bin");
+
+    // Cut short under the reader: the record is gone.
+    std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(loc.record_offset + 10).unwrap();
+    assert!(matches!(pool.read_segment_data("cam1", &loc), Err(NvrError::SegmentEvicted { .. })));
+
+    // The read itself fails (EISDIR standing in for EIO): a storage error.
+    std::fs::remove_file(&path).unwrap();
+    std::fs::create_dir(&path).unwrap();
+    assert!(matches!(pool.read_segment_data("cam1", &loc), Err(NvrError::Io(_))));
+}
+
+#[test]
+fn test_read_view_reads_without_touching_pool_files() {
+    let dir = tmp_dir();
+    let now = Utc::now();
+    // 164-byte records, 6 per 1 KB pool.
+    let mut pool = ChunkPool::open(dir.path(), 1024, 2).expect("open");
+    let loc = pool.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+    let header = std::fs::read(dir.path().join("pool_001.bin")).expect("read")[..64].to_vec();
+
+    let mut view = pool.read_view();
+    assert_eq!(view.read_segment_data("cam1", &loc).expect("read"), [0x11u8; 100]);
+    assert!(view.append("cam1", now, now, &[0u8; 100]).is_err());
+    view.sync().expect("nothing to sync");
+    assert_eq!(std::fs::read(dir.path().join("pool_001.bin")).expect("read")[..64], header[..]);
+
+    // Rotated out after the view was taken: caught like any stale read.
+    for _ in 0..12 {
+        pool.append("cam1", now, now, &[0x22u8; 100]).expect("append");
+    }
+    assert!(matches!(view.read_segment_data("cam1", &loc), Err(NvrError::SegmentEvicted { .. })));
+}
+
+#[test]
+fn test_fsck_reports_and_repairs_damaged_pool() {
+    use nvr::storage::fsck;
+
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024 * 1024;
+    let now = Utc::now();
+
+    let bad = {
+        let mut pool = ChunkPool::open(dir.path(), pool_size, 3).expect("open");
+        pool.append("cam1", now, now, &[0x11u8; 100]).expect("append");
+        let bad = pool.append("cam1", now, now, &[0x22u8; 100]).expect("append");
+        pool.append("cam1", now, now, &[0x33u8; 100]).expect("append");
+        bad
+    };
+    corrupt_byte(&dir.path().join("pool_000.bin"), bad.record_offset + bad.header_size + 1);
+
+    let report = fsck::check_pools(dir.path(), &[], 3, false).expect("fsck");
+    assert!(!report.is_clean());
+    assert_eq!(report.pools.len(), 3);
+    assert_eq!(report.pools[0].good_records, 1);
+    assert_eq!(report.pools[0].first_bad_offset, Some(bad.record_offset));
+    assert!(report.pools[1].is_clean(), "untouched pre-allocated pool is fine");
+
+    let repaired = fsck::check_pools(dir.path(), &[], 3, true).expect("fsck --repair");
+    assert!(repaired.is_repaired());
+    assert!(fsck::check_pools(dir.path(), &[], 3, false).expect("fsck").is_clean());
+
+    // Recording resumes right after the last good record.
+    let mut pool = ChunkPool::open(dir.path(), pool_size, 3).expect("reopen");
+    let loc = pool.append("cam1", now, now, &[0x44u8; 10]).expect("append");
+    assert_eq!(loc.pool_idx, 0);
+    assert_eq!(loc.record_offset, bad.record_offset);
+}
+
 #[test]
 fn test_segments_in_range() {
     let dir = tmp_dir();
@@ -237,6 +1479,377 @@ fn test_segments_in_range() {
     assert_eq!(wrong_cam.len(), 0);
 }
 
+#[test]
+fn test_coverage_merges_spans_and_reports_gaps() {
+    use nvr::storage::index::TimeSpan;
+
+    let dir = tmp_dir();
+    let mut pool = ChunkPool::open(dir.path(), 1024 * 1024, 3).expect("open");
+    let mut index = SegmentIndex::new();
+    let at = |s: &str| {
+        chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").unwrap().and_utc()
+    };
+    let mut record = |start: &str, end: &str| {
+        let loc = pool.append("cam1", at(start), at(end), b"data").expect("append");
+        index.insert("cam1", at(start), at(end), loc);
+    };
+    // A 1 s hiccup is still continuous; a 2 s one is a gap, as in HLS.
+    record("2026-02-19T14:00:00", "2026-02-19T14:05:00");
+    record("2026-02-19T14:05:01", "2026-02-19T14:10:00");
+    record("2026-02-19T14:30:00", "2026-02-19T14:35:00");
+    record("2026-02-19T14:35:02", "2026-02-19T14:40:00");
+    record("2026-02-19T14:59:30", "2026-02-19T15:00:30");
+
+    let span = |start: &str, end: &str| TimeSpan { start: at(start), end: at(end) };
+    let cov = index.coverage("cam1", at("2026-02-19T13:30:00"), at("2026-02-19T15:30:00"));
+    assert_eq!(
+        cov.spans,
+        vec![
+            span("2026-02-19T14:00:00", "2026-02-19T14:10:00"),
+            span("2026-02-19T14:30:00", "2026-02-19T14:35:00"),
+            span("2026-02-19T14:35:02", "2026-02-19T14:40:00"),
+            span("2026-02-19T14:59:30", "2026-02-19T15:00:30"),
+        ]
+    );
+    assert_eq!(
+        cov.gaps,
+        vec![
+            span("2026-02-19T13:30:00", "2026-02-19T14:00:00"),
+            span("2026-02-19T14:10:00", "2026-02-19T14:30:00"),
+            span("2026-02-19T14:35:00", "2026-02-19T14:35:02"),
+            span("2026-02-19T14:40:00", "2026-02-19T14:59:30"),
+            span("2026-02-19T15:00:30", "2026-02-19T15:30:00"),
+        ]
+    );
+    assert_eq!(cov.recorded(), chrono::Duration::seconds(600 + 300 + 298 + 60));
+
+    // Hourly buckets are clipped to the range; the span across 15:00 is split.
+    let hours = cov.buckets(chrono::Duration::hours(1));
+    let summary: Vec<_> = hours.iter().map(|b| (b.span, b.recorded.num_seconds())).collect();
+    assert_eq!(
+        summary,
+        vec![
+            (span("2026-02-19T13:30:00", "2026-02-19T14:00:00"), 0),
+            (span("2026-02-19T14:00:00", "2026-02-19T15:00:00"), 1228),
+            (span("2026-02-19T15:00:00", "2026-02-19T15:30:00"), 30),
+        ]
+    );
+    assert!((hours[2].ratio() - 30.0 / 1800.0).abs() < 1e-9);
+    let days = cov.buckets(chrono::Duration::days(1));
+    assert_eq!(days.len(), 1);
+    assert_eq!(days[0].recorded, cov.recorded());
+
+    // Clipped to a range starting mid-segment; nothing recorded at all.
+    let cov = index.coverage("cam1", at("2026-02-19T14:02:00"), at("2026-02-19T14:10:01"));
+    assert_eq!(cov.spans, vec![span("2026-02-19T14:02:00", "2026-02-19T14:10:00")]);
+    assert!(cov.gaps.is_empty());
+    let cov = index.coverage("cam2", at("2026-02-19T14:00:00"), at("2026-02-19T15:00:00"));
+    assert!(cov.spans.is_empty());
+    assert_eq!(cov.gaps, vec![span("2026-02-19T14:00:00", "2026-02-19T15:00:00")]);
+}
+
+#[test]
+fn test_thumbnails_cached_by_segment_and_pruned() {
+    use nvr::storage::crypto::RecordCipher;
+    use nvr::thumbnail::ThumbnailCache;
+    use std::cell::Cell;
+
+    let dir = tmp_dir();
+    let cache = ThumbnailCache::new(dir.path(), None);
+    let renders = Cell::new(0);
+    let render = |jpeg: &'static [u8]| {
+        renders.set(renders.get() + 1);
+        Ok(Some(jpeg.to_vec()))
+    };
+
+    // Rendered on first request only.
+    for _ in 0..2 {
+        let jpeg = cache.get_or_create("cam1", 7, || render(b"jpeg-7")).expect("thumbnail");
+        assert_eq!(jpeg.as_deref(), Some(&b"jpeg-7"[..]));
+    }
+    assert_eq!(renders.get(), 1);
+    assert!(cache.dir().join("cam1").join("7.jpg").exists());
+    cache.get_or_create("cam1", 8, || render(b"jpeg-8")).expect("thumbnail");
+    cache.get_or_create("cam2", 7, || render(b"jpeg-2-7")).expect("thumbnail");
+
+    // Missing segments, and IDs that would leave the cache, aren't cached.
+    assert!(cache.get_or_create("cam1", 9, || Ok(None)).expect("missing").is_none());
+    let outside = cache.get_or_create("..", 7, || render(b"outside")).expect("thumbnail");
+    assert_eq!(outside.as_deref(), Some(&b"outside"[..]));
+    assert!(cache.get("..", 7).expect("get").is_none());
+    assert!(!dir.path().join("7.jpg").exists());
+
+    // Thumbnails of segments gone everywhere are pruned.
+    let removed = cache.prune(|camera_id, id| camera_id == "cam1" && id == 7).expect("prune");
+    assert_eq!(removed, 2);
+    assert!(cache.get("cam1", 7).expect("get").is_some());
+    assert!(cache.get("cam1", 8).expect("get").is_none());
+
+    // Sealed at rest with the recordings' key; made again after a key change.
+    let sealed = ThumbnailCache::new(dir.path(), Some(RecordCipher::new(&[7u8; 32])));
+    sealed.get_or_create("cam3", 1, || render(b"secret-frame")).expect("thumbnail");
+    let on_disk = std::fs::read(sealed.dir().join("cam3").join("1.jpg")).expect("read");
+    assert!(!on_disk.windows(12).any(|w| w == b"secret-frame"));
+    assert_eq!(sealed.get("cam3", 1).expect("get").as_deref(), Some(&b"secret-frame"[..]));
+    let rekeyed = ThumbnailCache::new(dir.path(), Some(RecordCipher::new(&[8u8; 32])));
+    assert!(rekeyed.get("cam3", 1).expect("get").is_none());
+
+    // The VOD thumbnail track runs on the playlist's timeline: gaps between
+    // segments don't count.
+    let mut pool = ChunkPool::open(dir.path(), 1024 * 1024, 3).expect("open");
+    let mut index = SegmentIndex::new();
+    let t0 = Utc::now();
+    let secs = |s: i64| t0 + chrono::Duration::seconds(s);
+    let mut ids = Vec::new();
+    for (start, end) in [(0, 60), (90, 150)] {
+        let loc = pool.append("cam1", secs(start), secs(end), b"data").expect("append");
+        ids.push(index.insert("cam1", secs(start), secs(end), loc));
+    }
+    let vtt = nvr::hls::generate_thumbnail_track(&index, None, "cam1", secs(0), secs(150), 60)
+        .expect("track");
+    assert_eq!(
+        vtt,
+        format!(
+            "WEBVTT\n\n00:00:00.000 --> 00:01:00.000\nthumbnail/{}\n\n\
+             00:01:00.000 --> 00:02:00.000\nthumbnail/{}\n",
+            ids[0], ids[1]
+        )
+    );
+    assert!(nvr::hls::generate_thumbnail_track(&index, None, "cam2", secs(0), secs(150), 60).is_none());
+}
+
+#[test]
+fn test_codec_strings_read_from_segment_sample_entries() {
+    use nvr::codec::codec_string;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut b = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(kind);
+        b.extend_from_slice(payload);
+        b
+    }
+    // trak/mdia/{hdlr,minf/stbl/stsd/<entry>/<config>}, as muxed by mp4mux.
+    let trak = |handler: &[u8; 4], entry: &[u8; 4], config_kind: &[u8; 4], config: &[u8]| {
+        let fixed = if handler == b"soun" { 28 } else { 78 };
+        let mut entry_payload = vec![0u8; fixed];
+        entry_payload.extend(mp4_box(config_kind, config));
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(entry, &entry_payload));
+        let mut hdlr = vec![0u8; 8];
+        hdlr.extend_from_slice(handler);
+        hdlr.extend_from_slice(&[0u8; 13]);
+        let minf = mp4_box(b"minf", &mp4_box(b"stbl", &mp4_box(b"stsd", &stsd)));
+        mp4_box(b"trak", &mp4_box(b"mdia", &[mp4_box(b"hdlr", &hdlr), minf].concat()))
+    };
+    let segment = |traks: &[Vec<u8>]| {
+        let mut data = mp4_box(b"ftyp", b"iso6");
+        data.extend(mp4_box(b"moov", &traks.concat()));
+        data
+    };
+
+    let avc = segment(&[trak(b"vide", b"avc1", b"avcC", &[1, 0x64, 0x00, 0x1F, 0xFF])]);
+    assert_eq!(codec_string(&avc).as_deref(), Some("avc1.64001F"));
+    assert!(!nvr::codec::has_audio(&avc));
+
+    // Main profile, Main-compatible, progressive source, level 4.0.
+    let hvcc = [1, 0x01, 0x60, 0, 0, 0, 0x90, 0, 0, 0, 0, 0, 120];
+    let hevc = segment(&[trak(b"vide", b"hvc1", b"hvcC", &hvcc)]);
+    assert_eq!(codec_string(&hevc).as_deref(), Some("hvc1.1.6.L120.90"));
+
+    let av1 = segment(&[trak(b"vide", b"av01", b"av1C", &[0x81, 0x08, 0x0C, 0x00])]);
+    assert_eq!(codec_string(&av1).as_deref(), Some("av01.0.08M.08"));
+
+    // AAC-LC audio follows the video codec, whichever track comes first.
+    let esds = [
+        0, 0, 0, 0, // version/flags
+        0x03, 0x80, 0x80, 0x80, 0x19, 0, 1, 0, // ES_Descriptor, ES_ID 1, no flags
+        0x04, 0x11, 0x40, 0x15, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // DecoderConfig, MPEG-4 audio
+        0x05, 0x02, 0x12, 0x10, // AudioSpecificConfig: AAC LC, 44.1 kHz, stereo
+        0x06, 0x01, 0x02, // SLConfig
+    ];
+    let aac = trak(b"soun", b"mp4a", b"esds", &esds);
+    let with_audio = segment(&[aac.clone(), trak(b"vide", b"hvc1", b"hvcC", &hvcc)]);
+    assert_eq!(codec_string(&with_audio).as_deref(), Some("hvc1.1.6.L120.90,mp4a.40.2"));
+    assert!(nvr::codec::has_audio(&with_audio));
+
+    // No video, unknown video codecs and truncated data have no string;
+    // unknown audio codecs are left out.
+    assert!(codec_string(&segment(&[aac])).is_none());
+    assert!(codec_string(&segment(&[trak(b"vide", b"mp4v", b"esds", &[0; 16])])).is_none());
+    assert!(codec_string(&hevc[..hevc.len() - 4]).is_none());
+    let alaw = trak(b"soun", b"alaw", b"wave", &[0; 8]);
+    let with_alaw = segment(&[trak(b"vide", b"hvc1", b"hvcC", &hvcc), alaw]);
+    assert_eq!(codec_string(&with_alaw).as_deref(), Some("hvc1.1.6.L120.90"));
+
+    assert_eq!(
+        nvr::hls::generate_master_playlist(&[nvr::hls::Variant {
+            uri: "live.m3u8".to_string(),
+            codecs: Some("hvc1.1.6.L120.90".to_string()),
+            bandwidth: 4_000_000,
+        }]),
+        "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-INDEPENDENT-SEGMENTS\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=4000000,CODECS=\"hvc1.1.6.L120.90\"\nlive.m3u8\n"
+    );
+}
+
+#[test]
+fn test_substream_tagged_in_pool_and_snapshot_and_kept_out_of_main_queries() {
+    use nvr::storage::chunk_pool::NewRecord;
+
+    let dir = tmp_dir();
+    let pool_size: u64 = 1024 * 1024;
+    let t0 = Utc::now() - chrono::Duration::seconds(60);
+    let t1 = t0 + chrono::Duration::seconds(2);
+
+    {
+        let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("open");
+        pool.append("cam1", t0, t1, &[0x11u8; 400]).expect("append");
+        let sub = NewRecord { camera_id: "cam1", stream: Stream::Sub, start_ts: t0, end_ts: t1, data: &[0x22u8; 100] };
+        pool.append_batch(&[sub]).expect("append batch");
+        save_snapshot(&pool);
+        // After the snapshot: its tag comes from the tail scan instead.
+        let sub = NewRecord { camera_id: "cam1", stream: Stream::Sub, start_ts: t1, end_ts: t1 + chrono::Duration::seconds(2), data: &[0x33u8; 100] };
+        pool.append_batch(&[sub]).expect("append batch");
+    }
+
+    let mut pool = ChunkPool::open(dir.path(), pool_size, 2).expect("reopen");
+    let records = pool.take_recovered();
+    assert_eq!(
+        records.iter().map(|r| r.stream).collect::<Vec<_>>(),
+        [Stream::Main, Stream::Sub, Stream::Sub]
+    );
+    let mut index = SegmentIndex::new();
+    index.rebuild_from_scanned(records);
+
+    // Listing, ranges and coverage see the main stream only.
+    let main = index.segments_for_camera("cam1");
+    assert_eq!(main.len(), 1);
+    assert_eq!(index.segments_in_range("cam1", t0, t1).len(), 1);
+    let sub = index.latest_for_stream("cam1", Stream::Sub, 10);
+    assert_eq!(sub.len(), 2);
+    assert!(sub[0].start_ts < sub[1].start_ts);
+
+    // Segment IDs are unique across streams, and either can be looked up.
+    let found = index.segment("cam1", sub[1].segment_id).expect("substream segment");
+    assert_eq!(found.location.stream, Stream::Sub);
+    assert_eq!(pool.read_segment_data("cam1", &found.location).expect("read"), [0x33u8; 100]);
+    assert_eq!(index.segment("cam1", main[0].segment_id).expect("main segment").location.stream, Stream::Main);
+
+    let live = nvr::hls::generate_live_playlist(&index, "cam1", Stream::Sub, 2, None).expect("playlist");
+    assert_eq!(live.matches("#EXTINF").count(), 2);
+    assert!(live.contains(&format!("segment/mp4/{}", sub[1].segment_id)));
+    let mpd = nvr::dash::generate_live_mpd(&index, "cam1", 2, None, None).expect("mpd");
+    assert!(mpd.contains(r#"<Representation id="cam1" "#));
+    assert!(mpd.contains(r#"<Representation id="cam1-sub" "#));
+
+    let variant = |uri: &str, bandwidth| nvr::hls::Variant { uri: uri.to_string(), codecs: None, bandwidth };
+    assert_eq!(
+        nvr::hls::generate_master_playlist(&[variant("live.m3u8", 4_000_000), variant("live.m3u8?stream=sub", 500_000)]),
+        "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-INDEPENDENT-SEGMENTS\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=4000000\nlive.m3u8\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=500000\nlive.m3u8?stream=sub\n"
+    );
+}
+
+/// 150 cameras, 1000 one-minute segments each from `t0`, inserted
+/// interleaved so no camera's segments are contiguous in the index.
+fn per_camera_index(t0: chrono::DateTime<Utc>) -> SegmentIndex {
+    use nvr::storage::chunk_pool::SegmentLocation;
+
+    let mut index = SegmentIndex::new();
+    let minute = |m: i64| t0 + chrono::Duration::minutes(m);
+    let mut seq = 0u64;
+    for m in 0..1000 {
+        for cam in 0..150 {
+            let loc = SegmentLocation {
+                pool_idx: 0,
+                pool_id: 0,
+                record_offset: 64,
+                record_size: 100,
+                header_size: 64,
+                sequence: Some(seq),
+                stream: Stream::Main,
+            };
+            index.insert(&format!("cam{cam:03}"), minute(m), minute(m + 1), loc);
+            seq += 1;
+        }
+    }
+    index
+}
+
+#[test]
+fn test_per_camera_queries_match_a_full_scan() {
+    let t0 = Utc::now();
+    let minute = |m: i64| t0 + chrono::Duration::minutes(m);
+    let index = per_camera_index(t0);
+
+    let scan = |camera_id: &str, from, to| -> Vec<u64> {
+        index
+            .all_segments()
+            .filter(|m| m.camera_id == camera_id && m.start_ts < to && m.end_ts > from)
+            .map(|m| m.segment_id)
+            .collect()
+    };
+    let ids = |segs: Vec<&nvr::storage::index::SegmentMeta>| -> Vec<u64> {
+        segs.iter().map(|m| m.segment_id).collect()
+    };
+
+    // Mid-segment start, boundary end, and the edges of the recording.
+    let ranges = [
+        (minute(500) + chrono::Duration::seconds(30), minute(510)),
+        (minute(-5), minute(3)),
+        (minute(995), minute(1200)),
+        (minute(1000), minute(1010)),
+    ];
+    for cam in ["cam000", "cam075", "cam149"] {
+        for &(from, to) in &ranges {
+            assert_eq!(ids(index.segments_in_range(cam, from, to)), scan(cam, from, to));
+        }
+        let all = scan(cam, minute(0), minute(1000));
+        assert_eq!(all.len(), 1000);
+        assert_eq!(ids(index.segments_for_camera(cam)), all);
+        assert_eq!(ids(index.latest_for_camera(cam, 10)), all[990..]);
+        assert_eq!(ids(index.latest_for_camera(cam, 5000)), all);
+    }
+    let (from, to) = ranges[0];
+    assert_eq!(index.segments_in_range("cam075", from, to).len(), 10);
+    assert!(index.segments_in_range("cam075", ranges[3].0, ranges[3].1).is_empty());
+    assert!(index.segments_in_range("cam999", from, to).is_empty());
+    assert!(index.latest_for_camera("cam999", 10).is_empty());
+}
+
+/// Wall-clock comparison, so not run by default:
+/// `cargo test --release --test storage_test -- --ignored --nocapture bench_`
+#[test]
+#[ignore = "benchmark; run in release with --ignored --nocapture"]
+fn bench_per_camera_queries_against_full_scan() {
+    use std::time::{Duration, Instant};
+
+    let t0 = Utc::now();
+    let index = per_camera_index(t0);
+    let from = t0 + chrono::Duration::seconds(500 * 60 + 30);
+    let to = t0 + chrono::Duration::minutes(510);
+    let scan = || -> usize {
+        index
+            .all_segments()
+            .filter(|m| m.camera_id == "cam075" && m.start_ts < to && m.end_ts > from)
+            .count()
+    };
+    let time = |f: &dyn Fn() -> usize| -> Duration {
+        let started = Instant::now();
+        for _ in 0..50 {
+            std::hint::black_box(f());
+        }
+        started.elapsed()
+    };
+    let full_scan = time(&scan);
+    let range = time(&|| index.segments_in_range("cam075", from, to).len());
+    let latest = time(&|| index.latest_for_camera("cam075", 10).len());
+    println!("150k segments, 50 queries: full scan {full_scan:?}, range {range:?}, latest {latest:?}");
+    assert!(range * 10 < full_scan, "range query {range:?} vs full scan {full_scan:?}");
+    assert!(latest * 10 < full_scan, "latest query {latest:?} vs full scan {full_scan:?}");
+}
+
 #[test]
 fn test_export_range_end_to_end() {
     let dir = tmp_dir();
@@ -257,7 +1870,7 @@ fn test_export_range_end_to_end() {
 
     // Export to file.
     let out_path = dir.path().join("export.ts");
-    let count = nvr::playback::export_range(&pool, &index, "cam1", t0, t2, &out_path)
+    let count = nvr::playback::export_range(&pool, &index, None, "cam1", t0, t2, &out_path)
         .expect("export");
     assert_eq!(count, 2);
 